@@ -1,5 +1,7 @@
 extern crate hprof;
 
+// the loop stands in for a game's main loop, and only runs once
+#[allow(clippy::never_loop)]
 fn main() {
     let p = hprof::Profiler::new("main loop");
 
@@ -8,32 +10,32 @@ fn main() {
 
         {
             let _g = p.enter("setup");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
         }
         {
             let _g = p.enter("physics");
 
             let _g = p.enter("collision");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             drop(_g);
 
             let _g = p.enter("update positions");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             drop(_g);
         }
         {
             let _g = p.enter("render");
 
             let _g = p.enter("cull");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             drop(_g);
 
             let _g = p.enter("gpu submit");
-            std::thread::sleep_ms(2);
+            std::thread::sleep(std::time::Duration::from_millis(2));
             drop(_g);
 
             let _g = p.enter("gpu wait");
-            std::thread::sleep_ms(10);
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
 
         p.end_frame();
@@ -1,37 +1,39 @@
 extern crate hprof;
 
+// the loop stands in for a game's main loop, and only runs once
+#[allow(clippy::never_loop)]
 fn main() {
     loop {
         hprof::start_frame();
 
         {
             let _g = hprof::enter("setup");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
         }
         {
             let _g = hprof::enter("physics");
 
             let _g = hprof::enter("collision");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             drop(_g);
 
             let _g = hprof::enter("update positions");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             drop(_g);
         }
         {
             let _g = hprof::enter("render");
 
             let _g = hprof::enter("cull");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             drop(_g);
 
             let _g = hprof::enter("gpu submit");
-            std::thread::sleep_ms(2);
+            std::thread::sleep(std::time::Duration::from_millis(2));
             drop(_g);
 
             let _g = hprof::enter("gpu wait");
-            std::thread::sleep_ms(10);
+            std::thread::sleep(std::time::Duration::from_millis(10));
             drop(_g);
         }
 
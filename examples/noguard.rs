@@ -1,5 +1,7 @@
 extern crate hprof;
 
+// the loop stands in for a game's main loop, and only runs once
+#[allow(clippy::never_loop)]
 fn main() {
     let p = hprof::Profiler::new("main loop");
 
@@ -8,18 +10,18 @@ fn main() {
 
         {
             p.enter_noguard("setup");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             p.leave();
         }
         {
             p.enter_noguard("physics");
 
             p.enter_noguard("collision");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             p.leave();
 
             p.enter_noguard("update positions");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             p.leave();
 
             p.leave();
@@ -28,15 +30,15 @@ fn main() {
             p.enter_noguard("render");
 
             p.enter_noguard("cull");
-            std::thread::sleep_ms(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
             p.leave();
 
             p.enter_noguard("gpu submit");
-            std::thread::sleep_ms(2);
+            std::thread::sleep(std::time::Duration::from_millis(2));
             p.leave();
 
             p.enter_noguard("gpu wait");
-            std::thread::sleep_ms(10);
+            std::thread::sleep(std::time::Duration::from_millis(10));
             p.leave();
 
             p.leave();
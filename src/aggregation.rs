@@ -0,0 +1,25 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! How much detail is kept about individual calls.
+
+/// What a `Profiler` keeps about individual call durations, chosen with
+/// `Profiler::with_aggregation` or `Profiler::set_aggregation`.
+///
+/// Every strategy keeps call counts and total times. The others additionally keep statistics
+/// about single calls for the lifetime of the profiler, rather than resetting them every frame,
+/// and show them in the text report.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Aggregation {
+    /// Counts and totals only. The default, and the cheapest.
+    #[default]
+    Sum,
+    /// Also the shortest and longest call of each node.
+    MinMax,
+    /// Also a histogram of call durations per node, for reporting tail latency precisely.
+    Histogram,
+    /// Also a uniform random sample of up to this many call durations per node.
+    Reservoir(usize),
+}
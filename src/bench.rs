@@ -0,0 +1,64 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Profiling Criterion benchmarks.
+//!
+//! Requires the `criterion` feature.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use criterion::profiler::Profiler as CriterionProfiler;
+
+/// A Criterion profiler that records the thread-local profiler while a benchmark is profiled,
+/// showing where inside the benchmarked function the time goes.
+///
+/// Set it up with `Criterion::with_profiler` and run the benchmarks with `--profile-time`:
+///
+/// ```ignore
+/// fn config() -> Criterion {
+///     Criterion::default().with_profiler(hprof::bench::BenchProfiler)
+/// }
+///
+/// criterion_group!(name = benches; config = config(); targets = bench_pathfinding);
+/// ```
+///
+/// ```text
+/// cargo bench --bench pathfinding -- --profile-time 5
+/// ```
+///
+/// Every iteration is recorded in a single frame, so call counts are totals over all iterations
+/// and averages are per call. Once profiling stops, the timing report is written to `hprof.txt`
+/// and the tab-separated one to `hprof.tsv` in the benchmark's profile directory, next to the
+/// rest of its report.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BenchProfiler;
+
+impl CriterionProfiler for BenchProfiler {
+    fn start_profiling(&mut self, benchmark_id: &str, _benchmark_dir: &Path) {
+        let profiler = ::profiler();
+        profiler.clear();
+        profiler.set_label(benchmark_id);
+        profiler.start_frame();
+    }
+
+    fn stop_profiling(&mut self, benchmark_id: &str, benchmark_dir: &Path) {
+        let profiler = ::profiler();
+        profiler.end_frame();
+        let result = fs::create_dir_all(benchmark_dir)
+            .and_then(|_| write_to(&benchmark_dir.join("hprof.txt"), |w| profiler.write_timing(w)))
+            .and_then(|_| write_to(&benchmark_dir.join("hprof.tsv"), |w| profiler.write_tsv(w)));
+        if let Err(e) = result {
+            error!("Couldn't write the profile of {}: {}", benchmark_id, e);
+        }
+    }
+}
+
+fn write_to<F: FnOnce(&mut BufWriter<File>) -> io::Result<()>>(path: &Path, f: F) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    f(&mut w)?;
+    w.flush()
+}
@@ -0,0 +1,265 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Inspect and convert recordings made with `Recording::save` or the flight recorder.
+
+extern crate hprof;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::time::{Duration, UNIX_EPOCH};
+
+use hprof::{CompareOptions, Nanoseconds, PathStats, ProfileSnapshot, Recording};
+
+const USAGE: &str = "\
+usage: hprof-view <command> <recording> [args]
+       hprof-view merge <output> <recording>...
+       hprof-view compare <before> <after> [min %]
+
+commands:
+    summary <recording> [key=value]       statistics of every node over all frames, or only
+                                          those with the given tag
+    frames <recording>                    list frames, their total times and when they started
+                                          and ended
+    frame <recording> <index|slowest>     show the tree of one frame
+    at <recording> <unix time>            show the tree of the frame in progress at a time in
+                                          seconds since the Unix epoch, such as 1444000000.123
+    history <recording> <path>            total time of one node in every frame
+    convert <recording> <output> <format> convert to one of the formats below
+    merge <output> <recording>...         combine recordings and compare them
+    compare <before> <after> [min %]      list the paths that got slower or faster by more than
+                                          min % (5 by default) and 1us per frame; exits with
+                                          status 3 if any got slower";
+
+// formats `convert` can write
+#[cfg(not(feature = "parquet"))]
+const FORMATS: &[&str] = &["chrome", "perfetto", "firefox", "msgpack", "delta"];
+#[cfg(feature = "parquet")]
+const FORMATS: &[&str] = &["chrome", "perfetto", "firefox", "msgpack", "delta", "parquet"];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        usage();
+    }
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if args[0] == "merge" {
+        if args.len() < 3 {
+            usage();
+        }
+        let recordings: Vec<Recording> = args[2..].iter().map(|path| load(path)).collect();
+        if let Err(e) = merge(&mut out, &args[1], &args[2..], recordings) {
+            eprintln!("hprof-view: {}", e);
+            process::exit(1);
+        }
+        return
+    }
+    if args[0] == "compare" {
+        let min_ratio = match args.get(3).map(|s| s.parse::<f64>()) {
+            Some(Ok(percent)) => percent / 100.0,
+            Some(Err(_)) => usage(),
+            None => CompareOptions::default().min_ratio,
+        };
+        if args.len() < 3 || args.len() > 4 {
+            usage();
+        }
+        let opts = CompareOptions { min_ratio, ..CompareOptions::default() };
+        let comparison = load(&args[1]).compare_with(&load(&args[2]), &opts);
+        if let Err(e) = hprof::compare::write_comparison(&mut out, &comparison) {
+            eprintln!("hprof-view: {}", e);
+            process::exit(1);
+        }
+        if !comparison.regressions.is_empty() {
+            process::exit(3);
+        }
+        return
+    }
+    let recording = load(&args[1]);
+    let result = match (&*args[0], &args[2..]) {
+        ("summary", []) => summary(&mut out, &recording),
+        ("summary", [tag]) => match tag.find('=') {
+            Some(i) => summary(&mut out, &recording.filter_tag(&tag[..i], &tag[i + 1..])),
+            None => Err(error(format!("expected key=value, got {}", tag))),
+        },
+        ("frames", []) => frames(&mut out, &recording),
+        ("frame", [which]) => frame(&mut out, &recording, which),
+        ("at", [time]) => at(&mut out, &recording, time),
+        ("history", [path]) => history(&mut out, &recording, path),
+        ("convert", [output, format]) => convert(&recording, output, format),
+        _ => {
+            usage();
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("hprof-view: {}", e);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}\n\nformats: {}", USAGE, FORMATS.join(", "));
+    process::exit(2);
+}
+
+fn load(path: &str) -> Recording {
+    match Recording::load(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("hprof-view: can't read {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn error(msg: String) -> io::Error {
+    io::Error::other(msg)
+}
+
+fn summary<W: Write>(w: &mut W, recording: &Recording) -> io::Result<()> {
+    for (key, value) in &recording.metadata {
+        writeln!(w, "{}: {}", key, value)?;
+    }
+    let stats = recording.path_stats();
+    let count = recording.frames.len() as u64;
+    let totals: Vec<u64> = recording.frames.iter().map(|f| f.total_time()).collect();
+    writeln!(w, "{} frames", count)?;
+    if let Some(mean) = totals.iter().sum::<u64>().checked_div(count) {
+        writeln!(w, "frame time: mean {}, min {}, max {}",
+                 Nanoseconds(mean),
+                 Nanoseconds(*totals.iter().min().unwrap()),
+                 Nanoseconds(*totals.iter().max().unwrap()))?;
+    }
+    let width = stats.iter().map(|s| s.path.len()).max().unwrap_or(0).max("path".len());
+    writeln!(w, "{:<width$}  {:>6}  {:>11}  {:>9}  {:>9}  {:>9}", "path", "frames", "calls/frame", "mean", "min", "max",
+             width = width)?;
+    for s in &stats {
+        writeln!(w, "{:<width$}  {:>6}  {:>11.1}  {:>9}  {:>9}  {:>9}", s.path, s.frames, s.calls_per_frame(),
+                 Nanoseconds::from(s.mean()), Nanoseconds::from(s.min), Nanoseconds::from(s.max),
+                 width = width)?;
+    }
+    Ok(())
+}
+
+// Save the combination of `recordings` to `output`, and compare the mean time of every path in
+// each of them with the combined mean.
+fn merge<W: Write>(w: &mut W, output: &str, names: &[String], recordings: Vec<Recording>) -> io::Result<()> {
+    let per_input: Vec<Vec<PathStats>> = recordings.iter().map(|r| r.path_stats()).collect();
+    let merged = Recording::merge(recordings);
+    merged.save(output)?;
+    let combined = merged.path_stats();
+    let width = combined.iter().map(|s| s.path.len()).max().unwrap_or(0).max("path".len());
+    let columns: Vec<usize> = names.iter().map(|n| n.len().max(9)).collect();
+    write!(w, "{:<width$}", "path", width = width)?;
+    for (name, &column) in names.iter().zip(&columns) {
+        write!(w, "  {:>column$}", name, column = column)?;
+    }
+    writeln!(w, "  {:>9}", "combined")?;
+    for s in &combined {
+        write!(w, "{:<width$}", s.path, width = width)?;
+        for (stats, &column) in per_input.iter().zip(&columns) {
+            match stats.iter().find(|t| t.path == s.path) {
+                Some(t) => write!(w, "  {:>column$}", Nanoseconds::from(t.mean()), column = column)?,
+                None => write!(w, "  {:>column$}", "-", column = column)?,
+            }
+        }
+        writeln!(w, "  {:>9}", Nanoseconds::from(s.mean()))?;
+    }
+    Ok(())
+}
+
+fn frames<W: Write>(w: &mut W, recording: &Recording) -> io::Result<()> {
+    for (index, frame) in recording.frames.iter().enumerate() {
+        write!(w, "{:>6}  {:>9}  {} nodes, frame {} of the session", index, Nanoseconds(frame.total_time()),
+               frame.nodes.len(), frame.index)?;
+        if let Some(time) = frame.unix_time() {
+            write!(w, " at {:.6}", time as f64 / 1e9)?;
+            if let Some(end) = frame.unix_end_time() {
+                write!(w, " to {:.6}", end as f64 / 1e9)?;
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn frame<W: Write>(w: &mut W, recording: &Recording, which: &str) -> io::Result<()> {
+    let index = if which == "slowest" {
+        (0..recording.frames.len()).max_by_key(|&i| recording.frames[i].total_time())
+    } else {
+        which.parse().ok()
+    };
+    let frame = match index.and_then(|i| recording.frames.get(i)) {
+        Some(frame) => frame,
+        None => return Err(error(format!("no frame {}", which))),
+    };
+    if frame.nodes.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "{} - {}", frame.nodes[0].name, Nanoseconds(frame.total_time()))?;
+    write_children(w, frame, 0, 2)
+}
+
+fn at<W: Write>(w: &mut W, recording: &Recording, time: &str) -> io::Result<()> {
+    let seconds: f64 = time.parse().map_err(|_| error(format!("expected seconds since the Unix epoch, got {}", time)))?;
+    let time = UNIX_EPOCH + Duration::from_nanos((seconds * 1e9) as u64);
+    match recording.frame_at(time) {
+        Some(index) => {
+            writeln!(w, "frame {} of the recording, {} of the session", index, recording.frames[index].index)?;
+            frame(w, recording, &index.to_string())
+        }
+        None => Err(error(format!("no frame in progress at {}", seconds))),
+    }
+}
+
+fn write_children<W: Write>(w: &mut W, frame: &ProfileSnapshot, idx: usize, indent: usize) -> io::Result<()> {
+    let parent_time = frame.nodes[idx].total_time as f64;
+    for (child, node) in frame.children(idx) {
+        writeln!(w, "{:indent$}{} - {} * {} = {} ({:.1}%)", "", node.name, node.calls,
+                 Nanoseconds(node.total_time / node.calls.max(1) as u64), Nanoseconds(node.total_time),
+                 100.0 * node.total_time as f64 / parent_time, indent = indent)?;
+        write_children(w, frame, child, indent + 2)?;
+    }
+    Ok(())
+}
+
+fn history<W: Write>(w: &mut W, recording: &Recording, path: &str) -> io::Result<()> {
+    for (index, frame) in recording.frames.iter().enumerate() {
+        match (1..frame.nodes.len()).find(|&i| frame.path(i) == path) {
+            Some(i) => writeln!(w, "{:>6}  {:>9}  {} calls", index, Nanoseconds(frame.nodes[i].total_time),
+                                frame.nodes[i].calls)?,
+            None => writeln!(w, "{:>6}  {:>9}", index, "-")?,
+        }
+    }
+    Ok(())
+}
+
+fn convert(recording: &Recording, output: &str, format: &str) -> io::Result<()> {
+    if !FORMATS.contains(&format) {
+        return Err(error(format!("unknown format {}, expected one of {}", format, FORMATS.join(", "))))
+    }
+    let mut w = BufWriter::new(File::create(output)?);
+    match format {
+        "chrome" => hprof::export::chrome::write_chrome_trace_with_metadata(&mut w, &recording.frames,
+                                                                            &recording.metadata)?,
+        "msgpack" => hprof::export::msgpack::write_recording(&mut w, recording)?,
+        "delta" => recording.write_delta_to(&mut w)?,
+        "firefox" => {
+            let thread = recording.metadata.get(hprof::THREAD_NAME).map(|t| &**t).unwrap_or("main");
+            hprof::export::firefox::write_firefox_profile(&mut w, thread, &recording.frames)?
+        }
+        "perfetto" => {
+            let thread = recording.metadata.get(hprof::THREAD_NAME).map(|t| &**t).unwrap_or("main");
+            hprof::export::perfetto::write_perfetto_trace(&mut w, thread, &recording.frames)?
+        }
+        #[cfg(feature = "parquet")]
+        "parquet" => return hprof::export::parquet::write_parquet_with_metadata(w, &recording.frames,
+                                                                                &recording.metadata),
+        _ => unreachable!("formats are checked against FORMATS"),
+    }
+    w.flush()
+}
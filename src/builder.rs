@@ -0,0 +1,262 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Construction of configured profilers.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
+
+use {Aggregation, Clock, Color, Overrun, Profiler, ScopeHook, Sink, Watchdog};
+
+/// A `Profiler` under construction, as returned by `Profiler::builder`.
+///
+/// Every option has the same default as for `Profiler::new`, and corresponds to a setter on
+/// `Profiler` that can still be used later.
+pub struct ProfilerBuilder {
+    name: &'static str,
+    aggregation: Aggregation,
+    clock: Option<Rc<dyn Clock>>,
+    budget: Option<Duration>,
+    node_budgets: Vec<(String, Duration)>,
+    colors: Vec<(String, Color)>,
+    watchdog: Option<Watchdog>,
+    capacity: usize,
+    flight_recorder: Option<usize>,
+    sinks: Vec<Box<dyn Sink>>,
+    hooks: Vec<Box<dyn ScopeHook>>,
+    slowest_calls: usize,
+    on_drop: Option<Box<dyn Write>>,
+    smoothing: Option<f64>,
+    collapsing: Option<(Duration, u32)>,
+    max_children: Option<usize>,
+    timeline: bool,
+    cpu_ids: bool,
+    callsites: bool,
+    fold_recursion: bool,
+    cpu_time: bool,
+    flat: bool,
+    metadata: BTreeMap<String, String>,
+}
+
+impl ProfilerBuilder {
+    /// Start with the default options, and the root named "root profiler".
+    pub fn new() -> ProfilerBuilder {
+        ProfilerBuilder {
+            name: "root profiler",
+            aggregation: Aggregation::Sum,
+            clock: None,
+            budget: None,
+            node_budgets: Vec::new(),
+            colors: Vec::new(),
+            watchdog: None,
+            capacity: 0,
+            flight_recorder: None,
+            sinks: Vec::new(),
+            hooks: Vec::new(),
+            slowest_calls: 0,
+            on_drop: None,
+            smoothing: None,
+            collapsing: None,
+            max_children: None,
+            timeline: false,
+            cpu_ids: false,
+            callsites: false,
+            fold_recursion: true,
+            cpu_time: false,
+            flat: false,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Name the root node.
+    pub fn name(mut self, name: &'static str) -> ProfilerBuilder {
+        self.name = name;
+        self
+    }
+
+    /// Keep statistics about individual calls. See `Profiler::with_aggregation`.
+    pub fn aggregation(mut self, aggregation: Aggregation) -> ProfilerBuilder {
+        self.aggregation = aggregation;
+        self
+    }
+
+    /// Take timestamps from `clock`. See `Profiler::set_clock`.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> ProfilerBuilder {
+        self.clock = Some(Rc::new(clock));
+        self
+    }
+
+    /// Expect frames to fit in `budget`. See `Profiler::set_budget`.
+    pub fn budget(mut self, budget: Duration) -> ProfilerBuilder {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Expect the node at `path` to fit in `budget` each frame. See `Profiler::set_node_budget`.
+    pub fn node_budget<S: Into<String>>(mut self, path: S, budget: Duration) -> ProfilerBuilder {
+        self.node_budgets.push((path.into(), budget));
+        self
+    }
+
+    /// Draw the node at `path` in `color`. See `Profiler::set_color`.
+    pub fn color<S: Into<String>>(mut self, path: S, color: Color) -> ProfilerBuilder {
+        self.colors.push((path.into(), color));
+        self
+    }
+
+    /// Call `watchdog` as soon as a frame goes over budget. See `Profiler::set_watchdog`.
+    pub fn watchdog<F: FnMut(&Overrun) + 'static>(mut self, watchdog: F) -> ProfilerBuilder {
+        self.watchdog = Some(Box::new(watchdog));
+        self
+    }
+
+    /// Allocate storage for `nodes` nodes up front. See `Profiler::reserve_nodes`.
+    pub fn capacity(mut self, nodes: usize) -> ProfilerBuilder {
+        self.capacity = nodes;
+        self
+    }
+
+    /// Keep the last `frames` frames in a flight recorder. See
+    /// `Profiler::enable_flight_recorder`.
+    pub fn flight_recorder(mut self, frames: usize) -> ProfilerBuilder {
+        self.flight_recorder = Some(frames);
+        self
+    }
+
+    /// Hand every completed frame to `sink`. May be called more than once. See
+    /// `Profiler::add_sink`.
+    pub fn sink<S: Sink + 'static>(mut self, sink: S) -> ProfilerBuilder {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Tell `hook` about every node entered and left. May be called more than once. See
+    /// `Profiler::add_hook`.
+    pub fn hook<H: ScopeHook + 'static>(mut self, hook: H) -> ProfilerBuilder {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Keep the `n` slowest individual calls of the session. See `Profiler::set_slowest_calls`.
+    pub fn slowest_calls(mut self, n: usize) -> ProfilerBuilder {
+        self.slowest_calls = n;
+        self
+    }
+
+    /// Write a session summary to `w` when the profiler is dropped. See
+    /// `Profiler::report_on_drop`.
+    pub fn report_on_drop<W: Write + 'static>(mut self, w: W) -> ProfilerBuilder {
+        self.on_drop = Some(Box::new(w));
+        self
+    }
+
+    /// Keep moving averages of the node totals. See `Profiler::set_smoothing`.
+    pub fn smoothing(mut self, alpha: f64) -> ProfilerBuilder {
+        self.smoothing = Some(alpha);
+        self
+    }
+
+    /// Collapse subtrees that stay under `threshold` for `frames` frames in a row. See
+    /// `Profiler::set_collapsing`.
+    pub fn collapsing(mut self, threshold: Duration, frames: u32) -> ProfilerBuilder {
+        self.collapsing = Some((threshold, frames));
+        self
+    }
+
+    /// Limit each node to `max` children, recording the rest under `"(other)"`. See
+    /// `Profiler::set_max_children`.
+    pub fn max_children(mut self, max: usize) -> ProfilerBuilder {
+        self.max_children = Some(max);
+        self
+    }
+
+    /// Record when each scope is entered and left. See `Profiler::set_timeline`.
+    pub fn timeline(mut self, enabled: bool) -> ProfilerBuilder {
+        self.timeline = enabled;
+        self
+    }
+
+    /// Record the CPU core of every timeline event. See `Profiler::set_cpu_ids`.
+    pub fn cpu_ids(mut self, enabled: bool) -> ProfilerBuilder {
+        self.cpu_ids = enabled;
+        self
+    }
+
+    /// Tell nodes apart by callsite. See `Profiler::set_callsites`.
+    pub fn callsites(mut self, enabled: bool) -> ProfilerBuilder {
+        self.callsites = enabled;
+        self
+    }
+
+    /// Treat scopes entered inside another of the same name as recursion. Enabled by default.
+    /// See `Profiler::set_fold_recursion`.
+    pub fn fold_recursion(mut self, enabled: bool) -> ProfilerBuilder {
+        self.fold_recursion = enabled;
+        self
+    }
+
+    /// Measure the CPU time of the thread along with wall time. See `Profiler::set_cpu_time`.
+    pub fn cpu_time(mut self, enabled: bool) -> ProfilerBuilder {
+        self.cpu_time = enabled;
+        self
+    }
+
+    /// Only sum up scopes by name, without building a tree. See `Profiler::set_flat`.
+    pub fn flat(mut self, enabled: bool) -> ProfilerBuilder {
+        self.flat = enabled;
+        self
+    }
+
+    /// Describe the session. May be called more than once. See `Profiler::set_metadata`.
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> ProfilerBuilder {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Create the profiler.
+    pub fn build(self) -> Profiler {
+        let mut profiler = Profiler::with_aggregation(self.name, self.aggregation);
+        if let Some(clock) = self.clock {
+            profiler.replace_clock(clock);
+        }
+        profiler.set_budget(self.budget);
+        for (path, budget) in self.node_budgets {
+            profiler.set_node_budget(&path, Some(budget));
+        }
+        for (path, color) in self.colors {
+            profiler.set_color(&path, Some(color));
+        }
+        *profiler.watchdog.borrow_mut() = self.watchdog;
+        profiler.reserve_nodes(self.capacity);
+        if let Some(frames) = self.flight_recorder {
+            profiler.enable_flight_recorder(frames);
+        }
+        *profiler.sinks.borrow_mut() = self.sinks;
+        *profiler.hooks.borrow_mut() = self.hooks;
+        profiler.set_slowest_calls(self.slowest_calls);
+        *profiler.on_drop.borrow_mut() = self.on_drop;
+        profiler.set_smoothing(self.smoothing);
+        if let Some((threshold, frames)) = self.collapsing {
+            profiler.set_collapsing(Some(threshold), frames);
+        }
+        profiler.set_max_children(self.max_children);
+        profiler.set_timeline(self.timeline);
+        profiler.set_cpu_ids(self.cpu_ids);
+        profiler.set_callsites(self.callsites);
+        profiler.set_fold_recursion(self.fold_recursion);
+        profiler.set_cpu_time(self.cpu_time);
+        profiler.set_flat(self.flat);
+        *profiler.metadata.borrow_mut() = self.metadata;
+        profiler
+    }
+}
+
+impl Default for ProfilerBuilder {
+    fn default() -> ProfilerBuilder {
+        ProfilerBuilder::new()
+    }
+}
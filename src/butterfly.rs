@@ -0,0 +1,123 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Caller/callee breakdowns of a single name, in the style of gprof's call graph.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use {Nanoseconds, ProfileNode};
+
+/// The time spent in every node with one name, split by what called it and what it called, as
+/// returned by `Profiler::butterfly`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Butterfly {
+    pub name: String,
+    /// Total calls made to every node with this name.
+    pub calls: u64,
+    /// Time spent in them including their children, counting nested calls once.
+    pub total: Duration,
+    /// Time spent in them and not in their children.
+    pub self_time: Duration,
+    /// The names of the nodes they were called from, with the calls and time spent in `name`
+    /// from each. Longest first.
+    pub callers: Vec<Edge>,
+    /// The names of their children, with the calls and time spent in each. Longest first.
+    pub callees: Vec<Edge>,
+}
+
+/// Calls between the name of a `Butterfly` and one of its callers or callees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    /// Name of the caller or callee.
+    pub name: &'static str,
+    pub calls: u64,
+    /// Time spent in the callee, including its children.
+    pub total: Duration,
+}
+
+/// Sum the nodes named `name` below `root` by caller and by callee, to see where a helper used
+/// all over the tree is called from and where its time goes.
+///
+/// Returns `None` if there is no such node. Includes the children of mounted profilers.
+pub fn butterfly(root: &ProfileNode, name: &str) -> Option<Butterfly> {
+    let mut butterfly = Butterfly {
+        name: name.to_string(),
+        calls: 0,
+        total: Duration::from_nanos(0),
+        self_time: Duration::from_nanos(0),
+        callers: Vec::new(),
+        callees: Vec::new(),
+    };
+    let mut callers = HashMap::new();
+    let mut callees = HashMap::new();
+    let mut found = false;
+    visit(root, name, false, &mut butterfly, &mut callers, &mut callees, &mut found);
+    if !found {
+        return None
+    }
+    butterfly.callers = sorted(callers);
+    butterfly.callees = sorted(callees);
+    Some(butterfly)
+}
+
+// `nested` is whether `node` is inside a node named `name`, so its total isn't added twice.
+fn visit(node: &ProfileNode, name: &str, nested: bool, butterfly: &mut Butterfly,
+         callers: &mut HashMap<&'static str, Edge>, callees: &mut HashMap<&'static str, Edge>, found: &mut bool) {
+    for child in &node.all_children() {
+        let matched = child.name == name;
+        if matched {
+            *found = true;
+            butterfly.calls += child.calls.get() as u64;
+            butterfly.self_time += child.self_time();
+            if !nested {
+                butterfly.total += child.total();
+            }
+            add(callers, node.name, child);
+            for grandchild in &child.all_children() {
+                add(callees, grandchild.name, grandchild);
+            }
+        }
+        visit(child, name, nested || matched, butterfly, callers, callees, found);
+    }
+}
+
+fn add(edges: &mut HashMap<&'static str, Edge>, name: &'static str, node: &ProfileNode) {
+    let edge = edges.entry(name).or_insert_with(|| Edge { name, calls: 0, total: Duration::from_nanos(0) });
+    edge.calls += node.calls.get() as u64;
+    edge.total += node.total();
+}
+
+fn sorted(edges: HashMap<&'static str, Edge>) -> Vec<Edge> {
+    let mut edges: Vec<Edge> = edges.into_values().collect();
+    edges.sort_by(|a, b| b.total.cmp(&a.total).then(a.name.cmp(b.name)));
+    edges
+}
+
+/// Write `butterfly` at indentation `indent`: its callers, then the name itself marked with `>`,
+/// then its callees, each with its calls, total, and share of the name's total.
+pub fn write_butterfly<W: Write>(w: &mut W, butterfly: &Butterfly, indent: usize) -> io::Result<()> {
+    let width = butterfly.callers.iter().chain(&butterfly.callees).map(|e| e.name.len())
+        .chain(Some(butterfly.name.len())).max().unwrap_or(0);
+    let total = butterfly.total.as_nanos() as u64;
+    for edge in &butterfly.callers {
+        write_edge(w, edge, total, indent, width)?;
+    }
+    writeln!(w, "{:indent$}> {:width$}  {:>7}  {:>8}  self {}", "", butterfly.name, butterfly.calls,
+             Nanoseconds(total).to_string(), Nanoseconds(butterfly.self_time.as_nanos() as u64), indent = indent,
+             width = width)?;
+    for edge in &butterfly.callees {
+        write_edge(w, edge, total, indent, width)?;
+    }
+    Ok(())
+}
+
+fn write_edge<W: Write>(w: &mut W, edge: &Edge, total: u64, indent: usize, width: usize) -> io::Result<()> {
+    let edge_total = edge.total.as_nanos() as u64;
+    writeln!(w, "{:indent$}  {:width$}  {:>7}  {:>8}  {:.1}%", "", edge.name, edge.calls,
+             Nanoseconds(edge_total).to_string(), 100.0 * edge_total as f64 / total as f64, indent = indent,
+             width = width)
+}
@@ -0,0 +1,47 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scope categories, for visually grouping large profiles.
+
+use std::fmt;
+
+/// An RGB display color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    /// Create a color from its components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
+}
+
+/// Formats as `#rrggbb`.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// A named group of scopes, such as "physics", "io" or "gpu".
+///
+/// Categories are usually declared once as constants and passed to `Profiler::enter_in`. A node
+/// without a category of its own belongs to its nearest categorized ancestor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Category {
+    pub name: &'static str,
+    pub color: Color,
+}
+
+impl Category {
+    /// Create a new category.
+    pub const fn new(name: &'static str, color: Color) -> Category {
+        Category { name, color }
+    }
+}
@@ -0,0 +1,56 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Instrumented channel receives.
+//!
+//! Waiting on a job or message queue is frequently where a frame actually went. The
+//! `ProfiledRecv` extension trait adds receive methods that record the time spent blocked as a
+//! child of the current node in the thread-local profiler, named `"recv wait"` by default, and
+//! entered with `hprof::enter_blocking` so it counts as blocked time.
+//!
+//! It is implemented for `std::sync::mpsc::Receiver`, and for `crossbeam_channel::Receiver`
+//! when the `crossbeam-channel` feature is enabled.
+
+use std::sync::mpsc;
+
+/// Default name of the node that blocking receive time is recorded under.
+pub const RECV_WAIT: &str = "recv wait";
+
+/// Receive methods that record their blocking time.
+pub trait ProfiledRecv {
+    /// The type of message received.
+    type Item;
+    /// The error returned when the channel is disconnected.
+    type Error;
+
+    /// Block until a message arrives, recording the wait under `name`.
+    fn recv_named(&self, name: &'static str) -> Result<Self::Item, Self::Error>;
+
+    /// Block until a message arrives, recording the wait under `"recv wait"`.
+    fn recv_profiled(&self) -> Result<Self::Item, Self::Error> {
+        self.recv_named(RECV_WAIT)
+    }
+}
+
+impl<T> ProfiledRecv for mpsc::Receiver<T> {
+    type Item = T;
+    type Error = mpsc::RecvError;
+
+    fn recv_named(&self, name: &'static str) -> Result<T, mpsc::RecvError> {
+        let _g = ::enter_blocking(name);
+        self.recv()
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl<T> ProfiledRecv for ::crossbeam_channel::Receiver<T> {
+    type Item = T;
+    type Error = ::crossbeam_channel::RecvError;
+
+    fn recv_named(&self, name: &'static str) -> Result<T, ::crossbeam_channel::RecvError> {
+        let _g = ::enter_blocking(name);
+        self.recv()
+    }
+}
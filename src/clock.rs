@@ -0,0 +1,216 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Sources of timestamps.
+
+#[cfg(feature = "quanta")]
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use clock_ticks;
+
+use ProfileNode;
+
+/// A monotonic source of timestamps, used by a `Profiler` for every `enter` and `leave`.
+///
+/// Only differences between timestamps are meaningful, so a clock may count from any origin.
+pub trait Clock {
+    /// The current time in ns.
+    fn now(&self) -> u64;
+}
+
+/// The clock profilers use unless given another with `Profiler::set_clock`: `QuantaClock` with
+/// the `quanta` feature, which makes it the clock of the thread-local profiler too, and
+/// `PreciseTime` otherwise.
+#[cfg(feature = "quanta")]
+pub type DefaultClock = QuantaClock;
+
+/// The clock profilers use unless given another with `Profiler::set_clock`: `QuantaClock` with
+/// the `quanta` feature, which makes it the clock of the thread-local profiler too, and
+/// `PreciseTime` otherwise.
+#[cfg(not(feature = "quanta"))]
+pub type DefaultClock = PreciseTime;
+
+/// The current time in ns by the default clock, the same as `DefaultClock::default().now()` but
+/// without creating a clock.
+#[cfg(feature = "quanta")]
+pub fn now() -> u64 {
+    let (clock, origin) = shared_quanta();
+    clock.delta_as_nanos(*origin, clock.raw())
+}
+
+/// The current time in ns by the default clock, the same as `DefaultClock::default().now()` but
+/// without creating a clock.
+#[cfg(not(feature = "quanta"))]
+pub fn now() -> u64 {
+    clock_ticks::precise_time_ns()
+}
+
+/// A clock using `clock_ticks::precise_time_ns`, the default without the `quanta` feature.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PreciseTime;
+
+impl Clock for PreciseTime {
+    fn now(&self) -> u64 {
+        clock_ticks::precise_time_ns()
+    }
+}
+
+/// A clock reading the CPU's timestamp counter through the `quanta` crate, where available.
+///
+/// Much cheaper to read than the OS clock, which adds up with thousands of scopes per frame.
+/// Timestamps count from when the first `QuantaClock` of the process was created, so the
+/// profilers of different threads agree on them. Requires the `quanta` feature, which also makes
+/// it the default clock.
+#[cfg(feature = "quanta")]
+#[derive(Clone, Debug)]
+pub struct QuantaClock {
+    clock: ::quanta::Clock,
+    origin: u64,
+}
+
+#[cfg(feature = "quanta")]
+impl QuantaClock {
+    /// Create a clock, calibrating the timestamp counter if no clock was created before.
+    pub fn new() -> QuantaClock {
+        let (clock, origin) = shared_quanta();
+        QuantaClock { clock: clock.clone(), origin: *origin }
+    }
+}
+
+// The calibrated clock every `QuantaClock` reads, and the reading they all count from.
+#[cfg(feature = "quanta")]
+fn shared_quanta() -> &'static (::quanta::Clock, u64) {
+    static SHARED: OnceLock<(::quanta::Clock, u64)> = OnceLock::new();
+    SHARED.get_or_init(|| {
+        let clock = ::quanta::Clock::new();
+        let origin = clock.raw();
+        (clock, origin)
+    })
+}
+
+#[cfg(feature = "quanta")]
+impl Default for QuantaClock {
+    fn default() -> QuantaClock {
+        QuantaClock::new()
+    }
+}
+
+#[cfg(feature = "quanta")]
+impl Clock for QuantaClock {
+    fn now(&self) -> u64 {
+        self.clock.delta_as_nanos(self.origin, self.clock.raw())
+    }
+}
+
+/// CPU time used so far by the calling thread in ns, or `None` where the OS doesn't tell.
+///
+/// Unlike the wall time measured by a `Clock`, this doesn't advance while the thread is blocked
+/// or descheduled. Read with `clock_gettime(CLOCK_THREAD_CPUTIME_ID)` on Unix and
+/// `GetThreadTimes` on Windows.
+#[cfg(unix)]
+pub fn thread_cpu_time() -> Option<u64> {
+    let mut ts = ::libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    if unsafe { ::libc::clock_gettime(::libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } != 0 {
+        return None
+    }
+    Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+/// CPU time used so far by the calling thread in ns, or `None` where the OS doesn't tell.
+///
+/// Unlike the wall time measured by a `Clock`, this doesn't advance while the thread is blocked
+/// or descheduled. Read with `clock_gettime(CLOCK_THREAD_CPUTIME_ID)` on Unix and
+/// `GetThreadTimes` on Windows.
+#[cfg(windows)]
+pub fn thread_cpu_time() -> Option<u64> {
+    use std::os::raw::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn GetThreadTimes(thread: *mut c_void, creation: *mut FileTime, exit: *mut FileTime, kernel: *mut FileTime,
+                          user: *mut FileTime) -> i32;
+    }
+
+    let (mut creation, mut exit, mut kernel, mut user) =
+        (FileTime::default(), FileTime::default(), FileTime::default(), FileTime::default());
+    if unsafe { GetThreadTimes(GetCurrentThread(), &mut creation, &mut exit, &mut kernel, &mut user) } == 0 {
+        return None
+    }
+    // in units of 100ns
+    let ticks = |t: &FileTime| (t.high as u64) << 32 | t.low as u64;
+    Some((ticks(&kernel) + ticks(&user)) * 100)
+}
+
+/// CPU time used so far by the calling thread in ns, or `None` where the OS doesn't tell.
+#[cfg(not(any(unix, windows)))]
+pub fn thread_cpu_time() -> Option<u64> {
+    None
+}
+
+/// The CPU core the calling thread is running on, or `None` where the OS doesn't tell.
+///
+/// The thread may be moved to another core at any time, even before this returns. Read with
+/// `sched_getcpu` on Linux and Android and `GetCurrentProcessorNumber` on Windows.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn current_cpu() -> Option<u32> {
+    match unsafe { ::libc::sched_getcpu() } {
+        -1 => None,
+        cpu => Some(cpu as u32),
+    }
+}
+
+/// The CPU core the calling thread is running on, or `None` where the OS doesn't tell.
+///
+/// The thread may be moved to another core at any time, even before this returns. Read with
+/// `sched_getcpu` on Linux and Android and `GetCurrentProcessorNumber` on Windows.
+#[cfg(windows)]
+pub fn current_cpu() -> Option<u32> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcessorNumber() -> u32;
+    }
+
+    Some(unsafe { GetCurrentProcessorNumber() })
+}
+
+/// The CPU core the calling thread is running on, or `None` where the OS doesn't tell.
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+pub fn current_cpu() -> Option<u32> {
+    None
+}
+
+/// Estimated cost of profiling a single call, as measured by `Profiler::calibrate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overhead {
+    /// Time an empty scope reports for itself.
+    pub inner: Duration,
+    /// Time an empty scope adds to the total of its parent.
+    pub outer: Duration,
+}
+
+impl Overhead {
+    /// Subtract the estimated overhead from the totals of the tree rooted at `node`.
+    ///
+    /// Returns the number of calls made to `node` and all of its descendants.
+    pub fn compensate(&self, node: &ProfileNode) -> u64 {
+        let mut descendant_calls = 0;
+        for child in &*node.children.borrow() {
+            descendant_calls += self.compensate(child);
+        }
+        let calls = node.calls.get() as u64;
+        let cost = self.inner.as_nanos() as u64 * calls + self.outer.as_nanos() as u64 * descendant_calls;
+        node.total_time.set(node.total_time.get().saturating_sub(cost));
+        calls + descendant_calls
+    }
+}
@@ -0,0 +1,169 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Comparisons of two recordings, such as before and after an optimization.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use recording::Recording;
+use Nanoseconds;
+
+/// Thresholds for `Recording::compare_with`. A path only counts as changed if its time changed
+/// by more than both.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompareOptions {
+    /// Smallest change counted, as a fraction of the time before: 0.05 is 5%.
+    pub min_ratio: f64,
+    /// Smallest change counted, per frame.
+    pub min_change: Duration,
+}
+
+impl Default for CompareOptions {
+    /// 5% and 1us.
+    fn default() -> CompareOptions {
+        CompareOptions { min_ratio: 0.05, min_change: Duration::from_micros(1) }
+    }
+}
+
+/// The time of one path in both recordings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathChange {
+    /// The `/`-separated path, not including the root.
+    pub path: String,
+    /// Mean time per frame before, counting frames where the path doesn't appear as 0.
+    pub before: Duration,
+    /// Mean time per frame after, likewise.
+    pub after: Duration,
+    /// Mean calls per frame before.
+    pub calls_before: f64,
+    /// Mean calls per frame after.
+    pub calls_after: f64,
+}
+
+impl PathChange {
+    /// Change in ns per frame, positive if the path got slower.
+    pub fn change(&self) -> i64 {
+        self.after.as_nanos() as i64 - self.before.as_nanos() as i64
+    }
+
+    /// Change as a fraction of the time before. Infinite for paths that are new.
+    pub fn ratio(&self) -> f64 {
+        self.change() as f64 / self.before.as_nanos() as f64
+    }
+}
+
+/// How the paths of one recording changed in another, as returned by `Recording::compare`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// Mean frame time before.
+    pub frame_before: Duration,
+    /// Mean frame time after.
+    pub frame_after: Duration,
+    /// Paths that got slower, or are new, by more than the thresholds. Largest change first.
+    pub regressions: Vec<PathChange>,
+    /// Paths that got faster, or are gone, by more than the thresholds. Largest change first.
+    pub improvements: Vec<PathChange>,
+    /// Number of paths whose change was within the thresholds.
+    pub unchanged: usize,
+}
+
+/// Compare the mean time per frame of every path in `before` with `after`.
+pub fn compare(before: &Recording, after: &Recording, opts: &CompareOptions) -> Comparison {
+    let mut comparison = Comparison {
+        frame_before: mean_frame(before),
+        frame_after: mean_frame(after),
+        regressions: Vec::new(),
+        improvements: Vec::new(),
+        unchanged: 0,
+    };
+    let mut changes: Vec<PathChange> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for &(recording, is_before) in &[(before, true), (after, false)] {
+        let frames = recording.frames.len().max(1) as u128;
+        for stats in recording.path_stats() {
+            let i = *index.entry(stats.path.clone()).or_insert_with(|| {
+                changes.push(PathChange {
+                    path: stats.path.clone(),
+                    before: Duration::from_nanos(0),
+                    after: Duration::from_nanos(0),
+                    calls_before: 0.0,
+                    calls_after: 0.0,
+                });
+                changes.len() - 1
+            });
+            let mean = Duration::from_nanos((stats.total.as_nanos() / frames) as u64);
+            let calls = stats.calls as f64 / frames as f64;
+            if is_before {
+                changes[i].before = mean;
+                changes[i].calls_before = calls;
+            } else {
+                changes[i].after = mean;
+                changes[i].calls_after = calls;
+            }
+        }
+    }
+    for change in changes {
+        let delta = change.change();
+        if delta.unsigned_abs() as u128 <= opts.min_change.as_nanos() || change.ratio().abs() <= opts.min_ratio {
+            comparison.unchanged += 1;
+        } else if delta > 0 {
+            comparison.regressions.push(change);
+        } else {
+            comparison.improvements.push(change);
+        }
+    }
+    comparison.regressions.sort_by_key(|c| Reverse(c.change()));
+    comparison.improvements.sort_by_key(|c| c.change());
+    comparison
+}
+
+fn mean_frame(recording: &Recording) -> Duration {
+    let total: u128 = recording.frames.iter().map(|f| f.total_time() as u128).sum();
+    Duration::from_nanos((total / recording.frames.len().max(1) as u128) as u64)
+}
+
+/// Write `comparison` as text: the mean frame times, then the regressions and improvements with
+/// their times before and after and the change.
+pub fn write_comparison<W: Write>(w: &mut W, comparison: &Comparison) -> io::Result<()> {
+    let frame = PathChange {
+        path: "frame".to_string(),
+        before: comparison.frame_before,
+        after: comparison.frame_after,
+        calls_before: 1.0,
+        calls_after: 1.0,
+    };
+    let width = comparison.regressions.iter().chain(&comparison.improvements).map(|c| c.path.len())
+        .max().unwrap_or(0).max(frame.path.len());
+    write_change(w, &frame, width)?;
+    let sections = [("Regressions", &comparison.regressions), ("Improvements", &comparison.improvements)];
+    for &(title, changes) in &sections {
+        if !changes.is_empty() {
+            writeln!(w, "{}:", title)?;
+            for change in changes.iter() {
+                write_change(w, change, width)?;
+            }
+        }
+    }
+    writeln!(w, "Unchanged paths: {}", comparison.unchanged)
+}
+
+fn write_change<W: Write>(w: &mut W, change: &PathChange, width: usize) -> io::Result<()> {
+    let delta = change.change();
+    let sign = if delta < 0 { "-" } else { "+" };
+    let before = Nanoseconds(change.before.as_nanos() as u64).to_string();
+    let after = Nanoseconds(change.after.as_nanos() as u64).to_string();
+    write!(w, "  {:width$}  {:>8} -> {:>8}  {:>9}", change.path, before, after,
+           format!("{}{}", sign, Nanoseconds(delta.unsigned_abs())), width = width)?;
+    if change.before.as_nanos() == 0 {
+        writeln!(w, "  new")
+    } else if change.after.as_nanos() == 0 {
+        writeln!(w, "  gone")
+    } else {
+        writeln!(w, "  {}{:.1}%", sign, 100.0 * change.ratio().abs())
+    }
+}
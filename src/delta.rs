@@ -0,0 +1,527 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Delta-encoded recordings, for long captures.
+//!
+//! The nodes of a frame rarely change from one frame to the next, so rather than repeating the
+//! whole tree every frame as the text format does, this format writes the tree once in a
+//! keyframe and then only the change in each node's calls and total time. A new keyframe is
+//! written whenever the set of nodes changes, and every few hundred frames so a damaged file
+//! loses little. Long recordings typically come out an order of magnitude smaller.
+//!
+//! # File format
+//!
+//! The file starts with the line `hprof-delta 1`, followed by the number of metadata entries
+//! and each key and value. Integers are LEB128 varints, signed ones zigzag-encoded first, and
+//! strings are their length in bytes followed by their UTF-8. Optional values are preceded by
+//! a 0 byte when absent and a 1 byte when present. Each frame is then
+//!
+//! ```text
+//! kind: 0 for a keyframe, 1 for a delta frame
+//! start time, index: absolute in keyframes, signed differences from the previous frame otherwise
+//! wall-clock ns since the Unix epoch: optional, a signed difference if the previous frame had one
+//! end wall-clock ns: optional, a signed difference from the start if the frame has one
+//! tags: in delta frames, a 0 byte if they're the same as the previous frame's, or 1 followed by
+//!       the count and each key and value
+//! nodes: in keyframes, the count and each node's depth, id, calls, total ns, optional CPU ns,
+//!        optional blocked ns, optional category, optional color as three bytes, name, and
+//!        annotations; in delta frames, each node's signed difference in calls and total ns
+//!        from the previous frame, optional CPU ns and blocked ns, each a signed difference if
+//!        the node had one in the previous frame, and annotations
+//! events: the count and each event's kind (0 for enter, 1 for leave), the index of its node
+//!         plus one, or 0 followed by its id if the node isn't in the frame, signed difference
+//!         in time from the previous event or the start of the frame, optional payload, and
+//!         optional CPU core
+//! ```
+//!
+//! where annotations are a count followed by each note.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use recording::Recording;
+use sink::{FrameInfo, Sink};
+use snapshot::{ProfileSnapshot, SnapshotNode};
+use timeline::{EventKind, TimelineEvent};
+use {Color, ProfileNode};
+
+/// The first line of every delta-encoded recording.
+pub const MAGIC: &[u8] = b"hprof-delta 1\n";
+
+const KEYFRAME: u8 = 0;
+const DELTA: u8 = 1;
+
+/// Writes frames in the delta-encoded format as they come, to a file or as a `Sink`.
+///
+/// Used as a sink, frames don't have their timeline events, since sinks only see the tree.
+pub struct DeltaWriter<W: Write> {
+    w: W,
+    // frames written at most between keyframes
+    keyframe_interval: usize,
+    // delta frames written since the last keyframe
+    since_keyframe: usize,
+    // the last frame written, if any, which the next one is encoded against
+    previous: Option<ProfileSnapshot>,
+    // reused by `Sink::frame`
+    scratch: ProfileSnapshot,
+}
+
+impl DeltaWriter<BufWriter<File>> {
+    /// Write frames to the file at `path`, replacing it if it exists, with the session's
+    /// `metadata`.
+    pub fn create<P: AsRef<Path>>(path: P, metadata: &BTreeMap<String, String>)
+        -> io::Result<DeltaWriter<BufWriter<File>>>
+    {
+        DeltaWriter::new(BufWriter::new(File::create(path)?), metadata)
+    }
+}
+
+impl<W: Write> DeltaWriter<W> {
+    /// Write the header and the session's `metadata` to `w`, ready for frames.
+    pub fn new(mut w: W, metadata: &BTreeMap<String, String>) -> io::Result<DeltaWriter<W>> {
+        w.write_all(MAGIC)?;
+        write_uint(&mut w, metadata.len() as u64)?;
+        for (key, value) in metadata {
+            write_str(&mut w, key)?;
+            write_str(&mut w, value)?;
+        }
+        Ok(DeltaWriter {
+            w,
+            keyframe_interval: 256,
+            since_keyframe: 0,
+            previous: None,
+            scratch: ProfileSnapshot::default(),
+        })
+    }
+
+    /// Write a keyframe at least every `frames` frames, even if the nodes don't change. 256 by
+    /// default.
+    pub fn set_keyframe_interval(&mut self, frames: usize) {
+        self.keyframe_interval = frames.max(1);
+    }
+
+    /// Append `frame`.
+    pub fn write_frame(&mut self, frame: &ProfileSnapshot) -> io::Result<()> {
+        let delta = match self.previous {
+            Some(ref previous) => self.since_keyframe + 1 < self.keyframe_interval && same_nodes(previous, frame),
+            None => false,
+        };
+        if delta {
+            self.since_keyframe += 1;
+            write_delta_frame(&mut self.w, self.previous.as_ref().unwrap(), frame)?;
+        } else {
+            self.since_keyframe = 0;
+            write_keyframe(&mut self.w, frame)?;
+        }
+        match self.previous {
+            Some(ref mut previous) => previous.clone_from(frame),
+            None => self.previous = Some(frame.clone()),
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+    /// Stop writing, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+impl<W: Write> Sink for DeltaWriter<W> {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let mut frame = mem::take(&mut self.scratch);
+        frame.fill(root);
+        frame.index = info.index;
+        frame.wall_time = Some(info.start);
+        frame.end_wall_time = Some(info.timestamp);
+        frame.tags.extend_from_slice(info.tags);
+        let result = self.write_frame(&frame);
+        self.scratch = frame;
+        result?;
+        self.w.flush()
+    }
+}
+
+/// Write `recording` in the delta-encoded format.
+pub fn write_delta<W: Write>(w: &mut W, recording: &Recording) -> io::Result<()> {
+    let mut writer = DeltaWriter::new(w, &recording.metadata)?;
+    for frame in &recording.frames {
+        writer.write_frame(frame)?;
+    }
+    Ok(())
+}
+
+/// Read a delta-encoded recording from `r`.
+pub fn read_delta<R: BufRead>(mut r: R) -> io::Result<Recording> {
+    let mut magic = vec![0; MAGIC.len()];
+    r.read_exact(&mut magic).map_err(|_| invalid("not a delta-encoded hprof recording"))?;
+    if magic != MAGIC {
+        return Err(invalid("not a delta-encoded hprof recording"))
+    }
+    let mut recording = Recording::new();
+    for _ in 0..read_uint(&mut r)? {
+        let key = read_str(&mut r)?;
+        let value = read_str(&mut r)?;
+        recording.metadata.insert(key, value);
+    }
+    while let Some(&kind) = r.fill_buf()?.first() {
+        r.consume(1);
+        let frame = match (kind, recording.frames.last()) {
+            (KEYFRAME, _) => read_keyframe(&mut r)?,
+            (DELTA, Some(previous)) => read_delta_frame(&mut r, previous)?,
+            (DELTA, None) => return Err(invalid("delta frame without a keyframe")),
+            _ => return Err(invalid("unknown frame kind")),
+        };
+        recording.frames.push(frame);
+    }
+    Ok(recording)
+}
+
+// Whether `a` and `b` have the same nodes in the same order, so one can be encoded against the
+// other.
+fn same_nodes(a: &ProfileSnapshot, b: &ProfileSnapshot) -> bool {
+    a.nodes.len() == b.nodes.len() && a.nodes.iter().zip(&b.nodes).all(|(a, b)| {
+        a.id == b.id && a.depth == b.depth && a.name == b.name && a.category == b.category && a.color == b.color
+    })
+}
+
+fn write_keyframe<W: Write>(w: &mut W, frame: &ProfileSnapshot) -> io::Result<()> {
+    w.write_all(&[KEYFRAME])?;
+    write_uint(w, frame.start_time)?;
+    write_uint(w, frame.index)?;
+    write_option(w, frame.unix_time(), write_uint)?;
+    write_end_time(w, frame)?;
+    write_tags(w, &frame.tags)?;
+    write_uint(w, frame.nodes.len() as u64)?;
+    for node in &frame.nodes {
+        write_uint(w, node.depth as u64)?;
+        write_uint(w, node.id)?;
+        write_uint(w, node.calls as u64)?;
+        write_uint(w, node.total_time)?;
+        write_option(w, node.cpu_time, write_uint)?;
+        write_option(w, node.blocked_time, write_uint)?;
+        write_option(w, node.category.as_ref(), |w, c| write_str(w, c))?;
+        write_option(w, node.color, |w, c| w.write_all(&[c.r, c.g, c.b]))?;
+        write_str(w, &node.name)?;
+        write_annotations(w, &node.annotations)?;
+    }
+    write_events(w, frame)
+}
+
+fn write_end_time<W: Write>(w: &mut W, frame: &ProfileSnapshot) -> io::Result<()> {
+    match (frame.unix_end_time(), frame.unix_time()) {
+        (Some(end), Some(start)) => write_option(w, Some(diff(end, start)), write_int),
+        (end, _) => write_option(w, end, write_uint),
+    }
+}
+
+fn write_delta_frame<W: Write>(w: &mut W, previous: &ProfileSnapshot, frame: &ProfileSnapshot) -> io::Result<()> {
+    w.write_all(&[DELTA])?;
+    write_int(w, diff(frame.start_time, previous.start_time))?;
+    write_int(w, diff(frame.index, previous.index))?;
+    match (frame.unix_time(), previous.unix_time()) {
+        (Some(time), Some(previous)) => write_option(w, Some(diff(time, previous)), write_int)?,
+        (time, _) => write_option(w, time, write_uint)?,
+    }
+    write_end_time(w, frame)?;
+    if frame.tags == previous.tags {
+        w.write_all(&[0])?;
+    } else {
+        w.write_all(&[1])?;
+        write_tags(w, &frame.tags)?;
+    }
+    for (node, previous) in frame.nodes.iter().zip(&previous.nodes) {
+        write_int(w, diff(node.calls as u64, previous.calls as u64))?;
+        write_int(w, diff(node.total_time, previous.total_time))?;
+        write_change(w, node.cpu_time, previous.cpu_time)?;
+        write_change(w, node.blocked_time, previous.blocked_time)?;
+        write_annotations(w, &node.annotations)?;
+    }
+    write_events(w, frame)
+}
+
+// Write the optional `value`, as a signed difference from `previous` if there is one.
+fn write_change<W: Write>(w: &mut W, value: Option<u64>, previous: Option<u64>) -> io::Result<()> {
+    match (value, previous) {
+        (Some(value), Some(previous)) => write_option(w, Some(diff(value, previous)), write_int),
+        (value, _) => write_option(w, value, write_uint),
+    }
+}
+
+fn write_tags<W: Write>(w: &mut W, tags: &[(String, String)]) -> io::Result<()> {
+    write_uint(w, tags.len() as u64)?;
+    for (key, value) in tags {
+        write_str(w, key)?;
+        write_str(w, value)?;
+    }
+    Ok(())
+}
+
+fn write_annotations<W: Write>(w: &mut W, annotations: &[String]) -> io::Result<()> {
+    write_uint(w, annotations.len() as u64)?;
+    for note in annotations {
+        write_str(w, note)?;
+    }
+    Ok(())
+}
+
+fn write_events<W: Write>(w: &mut W, frame: &ProfileSnapshot) -> io::Result<()> {
+    write_uint(w, frame.events.len() as u64)?;
+    if frame.events.is_empty() {
+        return Ok(())
+    }
+    let index: HashMap<u64, usize> = frame.nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+    let mut time = frame.start_time;
+    for event in &frame.events {
+        w.write_all(&[match event.kind {
+            EventKind::Enter => 0,
+            EventKind::Leave => 1,
+        }])?;
+        match index.get(&event.id) {
+            Some(&idx) => write_uint(w, idx as u64 + 1)?,
+            None => {
+                write_uint(w, 0)?;
+                write_uint(w, event.id)?;
+            }
+        }
+        write_int(w, diff(event.time, time))?;
+        time = event.time;
+        write_option(w, event.payload.as_ref(), |w, p| write_str(w, p))?;
+        write_option(w, event.cpu, |w, cpu| write_uint(w, cpu as u64))?;
+    }
+    Ok(())
+}
+
+fn read_keyframe<R: BufRead>(r: &mut R) -> io::Result<ProfileSnapshot> {
+    let start_time = read_uint(r)?;
+    let index = read_uint(r)?;
+    let wall_time = read_option(r, read_uint)?.map(|ns| UNIX_EPOCH + Duration::from_nanos(ns));
+    let mut frame = ProfileSnapshot { start_time, index, wall_time, ..ProfileSnapshot::default() };
+    read_end_time(r, &mut frame)?;
+    frame.tags = read_tags(r)?;
+    // stack of (depth, index) for the nodes on the path to the last node read
+    let mut stack: Vec<(u32, usize)> = Vec::new();
+    for _ in 0..read_uint(r)? {
+        let depth = read_uint(r)? as u32;
+        while stack.last().map(|&(d, _)| d >= depth).unwrap_or(false) {
+            stack.pop();
+        }
+        let parent = stack.last().map(|&(_, i)| i);
+        if parent.is_none() && depth != 0 {
+            return Err(invalid("node has no parent"))
+        }
+        let node = SnapshotNode {
+            depth,
+            parent,
+            id: read_uint(r)?,
+            calls: read_uint(r)? as u32,
+            total_time: read_uint(r)?,
+            cpu_time: read_option(r, read_uint)?,
+            blocked_time: read_option(r, read_uint)?,
+            category: read_option(r, read_str)?.map(Cow::Owned),
+            color: read_option(r, |r| {
+                let mut rgb = [0; 3];
+                r.read_exact(&mut rgb)?;
+                Ok(Color::rgb(rgb[0], rgb[1], rgb[2]))
+            })?,
+            name: Cow::Owned(read_str(r)?),
+            annotations: read_annotations(r)?,
+            location: None,
+            bytes: 0,
+        };
+        stack.push((depth, frame.nodes.len()));
+        frame.nodes.push(node);
+    }
+    read_events(r, &mut frame)?;
+    Ok(frame)
+}
+
+fn read_end_time<R: BufRead>(r: &mut R, frame: &mut ProfileSnapshot) -> io::Result<()> {
+    frame.end_wall_time = match frame.unix_time() {
+        Some(start) => read_option(r, read_int)?.map(|d| apply(start, d)),
+        None => read_option(r, read_uint)?,
+    }.map(|ns| UNIX_EPOCH + Duration::from_nanos(ns));
+    Ok(())
+}
+
+fn read_delta_frame<R: BufRead>(r: &mut R, previous: &ProfileSnapshot) -> io::Result<ProfileSnapshot> {
+    let start_time = apply(previous.start_time, read_int(r)?);
+    let index = apply(previous.index, read_int(r)?);
+    let wall_time = match previous.unix_time() {
+        Some(time) => read_option(r, read_int)?.map(|d| apply(time, d)),
+        None => read_option(r, read_uint)?,
+    }.map(|ns| UNIX_EPOCH + Duration::from_nanos(ns));
+    let mut frame = ProfileSnapshot { start_time, index, wall_time, ..ProfileSnapshot::default() };
+    read_end_time(r, &mut frame)?;
+    frame.tags = match read_byte(r)? {
+        0 => previous.tags.clone(),
+        _ => read_tags(r)?,
+    };
+    frame.nodes.reserve(previous.nodes.len());
+    for node in &previous.nodes {
+        let calls = apply(node.calls as u64, read_int(r)?) as u32;
+        let total_time = apply(node.total_time, read_int(r)?);
+        let cpu_time = read_change(r, node.cpu_time)?;
+        let blocked_time = read_change(r, node.blocked_time)?;
+        frame.nodes.push(SnapshotNode {
+            calls,
+            total_time,
+            cpu_time,
+            blocked_time,
+            annotations: read_annotations(r)?,
+            ..node.clone()
+        });
+    }
+    read_events(r, &mut frame)?;
+    Ok(frame)
+}
+
+// Read an optional value written by `write_change` against `previous`.
+fn read_change<R: BufRead>(r: &mut R, previous: Option<u64>) -> io::Result<Option<u64>> {
+    match previous {
+        Some(previous) => Ok(read_option(r, read_int)?.map(|d| apply(previous, d))),
+        None => read_option(r, read_uint),
+    }
+}
+
+fn read_tags<R: BufRead>(r: &mut R) -> io::Result<Vec<(String, String)>> {
+    let mut tags = Vec::new();
+    for _ in 0..read_uint(r)? {
+        let key = read_str(r)?;
+        tags.push((key, read_str(r)?));
+    }
+    Ok(tags)
+}
+
+fn read_annotations<R: BufRead>(r: &mut R) -> io::Result<Vec<String>> {
+    let mut annotations = Vec::new();
+    for _ in 0..read_uint(r)? {
+        annotations.push(read_str(r)?);
+    }
+    Ok(annotations)
+}
+
+fn read_events<R: BufRead>(r: &mut R, frame: &mut ProfileSnapshot) -> io::Result<()> {
+    let mut time = frame.start_time;
+    for _ in 0..read_uint(r)? {
+        let kind = match read_byte(r)? {
+            0 => EventKind::Enter,
+            1 => EventKind::Leave,
+            _ => return Err(invalid("unknown event kind")),
+        };
+        let id = match read_uint(r)? {
+            0 => read_uint(r)?,
+            idx => frame.nodes.get(idx as usize - 1).ok_or_else(|| invalid("event of a missing node"))?.id,
+        };
+        time = apply(time, read_int(r)?);
+        let payload = read_option(r, read_str)?;
+        let cpu = read_option(r, read_uint)?.map(|cpu| cpu as u32);
+        frame.events.push(TimelineEvent { kind, id, time, payload, cpu });
+    }
+    Ok(())
+}
+
+// The signed difference `a - b`, wrapping so any two values round-trip through `apply`.
+fn diff(a: u64, b: u64) -> i64 {
+    a.wrapping_sub(b) as i64
+}
+
+fn apply(base: u64, diff: i64) -> u64 {
+    base.wrapping_add(diff as u64)
+}
+
+fn write_uint<W: Write>(w: &mut W, mut n: u64) -> io::Result<()> {
+    let mut buf = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf[len] = byte;
+            len += 1;
+            break
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    w.write_all(&buf[..len])
+}
+
+fn write_int<W: Write>(w: &mut W, n: i64) -> io::Result<()> {
+    write_uint(w, ((n << 1) ^ (n >> 63)) as u64)
+}
+
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_uint(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_option<W: Write, T, F>(w: &mut W, value: Option<T>, write: F) -> io::Result<()>
+    where F: FnOnce(&mut W, T) -> io::Result<()>
+{
+    match value {
+        Some(value) => {
+            w.write_all(&[1])?;
+            write(w, value)
+        }
+        None => w.write_all(&[0]),
+    }
+}
+
+fn read_byte<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_uint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(r)?;
+        n |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n)
+        }
+    }
+    Err(invalid("malformed number"))
+}
+
+fn read_int<R: Read>(r: &mut R) -> io::Result<i64> {
+    let n = read_uint(r)?;
+    Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+}
+
+fn read_str<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = read_uint(r)?;
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated string"))
+    }
+    String::from_utf8(bytes).map_err(|_| invalid("string is not UTF-8"))
+}
+
+fn read_option<R: Read, T, F>(r: &mut R, read: F) -> io::Result<Option<T>>
+    where F: FnOnce(&mut R) -> io::Result<T>
+{
+    match read_byte(r)? {
+        0 => Ok(None),
+        1 => read(r).map(Some),
+        _ => Err(invalid("malformed optional value")),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,166 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Chrome trace event format, viewable in `chrome://tracing`, Perfetto and Speedscope.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use snapshot::{ProfileSnapshot, SnapshotNode};
+use timeline::{EventKind, TimelineEvent};
+use json;
+
+/// Write `frames` as a Chrome trace.
+///
+/// Frames with timeline events are written as they happened. Frames without them only have
+/// aggregate data, so each node is drawn as a single span as long as its total time, with
+/// children laid out back to back from the start of their parent.
+///
+/// The start of each frame is marked with a global instant event named after the frame's index,
+/// with its wall-clock time in seconds since the Unix epoch in `args`, if known. Events with a
+/// CPU core have it in `args`, as `cpu` when the scope was entered and `end_cpu` when it was left.
+pub fn write_chrome_trace<'a, W, I>(w: &mut W, frames: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    write_chrome_trace_with_metadata(w, frames, &BTreeMap::new())
+}
+
+/// Write `frames` as a Chrome trace, with the session's `metadata` in the trace's `metadata`
+/// object.
+pub fn write_chrome_trace_with_metadata<'a, W, I>(w: &mut W, frames: I, metadata: &BTreeMap<String, String>)
+    -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    let frames: Vec<&ProfileSnapshot> = frames.into_iter().collect();
+    // timestamps are from the start of the earliest frame, which of merged recordings needn't be the first
+    let base = frames.iter().map(|f| f.start_time).min().unwrap_or(0);
+    let mut first = true;
+    write!(w, "{{\"displayTimeUnit\":\"ns\",\"traceEvents\":[")?;
+    for frame in frames {
+        write_frame_marker(w, &mut first, frame, frame.start_time.saturating_sub(base))?;
+        if frame.events.is_empty() {
+            let mut starts = vec![0; frame.nodes.len()];
+            for (idx, node) in frame.nodes.iter().enumerate() {
+                let start = match node.parent {
+                    Some(parent) => {
+                        let start = starts[parent];
+                        starts[parent] += node.total_time;
+                        start
+                    }
+                    None => frame.start_time,
+                };
+                starts[idx] = start;
+                write_event(w, &mut first, node, "X", start.saturating_sub(base), Some(node.total_time), None)?;
+            }
+        } else {
+            let index: HashMap<u64, usize> = frame.nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+            for event in &frame.events {
+                let idx = match index.get(&event.id) {
+                    Some(&idx) => idx,
+                    None => continue,
+                };
+                let ph = match event.kind {
+                    EventKind::Enter => "B",
+                    EventKind::Leave => "E",
+                };
+                write_event(w, &mut first, &frame.nodes[idx], ph, event.time.saturating_sub(base), None, Some(event))?;
+            }
+        }
+    }
+    write!(w, "],\"metadata\":{{")?;
+    for (i, (key, value)) in metadata.iter().enumerate() {
+        if i > 0 {
+            write!(w, ",")?;
+        }
+        json::write_str(w, key)?;
+        write!(w, ":")?;
+        json::write_str(w, value)?;
+    }
+    write!(w, "}}}}")
+}
+
+fn write_frame_marker<W: Write>(w: &mut W, first: &mut bool, frame: &ProfileSnapshot, ts: u64) -> io::Result<()> {
+    if !*first {
+        write!(w, ",")?;
+    }
+    *first = false;
+    write!(w, "{{\"name\":\"frame {}\",\"cat\":\"frame\",\"ph\":\"i\",\"s\":\"g\",\"ts\":{:.3},\"pid\":1,\"tid\":1",
+           frame.index, ts as f64 / 1000.)?;
+    write!(w, ",\"args\":{{\"index\":{}", frame.index)?;
+    if let Some(time) = frame.unix_time() {
+        write!(w, ",\"wall_time\":{:.6}", time as f64 / 1e9)?;
+    }
+    write!(w, "}}}}")
+}
+
+fn write_event<W: Write>(w: &mut W, first: &mut bool, node: &SnapshotNode, ph: &str, ts: u64, dur: Option<u64>,
+                         event: Option<&TimelineEvent>) -> io::Result<()> {
+    if !*first {
+        write!(w, ",")?;
+    }
+    *first = false;
+    write!(w, "{{\"name\":")?;
+    json::write_str(w, &node.name)?;
+    write!(w, ",\"cat\":")?;
+    json::write_str(w, node.category.as_deref().unwrap_or("hprof"))?;
+    write!(w, ",\"ph\":\"{}\",\"ts\":{:.3},\"pid\":1,\"tid\":1", ph, ts as f64 / 1000.)?;
+    if let Some(dur) = dur {
+        write!(w, ",\"dur\":{:.3}", dur as f64 / 1000.)?;
+    }
+    // end events share the details of the matching begin event
+    let (annotations, location, blocked) = if ph == "E" {
+        (&[][..], None, None)
+    } else {
+        (&node.annotations[..], node.location, node.blocked_time)
+    };
+    if blocked.is_some() && node.waiting() {
+        // drawn in the color the viewers use for threads waiting on IO
+        write!(w, ",\"cname\":\"thread_state_iowait\"")?;
+    }
+    let payload = event.and_then(|e| e.payload.as_deref());
+    let cpu = event.and_then(|e| e.cpu);
+    if node.color.is_some() || location.is_some() || !annotations.is_empty() || payload.is_some() || blocked.is_some()
+        || cpu.is_some()
+    {
+        write!(w, ",\"args\":{{")?;
+        let mut sep = "";
+        if let Some(blocked) = blocked {
+            write!(w, "\"blocked_us\":{:.3}", blocked as f64 / 1000.)?;
+            sep = ",";
+        }
+        if let Some(color) = node.color {
+            write!(w, "{}\"color\":\"{}\"", sep, color)?;
+            sep = ",";
+        }
+        if let Some(location) = location {
+            write!(w, "{}\"file\":", sep)?;
+            json::write_str(w, location.file)?;
+            write!(w, ",\"line\":{},\"module\":", location.line)?;
+            json::write_str(w, location.module)?;
+            sep = ",";
+        }
+        if !annotations.is_empty() {
+            write!(w, "{}\"annotations\":[", sep)?;
+            for (i, note) in annotations.iter().enumerate() {
+                if i > 0 {
+                    write!(w, ",")?;
+                }
+                json::write_str(w, note)?;
+            }
+            write!(w, "]")?;
+            sep = ",";
+        }
+        if let Some(payload) = payload {
+            write!(w, "{}\"payload\":", sep)?;
+            json::write_str(w, payload)?;
+            sep = ",";
+        }
+        if let Some(cpu) = cpu {
+            write!(w, "{}\"{}\":{}", sep, if ph == "E" { "end_cpu" } else { "cpu" }, cpu)?;
+        }
+        write!(w, "}}")?;
+    }
+    write!(w, "}}")
+}
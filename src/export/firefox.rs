@@ -0,0 +1,265 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! The Firefox Profiler's processed profile format, which profiler.firefox.com loads directly
+//! and copes with much larger captures than `chrome://tracing`.
+//!
+//! The Firefox Profiler is built around samples, so each node of each frame becomes a sample of
+//! its stack weighted by its self time, with `"tracing-ms"` weights, so the call tree and flame
+//! graph show exact times rather than estimates. Every frame is also an interval marker named
+//! after its index, so frames can be picked out in the marker chart. Samples are placed as nodes
+//! are laid out in the Chrome export without timeline events: children back to back from the
+//! start of their parent.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use json;
+use snapshot::ProfileSnapshot;
+
+/// Version of the processed profile format written.
+pub const PROCESSED_PROFILE_VERSION: u32 = 44;
+
+// Firefox Profiler category colors given to hprof categories in turn
+const COLORS: &[&str] = &["blue", "green", "orange", "purple", "yellow", "lightblue", "brown", "red"];
+
+/// Write `frames`, recorded on the thread `thread`, as a Firefox Profiler profile.
+pub fn write_firefox_profile<'a, W, I>(w: &mut W, thread: &str, frames: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    write_firefox_threads(w, Some((thread, frames)))
+}
+
+/// Write the frames of several threads as one Firefox Profiler profile, such as those collected
+/// from registered threads with `collect_threads`. Each `(name, frames)` pair becomes a thread
+/// of its own, the first being the main thread.
+pub fn write_firefox_threads<'a, 'b, W, I, F>(w: &mut W, threads: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=(&'b str, F)>, F: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    let mut categories = vec!["hprof".to_string()];
+    let mut threads: Vec<Thread> = threads.into_iter()
+        .map(|(name, frames)| Thread::new(name, frames.into_iter().collect()))
+        .collect();
+    // profile times are in ms from the start of the first frame of any thread
+    let base = threads.iter().filter_map(|t| t.frames.first()).min_by_key(|f| f.start_time);
+    let (base, wall) = match base {
+        Some(frame) => (frame.start_time, frame.unix_time().unwrap_or(0)),
+        None => (0, 0),
+    };
+    for thread in &mut threads {
+        thread.build(base, &mut categories);
+    }
+    write!(w, "{{\"meta\":{{\"interval\":1,\"startTime\":{:.3},\"processType\":0,\"product\":\"hprof\",", ms(wall))?;
+    write!(w, "\"stackwalk\":0,\"version\":27,\"preprocessedProfileVersion\":{},\"symbolicated\":true,",
+           PROCESSED_PROFILE_VERSION)?;
+    write!(w, "\"abi\":\"\",\"misc\":\"\",\"oscpu\":\"\",\"platform\":\"\",\"toolkit\":\"\",\"appBuildID\":\"\",")?;
+    write!(w, "\"sourceURL\":\"\",\"physicalCPUs\":0,\"logicalCPUs\":0,\"markerSchema\":[],")?;
+    write!(w, "\"extensions\":{{\"id\":[],\"name\":[],\"baseURL\":[],\"length\":0}},\"categories\":[")?;
+    for (i, category) in categories.iter().enumerate() {
+        let color = if i == 0 { "grey" } else { COLORS[(i - 1) % COLORS.len()] };
+        write!(w, "{}{{\"name\":", if i > 0 { "," } else { "" })?;
+        json::write_str(w, category)?;
+        write!(w, ",\"color\":\"{}\",\"subcategories\":[\"Other\"]}}", color)?;
+    }
+    write!(w, "]}},\"libs\":[],\"pages\":[],\"threads\":[")?;
+    for (i, thread) in threads.iter().enumerate() {
+        if i > 0 {
+            write!(w, ",")?;
+        }
+        thread.write(w, i)?;
+    }
+    write!(w, "]}}")
+}
+
+fn ms(ns: u64) -> f64 {
+    ns as f64 / 1e6
+}
+
+// The tables of one thread, as the Firefox Profiler stores them.
+struct Thread<'a, 'b> {
+    name: &'b str,
+    frames: Vec<&'a ProfileSnapshot>,
+    strings: Vec<String>,
+    string_index: HashMap<String, usize>,
+    // name of each function, and the index of each name in `funcs`
+    funcs: Vec<usize>,
+    func_index: HashMap<usize, usize>,
+    // (function, category) of each frame, and its index by the same
+    frame_table: Vec<(usize, usize)>,
+    frame_index: HashMap<(usize, usize), usize>,
+    // (prefix, frame) of each stack, and its index by the same
+    stacks: Vec<(Option<usize>, usize)>,
+    stack_index: HashMap<(Option<usize>, usize), usize>,
+    // (stack, time in ns from the base, weight in ns)
+    samples: Vec<(usize, u64, u64)>,
+    // (name, start, end) in ns from the base
+    markers: Vec<(usize, u64, u64)>,
+}
+
+impl<'a, 'b> Thread<'a, 'b> {
+    fn new(name: &'b str, frames: Vec<&'a ProfileSnapshot>) -> Thread<'a, 'b> {
+        Thread {
+            name,
+            frames,
+            strings: Vec::new(),
+            string_index: HashMap::new(),
+            funcs: Vec::new(),
+            func_index: HashMap::new(),
+            frame_table: Vec::new(),
+            frame_index: HashMap::new(),
+            stacks: Vec::new(),
+            stack_index: HashMap::new(),
+            samples: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        if let Some(&i) = self.string_index.get(s) {
+            return i
+        }
+        self.strings.push(s.to_string());
+        self.string_index.insert(s.to_string(), self.strings.len() - 1);
+        self.strings.len() - 1
+    }
+
+    // Fill in the tables from the frames, with times relative to `base`, adding any new hprof
+    // categories to `categories`.
+    fn build(&mut self, base: u64, categories: &mut Vec<String>) {
+        let frames = self.frames.clone();
+        for frame in frames {
+            let start = frame.start_time.saturating_sub(base);
+            let name = self.string(&format!("frame {}", frame.index));
+            self.markers.push((name, start, start + frame.total_time()));
+            let mut starts = vec![0; frame.nodes.len()];
+            let mut stacks = vec![0; frame.nodes.len()];
+            for (idx, node) in frame.nodes.iter().enumerate() {
+                let (node_start, prefix) = match node.parent {
+                    Some(parent) => {
+                        let node_start = starts[parent];
+                        starts[parent] += node.total_time;
+                        (node_start, Some(stacks[parent]))
+                    }
+                    None => (start, None),
+                };
+                starts[idx] = node_start;
+                let category = match node.category {
+                    Some(ref name) => match categories.iter().position(|c| c == name) {
+                        Some(i) => i,
+                        None => {
+                            categories.push(name.to_string());
+                            categories.len() - 1
+                        }
+                    },
+                    None => 0,
+                };
+                let name = self.string(&node.name);
+                let funcs = &mut self.funcs;
+                let func = *self.func_index.entry(name).or_insert_with(|| {
+                    funcs.push(name);
+                    funcs.len() - 1
+                });
+                let frame_table = &mut self.frame_table;
+                let frame_idx = *self.frame_index.entry((func, category)).or_insert_with(|| {
+                    frame_table.push((func, category));
+                    frame_table.len() - 1
+                });
+                let stack_table = &mut self.stacks;
+                let stack = *self.stack_index.entry((prefix, frame_idx)).or_insert_with(|| {
+                    stack_table.push((prefix, frame_idx));
+                    stack_table.len() - 1
+                });
+                stacks[idx] = stack;
+                let self_time = frame.self_time(idx).as_nanos() as u64;
+                if self_time > 0 {
+                    self.samples.push((stack, node_start, self_time));
+                }
+            }
+        }
+    }
+
+    fn write<W: Write>(&self, w: &mut W, index: usize) -> io::Result<()> {
+        write!(w, "{{\"name\":")?;
+        json::write_str(w, self.name)?;
+        write!(w, ",\"processType\":\"default\",\"processName\":\"hprof\",\"processStartupTime\":0,")?;
+        write!(w, "\"processShutdownTime\":null,\"registerTime\":0,\"unregisterTime\":null,\"pausedRanges\":[],")?;
+        write!(w, "\"isMainThread\":{},\"pid\":\"1\",\"tid\":{},", index == 0, index + 1)?;
+
+        let samples = &self.samples;
+        write!(w, "\"samples\":{{\"length\":{},\"weightType\":\"tracing-ms\",\"stack\":", samples.len())?;
+        write_array(w, samples.iter().map(|s| s.0))?;
+        write!(w, ",\"time\":")?;
+        write_array(w, samples.iter().map(|s| format!("{:.6}", ms(s.1))))?;
+        write!(w, ",\"weight\":")?;
+        write_array(w, samples.iter().map(|s| format!("{:.6}", ms(s.2))))?;
+
+        let markers = &self.markers;
+        write!(w, "}},\"markers\":{{\"length\":{},\"name\":", markers.len())?;
+        write_array(w, markers.iter().map(|m| m.0))?;
+        write!(w, ",\"startTime\":")?;
+        write_array(w, markers.iter().map(|m| format!("{:.6}", ms(m.1))))?;
+        write!(w, ",\"endTime\":")?;
+        write_array(w, markers.iter().map(|m| format!("{:.6}", ms(m.2))))?;
+        // phase 1 is an interval
+        write!(w, ",\"phase\":")?;
+        write_array(w, markers.iter().map(|_| 1))?;
+        write!(w, ",\"category\":")?;
+        write_array(w, markers.iter().map(|_| 0))?;
+        write!(w, ",\"data\":")?;
+        write_array(w, markers.iter().map(|_| "null"))?;
+
+        let stacks = &self.stacks;
+        write!(w, "}},\"stackTable\":{{\"length\":{},\"prefix\":", stacks.len())?;
+        write_array(w, stacks.iter().map(|s| s.0.map(|p| p.to_string()).unwrap_or("null".to_string())))?;
+        write!(w, ",\"frame\":")?;
+        write_array(w, stacks.iter().map(|s| s.1))?;
+        write!(w, ",\"category\":")?;
+        write_array(w, stacks.iter().map(|s| self.frame_table[s.1].1))?;
+        write!(w, ",\"subcategory\":")?;
+        write_array(w, stacks.iter().map(|_| 0))?;
+
+        let frames = &self.frame_table;
+        write!(w, "}},\"frameTable\":{{\"length\":{},\"func\":", frames.len())?;
+        write_array(w, frames.iter().map(|f| f.0))?;
+        write!(w, ",\"category\":")?;
+        write_array(w, frames.iter().map(|f| f.1))?;
+        for &(key, value) in &[("address", "-1"), ("inlineDepth", "0"), ("subcategory", "0"), ("nativeSymbol", "null"),
+                               ("innerWindowID", "0"), ("implementation", "null"), ("line", "null"),
+                               ("column", "null")] {
+            write!(w, ",\"{}\":", key)?;
+            write_array(w, frames.iter().map(|_| value))?;
+        }
+
+        let funcs = &self.funcs;
+        write!(w, "}},\"funcTable\":{{\"length\":{},\"name\":", funcs.len())?;
+        write_array(w, funcs.iter())?;
+        for &(key, value) in &[("isJS", "false"), ("relevantForJS", "false"), ("resource", "-1"),
+                               ("fileName", "null"), ("lineNumber", "null"), ("columnNumber", "null")] {
+            write!(w, ",\"{}\":", key)?;
+            write_array(w, funcs.iter().map(|_| value))?;
+        }
+
+        write!(w, "}},\"resourceTable\":{{\"length\":0,\"lib\":[],\"name\":[],\"host\":[],\"type\":[]}},")?;
+        write!(w, "\"nativeSymbols\":{{\"length\":0,\"libIndex\":[],\"address\":[],\"name\":[],")?;
+        write!(w, "\"functionSize\":[]}},")?;
+        write!(w, "\"stringArray\":[")?;
+        for (i, s) in self.strings.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            json::write_str(w, s)?;
+        }
+        write!(w, "]}}")
+    }
+}
+
+fn write_array<W: Write, T: Display, I: IntoIterator<Item=T>>(w: &mut W, items: I) -> io::Result<()> {
+    write!(w, "[")?;
+    for (i, item) in items.into_iter().enumerate() {
+        write!(w, "{}{}", if i > 0 { "," } else { "" }, item)?;
+    }
+    write!(w, "]")
+}
@@ -0,0 +1,50 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! GitHub-flavored Markdown tables, for pasting profiles into issues and pull requests.
+
+use std::io::{self, Write};
+
+use snapshot::ProfileSnapshot;
+use Nanoseconds;
+
+/// Write the tree of `frame` as a Markdown table with the same columns as the text report:
+/// name, calls, average and total time, and percentage of the parent.
+///
+/// Names are indented by depth with non-breaking spaces, since Markdown ignores leading spaces
+/// in table cells. The root is the first row.
+pub fn write_markdown<W: Write>(w: &mut W, frame: &ProfileSnapshot) -> io::Result<()> {
+    writeln!(w, "| name | calls | avg | total | % |")?;
+    writeln!(w, "|:-----|------:|----:|------:|--:|")?;
+    for node in &frame.nodes {
+        let parent_time = match node.parent {
+            Some(parent) => frame.nodes[parent].total_time,
+            None => node.total_time,
+        };
+        let percent = if parent_time == 0 { 0.0 } else { 100.0 * node.total_time as f64 / parent_time as f64 };
+        writeln!(w, "| {}{} | {} | {} | {} | {:.1}% |", "&nbsp;&nbsp;".repeat(node.depth as usize),
+                 escape(&node.name), node.calls, Nanoseconds(node.total_time / node.calls.max(1) as u64),
+                 Nanoseconds(node.total_time), percent)?;
+    }
+    Ok(())
+}
+
+// Escape the characters of `s` that Markdown or the HTML inside it would interpret.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '|' | '\\' | '*' | '_' | '`' | '[' | ']' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
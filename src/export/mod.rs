@@ -0,0 +1,14 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Exporting snapshots to other tools' formats.
+
+pub mod chrome;
+pub mod firefox;
+pub mod markdown;
+pub mod msgpack;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod perfetto;
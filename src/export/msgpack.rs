@@ -0,0 +1,202 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! MessagePack, a compact binary alternative to JSON.
+//!
+//! Snapshots are written as maps with the same keys as the fields of `ProfileSnapshot`:
+//!
+//! ```text
+//! {"start_time": uint, "index": uint, "wall_time": uint | nil, "end_wall_time": uint | nil,
+//!  "tags": {str: str}, "nodes": [node], "events": [event]}
+//! ```
+//!
+//! where `wall_time` and `end_wall_time` are in ns since the Unix epoch, each node is
+//!
+//! ```text
+//! {"id": uint, "name": str, "depth": uint, "parent": uint | nil, "calls": uint,
+//!  "total_time": uint, "category": str | nil, "color": "#rrggbb" | nil, "annotations": [str],
+//!  "cpu_time": uint | nil, "blocked_time": uint | nil}
+//! ```
+//!
+//! and each event is
+//!
+//! ```text
+//! {"kind": "enter" | "leave", "id": uint, "time": uint, "payload": str | nil, "cpu": uint | nil}
+//! ```
+//!
+//! Recordings are `{"metadata": {str: str}, "frames": [snapshot]}`. Times are in ns, and integers
+//! use the smallest encoding that fits.
+
+use std::io::{self, Write};
+
+use recording::Recording;
+use snapshot::ProfileSnapshot;
+use timeline::EventKind;
+
+/// Write `snapshot` as MessagePack.
+pub fn write_snapshot<W: Write>(w: &mut W, snapshot: &ProfileSnapshot) -> io::Result<()> {
+    write_map_len(w, 7)?;
+    write_str(w, "start_time")?;
+    write_uint(w, snapshot.start_time)?;
+    write_str(w, "index")?;
+    write_uint(w, snapshot.index)?;
+    write_str(w, "wall_time")?;
+    match snapshot.unix_time() {
+        Some(time) => write_uint(w, time)?,
+        None => write_nil(w)?,
+    }
+    write_str(w, "end_wall_time")?;
+    match snapshot.unix_end_time() {
+        Some(time) => write_uint(w, time)?,
+        None => write_nil(w)?,
+    }
+    write_str(w, "tags")?;
+    write_map_len(w, snapshot.tags.len())?;
+    for (key, value) in &snapshot.tags {
+        write_str(w, key)?;
+        write_str(w, value)?;
+    }
+    write_str(w, "nodes")?;
+    write_array_len(w, snapshot.nodes.len())?;
+    for node in &snapshot.nodes {
+        write_map_len(w, 11)?;
+        write_str(w, "id")?;
+        write_uint(w, node.id)?;
+        write_str(w, "name")?;
+        write_str(w, &node.name)?;
+        write_str(w, "depth")?;
+        write_uint(w, node.depth as u64)?;
+        write_str(w, "parent")?;
+        match node.parent {
+            Some(parent) => write_uint(w, parent as u64)?,
+            None => write_nil(w)?,
+        }
+        write_str(w, "calls")?;
+        write_uint(w, node.calls as u64)?;
+        write_str(w, "total_time")?;
+        write_uint(w, node.total_time)?;
+        write_str(w, "category")?;
+        match node.category {
+            Some(ref category) => write_str(w, category)?,
+            None => write_nil(w)?,
+        }
+        write_str(w, "color")?;
+        match node.color {
+            Some(color) => write_str(w, &color.to_string())?,
+            None => write_nil(w)?,
+        }
+        write_str(w, "annotations")?;
+        write_array_len(w, node.annotations.len())?;
+        for note in &node.annotations {
+            write_str(w, note)?;
+        }
+        write_str(w, "cpu_time")?;
+        match node.cpu_time {
+            Some(cpu) => write_uint(w, cpu)?,
+            None => write_nil(w)?,
+        }
+        write_str(w, "blocked_time")?;
+        match node.blocked_time {
+            Some(blocked) => write_uint(w, blocked)?,
+            None => write_nil(w)?,
+        }
+    }
+    write_str(w, "events")?;
+    write_array_len(w, snapshot.events.len())?;
+    for event in &snapshot.events {
+        write_map_len(w, 5)?;
+        write_str(w, "kind")?;
+        write_str(w, match event.kind {
+            EventKind::Enter => "enter",
+            EventKind::Leave => "leave",
+        })?;
+        write_str(w, "id")?;
+        write_uint(w, event.id)?;
+        write_str(w, "time")?;
+        write_uint(w, event.time)?;
+        write_str(w, "payload")?;
+        match event.payload {
+            Some(ref payload) => write_str(w, payload)?,
+            None => write_nil(w)?,
+        }
+        write_str(w, "cpu")?;
+        match event.cpu {
+            Some(cpu) => write_uint(w, cpu as u64)?,
+            None => write_nil(w)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write `recording` as MessagePack.
+pub fn write_recording<W: Write>(w: &mut W, recording: &Recording) -> io::Result<()> {
+    write_map_len(w, 2)?;
+    write_str(w, "metadata")?;
+    write_map_len(w, recording.metadata.len())?;
+    for (key, value) in &recording.metadata {
+        write_str(w, key)?;
+        write_str(w, value)?;
+    }
+    write_str(w, "frames")?;
+    write_array_len(w, recording.frames.len())?;
+    for frame in &recording.frames {
+        write_snapshot(w, frame)?;
+    }
+    Ok(())
+}
+
+fn write_nil<W: Write>(w: &mut W) -> io::Result<()> {
+    w.write_all(&[0xc0])
+}
+
+fn write_uint<W: Write>(w: &mut W, n: u64) -> io::Result<()> {
+    if n < 0x80 {
+        w.write_all(&[n as u8])
+    } else if n <= 0xff {
+        w.write_all(&[0xcc, n as u8])
+    } else if n <= 0xffff {
+        w.write_all(&[0xcd])?;
+        w.write_all(&(n as u16).to_be_bytes())
+    } else if n <= 0xffff_ffff {
+        w.write_all(&[0xce])?;
+        w.write_all(&(n as u32).to_be_bytes())
+    } else {
+        w.write_all(&[0xcf])?;
+        w.write_all(&n.to_be_bytes())
+    }
+}
+
+// Write the header of something of length `len`, using the fixed-size form whose low bits hold
+// the length if it fits in `fix_bits`, or else the smallest of the 8-, 16- or 32-bit forms
+// available.
+fn write_len<W: Write>(w: &mut W, len: usize, fix: u8, fix_bits: u32, len8: Option<u8>,
+                       len16: u8, len32: u8) -> io::Result<()> {
+    if len < 1 << fix_bits {
+        w.write_all(&[fix | len as u8])
+    } else if let (Some(marker), true) = (len8, len <= 0xff) {
+        w.write_all(&[marker, len as u8])
+    } else if len <= 0xffff {
+        w.write_all(&[len16])?;
+        w.write_all(&(len as u16).to_be_bytes())
+    } else if len <= 0xffff_ffff {
+        w.write_all(&[len32])?;
+        w.write_all(&(len as u32).to_be_bytes())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "too long for MessagePack"))
+    }
+}
+
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_len(w, s.len(), 0xa0, 5, Some(0xd9), 0xda, 0xdb)?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_array_len<W: Write>(w: &mut W, len: usize) -> io::Result<()> {
+    write_len(w, len, 0x90, 4, None, 0xdc, 0xdd)
+}
+
+fn write_map_len<W: Write>(w: &mut W, len: usize) -> io::Result<()> {
+    write_len(w, len, 0x80, 4, None, 0xde, 0xdf)
+}
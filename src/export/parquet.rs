@@ -0,0 +1,156 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Parquet, for loading long captures into dataframe libraries such as Polars and pandas.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use snapshot::ProfileSnapshot;
+
+const SCHEMA: &str = "
+    message hprof {
+        required int64 frame (INTEGER(64, false));
+        required int64 frame_start (INTEGER(64, false));
+        optional int64 frame_wall_time (TIMESTAMP(NANOS, true));
+        required int64 node_id (INTEGER(64, false));
+        required binary path (UTF8);
+        required binary name (UTF8);
+        required int32 depth (INTEGER(32, false));
+        required int32 calls (INTEGER(32, false));
+        required int64 total_ns (INTEGER(64, false));
+        required int64 self_ns (INTEGER(64, false));
+        optional binary category (UTF8);
+    }
+";
+
+// Rows buffered before being written out as a row group.
+const ROW_GROUP_SIZE: usize = 1 << 20;
+
+#[derive(Default)]
+struct Columns {
+    frame: Vec<i64>,
+    frame_start: Vec<i64>,
+    frame_wall_time: Vec<i64>,
+    frame_wall_time_defined: Vec<i16>,
+    node_id: Vec<i64>,
+    path: Vec<ByteArray>,
+    name: Vec<ByteArray>,
+    depth: Vec<i32>,
+    calls: Vec<i32>,
+    total_ns: Vec<i64>,
+    self_ns: Vec<i64>,
+    category: Vec<ByteArray>,
+    category_defined: Vec<i16>,
+}
+
+fn to_io(e: ParquetError) -> io::Error {
+    io::Error::other(e)
+}
+
+/// Write `frames` as a Snappy-compressed Parquet file with a row per node per frame.
+///
+/// The columns are `frame`, the index of the frame in the session; `frame_start`, the timestamp in
+/// ns of the start of the frame; `frame_wall_time`, the wall-clock time the frame started, which
+/// is null if unknown; and the node's `node_id`, `path`, `name`, `depth`, `calls`,
+/// `total_ns`, `self_ns` and `category`, which is null for nodes without one. Roots have an empty
+/// path and depth 0.
+pub fn write_parquet<'a, W, I>(w: W, frames: I) -> io::Result<()>
+    where W: Write + Send, I: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    write_parquet_with_metadata(w, frames, &BTreeMap::new())
+}
+
+/// Write `frames` as Parquet, with the session's `metadata` in the file's key-value metadata.
+pub fn write_parquet_with_metadata<'a, W, I>(w: W, frames: I, metadata: &BTreeMap<String, String>) -> io::Result<()>
+    where W: Write + Send, I: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    let schema = Arc::new(parse_message_type(SCHEMA).map_err(to_io)?);
+    let metadata = metadata.iter().map(|(k, v)| KeyValue::new(k.clone(), v.clone())).collect();
+    let props = Arc::new(WriterProperties::builder()
+                             .set_compression(Compression::SNAPPY)
+                             .set_key_value_metadata(Some(metadata))
+                             .build());
+    let mut writer = SerializedFileWriter::new(w, schema, props).map_err(to_io)?;
+    let mut columns = Columns::default();
+    for frame in frames {
+        let mut child_time = vec![0; frame.nodes.len()];
+        for node in &frame.nodes {
+            if let Some(parent) = node.parent {
+                child_time[parent] += node.total_time;
+            }
+        }
+        for (idx, node) in frame.nodes.iter().enumerate() {
+            columns.frame.push(frame.index as i64);
+            columns.frame_start.push(frame.start_time as i64);
+            match frame.unix_time() {
+                Some(time) => {
+                    columns.frame_wall_time.push(time as i64);
+                    columns.frame_wall_time_defined.push(1);
+                }
+                None => columns.frame_wall_time_defined.push(0),
+            }
+            columns.node_id.push(node.id as i64);
+            columns.path.push(ByteArray::from(frame.path(idx).into_bytes()));
+            columns.name.push(ByteArray::from(&*node.name));
+            columns.depth.push(node.depth as i32);
+            columns.calls.push(node.calls as i32);
+            columns.total_ns.push(node.total_time as i64);
+            columns.self_ns.push(node.total_time.saturating_sub(child_time[idx]) as i64);
+            match node.category {
+                Some(ref category) => {
+                    columns.category.push(ByteArray::from(&**category));
+                    columns.category_defined.push(1);
+                }
+                None => columns.category_defined.push(0),
+            }
+        }
+        if columns.frame.len() >= ROW_GROUP_SIZE {
+            write_row_group(&mut writer, &columns).map_err(to_io)?;
+            columns = Columns::default();
+        }
+    }
+    if !columns.frame.is_empty() {
+        write_row_group(&mut writer, &columns).map_err(to_io)?;
+    }
+    writer.close().map_err(to_io)?;
+    Ok(())
+}
+
+fn write_row_group<W: Write + Send>(writer: &mut SerializedFileWriter<W>,
+                                    columns: &Columns) -> Result<(), ParquetError> {
+    let mut row_group = writer.next_row_group()?;
+    let mut idx = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match idx {
+            0 => column.typed::<Int64Type>().write_batch(&columns.frame, None, None)?,
+            1 => column.typed::<Int64Type>().write_batch(&columns.frame_start, None, None)?,
+            2 => column.typed::<Int64Type>().write_batch(&columns.frame_wall_time,
+                                                         Some(&columns.frame_wall_time_defined), None)?,
+            3 => column.typed::<Int64Type>().write_batch(&columns.node_id, None, None)?,
+            4 => column.typed::<ByteArrayType>().write_batch(&columns.path, None, None)?,
+            5 => column.typed::<ByteArrayType>().write_batch(&columns.name, None, None)?,
+            6 => column.typed::<Int32Type>().write_batch(&columns.depth, None, None)?,
+            7 => column.typed::<Int32Type>().write_batch(&columns.calls, None, None)?,
+            8 => column.typed::<Int64Type>().write_batch(&columns.total_ns, None, None)?,
+            9 => column.typed::<Int64Type>().write_batch(&columns.self_ns, None, None)?,
+            _ => column.typed::<ByteArrayType>().write_batch(&columns.category,
+                                                             Some(&columns.category_defined), None)?,
+        };
+        column.close()?;
+        idx += 1;
+    }
+    row_group.close()?;
+    Ok(())
+}
@@ -0,0 +1,242 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Perfetto traces, in the protobuf format that ui.perfetto.dev opens natively.
+//!
+//! Each thread gets a track of its own, named after it, holding a slice for every scope. The
+//! root of each frame is a slice too, with the frame's index and wall-clock start time as debug
+//! annotations, and timeline events recorded with `Profiler::set_cpu_ids` add their CPU core as
+//! `cpu` and `end_cpu`. Scopes with a color have it as a `color` annotation, as `#rrggbb`. Only
+//! the messages and fields needed for that are written, so the encoder is small enough to do
+//! without a protobuf library:
+//!
+//! ```text
+//! Trace { repeated TracePacket packet = 1; }
+//! TracePacket { timestamp = 8; trusted_packet_sequence_id = 10; track_event = 11;
+//!               sequence_flags = 13; track_descriptor = 60; }
+//! TrackDescriptor { uuid = 1; name = 2; process = 3; thread = 4; parent_uuid = 5; }
+//! ProcessDescriptor { pid = 1; process_name = 6; }
+//! ThreadDescriptor { pid = 1; tid = 2; thread_name = 5; }
+//! TrackEvent { debug_annotations = 4; type = 9; track_uuid = 11; categories = 22; name = 23; }
+//! DebugAnnotation { uint_value = 3; string_value = 6; name = 10; }
+//! ```
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use snapshot::{ProfileSnapshot, SnapshotNode};
+use timeline::{EventKind, TimelineEvent};
+
+// made-up ids of the process all threads are put in
+const PID: u64 = 1;
+const PROCESS_UUID: u64 = 1;
+
+const SLICE_BEGIN: u64 = 1;
+const SLICE_END: u64 = 2;
+
+// set on the first packet of each sequence, which Perfetto requires before any track event
+const SEQ_INCREMENTAL_STATE_CLEARED: u64 = 1;
+
+/// Write `frames`, recorded on the thread `thread`, as a Perfetto trace.
+///
+/// Frames with timeline events are written as they happened. Frames without them only have
+/// aggregate data, so each node is drawn as a single slice as long as its total time, with
+/// children laid out back to back from the start of their parent, as in the Chrome export.
+pub fn write_perfetto_trace<'a, W, I>(w: &mut W, thread: &str, frames: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    write_perfetto_threads(w, Some((thread, frames)))
+}
+
+/// Write the frames of several threads as one Perfetto trace, such as those collected from
+/// registered threads with `collect_threads`. Each `(name, frames)` pair gets a track of its own.
+pub fn write_perfetto_threads<'a, 'b, W, I, F>(w: &mut W, threads: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=(&'b str, F)>, F: IntoIterator<Item=&'a ProfileSnapshot>
+{
+    let mut process = Vec::new();
+    write_uint_field(&mut process, 1, PID);
+    write_str_field(&mut process, 6, "hprof");
+    let mut descriptor = Vec::new();
+    write_uint_field(&mut descriptor, 1, PROCESS_UUID);
+    write_message_field(&mut descriptor, 3, &process);
+    let mut packet = Vec::new();
+    write_message_field(&mut packet, 60, &descriptor);
+    write_packet(w, &packet)?;
+    for (i, (name, frames)) in threads.into_iter().enumerate() {
+        let tid = i as u64 + 1;
+        let track = PROCESS_UUID + tid;
+        let mut thread = Vec::new();
+        write_uint_field(&mut thread, 1, PID);
+        write_uint_field(&mut thread, 2, tid);
+        write_str_field(&mut thread, 5, name);
+        let mut descriptor = Vec::new();
+        write_uint_field(&mut descriptor, 1, track);
+        write_str_field(&mut descriptor, 2, name);
+        write_message_field(&mut descriptor, 4, &thread);
+        write_uint_field(&mut descriptor, 5, PROCESS_UUID);
+        let mut packet = Vec::new();
+        write_message_field(&mut packet, 60, &descriptor);
+        write_packet(w, &packet)?;
+        let mut writer = TrackWriter { w, track, sequence: tid, first: true, packet: Vec::new() };
+        for frame in frames {
+            writer.write_frame(frame)?;
+        }
+    }
+    Ok(())
+}
+
+// Writes the slices of one thread's track, on a packet sequence of its own.
+struct TrackWriter<'w, W: Write + 'w> {
+    w: &'w mut W,
+    track: u64,
+    sequence: u64,
+    // whether no packet has been written on the sequence yet
+    first: bool,
+    // reused for every packet
+    packet: Vec<u8>,
+}
+
+impl<'w, W: Write> TrackWriter<'w, W> {
+    fn write_frame(&mut self, frame: &ProfileSnapshot) -> io::Result<()> {
+        if frame.events.is_empty() {
+            let mut starts = vec![0; frame.nodes.len()];
+            // (depth, end) of the slices begun and not yet ended
+            let mut open: Vec<(u32, u64)> = Vec::new();
+            for (idx, node) in frame.nodes.iter().enumerate() {
+                let start = match node.parent {
+                    Some(parent) => {
+                        let start = starts[parent];
+                        starts[parent] += node.total_time;
+                        start
+                    }
+                    None => frame.start_time,
+                };
+                starts[idx] = start;
+                while open.last().map(|&(depth, _)| depth >= node.depth).unwrap_or(false) {
+                    let (_, end) = open.pop().unwrap();
+                    self.write_event(SLICE_END, end, None, frame, None)?;
+                }
+                self.write_event(SLICE_BEGIN, start, Some(node), frame, None)?;
+                open.push((node.depth, start + node.total_time));
+            }
+            while let Some((_, end)) = open.pop() {
+                self.write_event(SLICE_END, end, None, frame, None)?;
+            }
+        } else {
+            let index: HashMap<u64, usize> = frame.nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+            for event in &frame.events {
+                let node = match index.get(&event.id) {
+                    Some(&idx) => &frame.nodes[idx],
+                    None => continue,
+                };
+                match event.kind {
+                    EventKind::Enter => self.write_event(SLICE_BEGIN, event.time, Some(node), frame, Some(event))?,
+                    EventKind::Leave => self.write_event(SLICE_END, event.time, None, frame, Some(event))?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Write a track event of type `kind` at `time`. Slices are begun with their `node`, and the
+    // root of `frame` is annotated with the frame's index and wall-clock time. The payload and
+    // CPU core of the timeline event it comes from, `source`, are annotations too.
+    fn write_event(&mut self, kind: u64, time: u64, node: Option<&SnapshotNode>, frame: &ProfileSnapshot,
+                   source: Option<&TimelineEvent>) -> io::Result<()> {
+        let mut event = Vec::new();
+        write_uint_field(&mut event, 9, kind);
+        write_uint_field(&mut event, 11, self.track);
+        if let Some(node) = node {
+            write_str_field(&mut event, 23, &node.name);
+            if let Some(ref category) = node.category {
+                write_str_field(&mut event, 22, category);
+            }
+            if node.parent.is_none() {
+                write_uint_annotation(&mut event, "frame", frame.index);
+                if let Some(time) = frame.unix_time() {
+                    write_uint_annotation(&mut event, "wall_time_ns", time);
+                }
+            }
+            if let Some(blocked) = node.blocked_time {
+                write_uint_annotation(&mut event, "blocked_ns", blocked);
+            }
+            if let Some(color) = node.color {
+                write_str_annotation(&mut event, "color", &color.to_string());
+            }
+            if let Some(location) = node.location {
+                write_str_annotation(&mut event, "file", location.file);
+                write_uint_annotation(&mut event, "line", location.line as u64);
+                write_str_annotation(&mut event, "module", location.module);
+            }
+            for note in &node.annotations {
+                write_str_annotation(&mut event, "annotation", note);
+            }
+            if let Some(payload) = source.and_then(|e| e.payload.as_deref()) {
+                write_str_annotation(&mut event, "payload", payload);
+            }
+        }
+        if let Some(cpu) = source.and_then(|e| e.cpu) {
+            // annotations of the end of a slice are merged into those of its beginning
+            write_uint_annotation(&mut event, if node.is_some() { "cpu" } else { "end_cpu" }, cpu as u64);
+        }
+        self.packet.clear();
+        write_uint_field(&mut self.packet, 8, time);
+        write_uint_field(&mut self.packet, 10, self.sequence);
+        if self.first {
+            write_uint_field(&mut self.packet, 13, SEQ_INCREMENTAL_STATE_CLEARED);
+            self.first = false;
+        }
+        write_message_field(&mut self.packet, 11, &event);
+        write_packet(self.w, &self.packet)
+    }
+}
+
+fn write_uint_annotation(w: &mut Vec<u8>, name: &str, value: u64) {
+    let mut annotation = Vec::new();
+    write_str_field(&mut annotation, 10, name);
+    write_uint_field(&mut annotation, 3, value);
+    write_message_field(w, 4, &annotation);
+}
+
+fn write_str_annotation(w: &mut Vec<u8>, name: &str, value: &str) {
+    let mut annotation = Vec::new();
+    write_str_field(&mut annotation, 10, name);
+    write_str_field(&mut annotation, 6, value);
+    write_message_field(w, 4, &annotation);
+}
+
+// Write `packet` as a field of the top-level `Trace` message.
+fn write_packet<W: Write>(w: &mut W, packet: &[u8]) -> io::Result<()> {
+    let mut header = Vec::new();
+    write_varint(&mut header, 1 << 3 | 2);
+    write_varint(&mut header, packet.len() as u64);
+    w.write_all(&header)?;
+    w.write_all(packet)
+}
+
+fn write_varint(w: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        w.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    w.push(n as u8);
+}
+
+fn write_uint_field(w: &mut Vec<u8>, field: u64, n: u64) {
+    // wire type 0, varint
+    write_varint(w, field << 3);
+    write_varint(w, n);
+}
+
+fn write_str_field(w: &mut Vec<u8>, field: u64, s: &str) {
+    write_message_field(w, field, s.as_bytes());
+}
+
+fn write_message_field(w: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    // wire type 2, length-delimited
+    write_varint(w, field << 3 | 2);
+    write_varint(w, bytes.len() as u64);
+    w.extend_from_slice(bytes);
+}
@@ -0,0 +1,116 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Flat profiles: totals by name, without a tree.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use Nanoseconds;
+
+/// Calls and time of every scope with one name, as returned by `Profiler::flat_totals`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlatStats {
+    pub name: &'static str,
+    pub calls: u64,
+    /// Time spent in scopes with this name, counting nested scopes with the same name once.
+    pub total: Duration,
+}
+
+struct Entry {
+    name: &'static str,
+    calls: u64,
+    total: u64,
+    // number of scopes with this name currently entered
+    active: u32,
+    // when the outermost of them was entered
+    start: u64,
+}
+
+/// The totals of a profiler in flat mode, and the scopes it has entered.
+pub struct FlatProfile {
+    entries: Vec<Entry>,
+    index: HashMap<&'static str, usize>,
+    // indices into `entries` of the scopes entered, innermost last
+    stack: Vec<usize>,
+}
+
+impl FlatProfile {
+    pub fn new() -> FlatProfile {
+        FlatProfile { entries: Vec::new(), index: HashMap::new(), stack: Vec::new() }
+    }
+
+    pub fn enter(&mut self, name: &'static str, now: u64) {
+        let entries = &mut self.entries;
+        let idx = *self.index.entry(name).or_insert_with(|| {
+            entries.push(Entry { name, calls: 0, total: 0, active: 0, start: 0 });
+            entries.len() - 1
+        });
+        let entry = &mut self.entries[idx];
+        entry.calls += 1;
+        if entry.active == 0 {
+            entry.start = now;
+        }
+        entry.active += 1;
+        self.stack.push(idx);
+    }
+
+    /// Leave the innermost scope, returning false if there was none.
+    pub fn leave(&mut self, now: u64) -> bool {
+        let idx = match self.stack.pop() {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let entry = &mut self.entries[idx];
+        entry.active -= 1;
+        if entry.active == 0 {
+            entry.total += now.saturating_sub(entry.start);
+        }
+        true
+    }
+
+    /// Forget the scopes entered, without counting their time.
+    pub fn abandon(&mut self) {
+        for idx in self.stack.drain(..) {
+            self.entries[idx].active = 0;
+        }
+    }
+
+    /// The totals of every name, longest first.
+    pub fn stats(&self) -> Vec<FlatStats> {
+        let mut stats: Vec<FlatStats> = self.entries.iter().map(|e| FlatStats {
+            name: e.name,
+            calls: e.calls,
+            total: Duration::from_nanos(e.total),
+        }).collect();
+        stats.sort_by(|a, b| b.total.cmp(&a.total).then(a.name.cmp(b.name)));
+        stats
+    }
+
+    /// Reset every total to zero, keeping the scopes currently entered.
+    pub fn clear(&mut self, now: u64) {
+        for entry in &mut self.entries {
+            entry.calls = 0;
+            entry.total = 0;
+            entry.start = now;
+        }
+    }
+}
+
+/// Write `stats` as a table at indentation `indent`, with the calls, average and total time of
+/// each name.
+pub fn write_flat<W: Write>(w: &mut W, stats: &[FlatStats], indent: usize) -> io::Result<()> {
+    let width = stats.iter().map(|s| s.name.len()).max().unwrap_or(0).max("name".len());
+    writeln!(w, "{:indent$}{:width$}  {:>7}  {:>8}  {:>8}", "", "name", "calls", "avg", "total", indent = indent,
+             width = width)?;
+    for s in stats {
+        let total = s.total.as_nanos() as u64;
+        writeln!(w, "{:indent$}{:width$}  {:>7}  {:>8}  {:>8}", "", s.name, s.calls,
+                 Nanoseconds(total / s.calls.max(1)).to_string(), Nanoseconds(total).to_string(), indent = indent,
+                 width = width)?;
+    }
+    Ok(())
+}
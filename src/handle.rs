@@ -0,0 +1,95 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! A profiler that can be stored in global state.
+
+use std::cell::UnsafeCell;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread::{self, ThreadId};
+
+use Profiler;
+
+/// A `Send` and `Sync` handle to a `Profiler` bound to a single thread.
+///
+/// A `Profiler` can't leave the thread it was created on, so it can't be kept in a `static` or
+/// an engine's shared resources. A handle can: the profiler is only created the first time
+/// `get` is called, and belongs to that thread from then on. On every other thread, `get`
+/// returns `None`.
+///
+/// If the handle is dropped on a thread other than the owner, the profiler is leaked rather than
+/// dropped there.
+pub struct ProfilerHandle {
+    make: Box<dyn Fn() -> Profiler + Send + Sync>,
+    owner: OnceLock<ThreadId>,
+    initializing: AtomicBool,
+    profiler: UnsafeCell<Option<Profiler>>,
+}
+
+// The profiler is only created, used and dropped on the owning thread, and references to it
+// can't leave that thread since `Profiler` isn't `Sync`.
+unsafe impl Send for ProfilerHandle {}
+unsafe impl Sync for ProfilerHandle {}
+
+impl ProfilerHandle {
+    /// Create a handle to a profiler with the given name for the root node.
+    pub fn new(name: &'static str) -> ProfilerHandle {
+        ProfilerHandle::with_constructor(move || Profiler::new(name))
+    }
+
+    /// Create a handle to the profiler returned by `make`, which is called on the owning thread
+    /// the first time `get` is called there.
+    ///
+    /// `make` can configure the profiler with `Profiler::builder`, but must not use the handle.
+    pub fn with_constructor<F: Fn() -> Profiler + Send + Sync + 'static>(make: F) -> ProfilerHandle {
+        ProfilerHandle {
+            make: Box::new(make),
+            owner: OnceLock::new(),
+            initializing: AtomicBool::new(false),
+            profiler: UnsafeCell::new(None),
+        }
+    }
+
+    /// The profiler, if called on the owning thread or if the handle hasn't been used yet.
+    ///
+    /// Panics if called from the constructor passed to `with_constructor`.
+    pub fn get(&self) -> Option<&Profiler> {
+        let current = thread::current().id();
+        if *self.owner.get_or_init(|| current) != current {
+            return None
+        }
+        // only the owning thread gets here
+        unsafe {
+            if (*self.profiler.get()).is_none() {
+                if self.initializing.swap(true, Ordering::Relaxed) {
+                    panic!("ProfilerHandle::get called while creating the profiler");
+                }
+                let profiler = (self.make)();
+                *self.profiler.get() = Some(profiler);
+                self.initializing.store(false, Ordering::Relaxed);
+            }
+            (*self.profiler.get()).as_ref()
+        }
+    }
+
+    /// The thread the profiler belongs to, unless the handle hasn't been used yet.
+    pub fn owner(&self) -> Option<ThreadId> {
+        self.owner.get().cloned()
+    }
+}
+
+impl Drop for ProfilerHandle {
+    fn drop(&mut self) {
+        if let Some(&owner) = self.owner.get() {
+            if owner != thread::current().id() {
+                if let Some(profiler) = self.profiler.get_mut().take() {
+                    error!("ProfilerHandle dropped outside of its thread, leaking the profiler");
+                    mem::forget(profiler);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! High-dynamic-range histograms of call durations.
+
+use std::io::{self, Write};
+
+// Values are bucketed by their top `SUB_BITS` significant bits, giving a relative error of at
+// most 1/64 from 1ns to centuries.
+const SUB_BITS: u32 = 7;
+const SUB: u64 = 1 << SUB_BITS;
+const HALF: u64 = SUB / 2;
+
+/// A histogram of durations with bounded relative error.
+///
+/// Values are counted in logarithmically sized buckets, each split linearly, so the error of
+/// any reported value is within about 1.6% regardless of magnitude. That makes it possible to
+/// report tail latencies of hot scopes precisely without storing every call.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    counts: Vec<u32>,
+    total: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+fn index_of(value: u64) -> usize {
+    if value < SUB {
+        return value as usize
+    }
+    let top = 63 - value.leading_zeros();
+    let shift = top + 1 - SUB_BITS;
+    let m = value >> shift;
+    (SUB + (top - SUB_BITS) as u64 * HALF + (m - HALF)) as usize
+}
+
+// The lowest and highest values that land in bucket `idx`.
+fn bounds_of(idx: usize) -> (u64, u64) {
+    let idx = idx as u64;
+    if idx < SUB {
+        return (idx, idx)
+    }
+    let k = idx - SUB;
+    let shift = (k / HALF) as u32 + 1;
+    let m = HALF + k % HALF;
+    (m << shift, ((m + 1) << shift).wrapping_sub(1))
+}
+
+impl Histogram {
+    /// Create an empty histogram.
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    /// Record a duration in ns.
+    pub fn record(&mut self, value: u64) {
+        let idx = index_of(value);
+        if idx >= self.counts.len() {
+            self.counts.resize(idx + 1, 0);
+        }
+        self.counts[idx] = self.counts[idx].saturating_add(1);
+        if self.total == 0 || value < self.min {
+            self.min = value;
+        }
+        if value > self.max {
+            self.max = value;
+        }
+        self.total += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    /// The number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// The smallest value recorded, if any.
+    pub fn min(&self) -> Option<u64> {
+        if self.total == 0 { None } else { Some(self.min) }
+    }
+
+    /// The largest value recorded, if any.
+    pub fn max(&self) -> Option<u64> {
+        if self.total == 0 { None } else { Some(self.max) }
+    }
+
+    /// The mean of the values recorded, if any.
+    pub fn mean(&self) -> Option<f64> {
+        if self.total == 0 { None } else { Some(self.sum as f64 / self.total as f64) }
+    }
+
+    /// The value below which a fraction `q` (between 0 and 1) of recorded values fall.
+    ///
+    /// Reports the highest value equivalent to the one found, within the histogram's precision.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.total == 0 {
+            return None
+        }
+        let target = ((q.clamp(0.0, 1.0) * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (idx, &count) in self.counts.iter().enumerate() {
+            seen += count as u64;
+            if seen >= target {
+                return Some(bounds_of(idx).1.min(self.max).max(self.min))
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Iterate over the non-empty buckets as `(lowest value, highest value, count)`.
+    pub fn buckets<'a>(&'a self) -> Box<dyn Iterator<Item=(u64, u64, u64)> + 'a> {
+        Box::new(self.counts.iter().enumerate().filter(|&(_, &c)| c > 0).map(|(i, &c)| {
+            let (lo, hi) = bounds_of(i);
+            (lo, hi, c as u64)
+        }))
+    }
+
+    /// Add all values recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.total == 0 {
+            return
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (mine, &theirs) in self.counts.iter_mut().zip(&other.counts) {
+            *mine = mine.saturating_add(theirs);
+        }
+        if self.total == 0 || other.min < self.min {
+            self.min = other.min;
+        }
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+
+    /// Forget all recorded values.
+    pub fn clear(&mut self) {
+        *self = Histogram::default();
+    }
+
+    /// Write the percentile distribution in the text format used by HdrHistogram tools, with
+    /// values in ns.
+    pub fn write_percentiles<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "{:>12} {:>14} {:>10} {:>14}", "Value", "Percentile", "TotalCount", "1/(1-Percentile)")?;
+        let mut seen = 0;
+        for (_, hi, count) in self.buckets() {
+            seen += count;
+            let fraction = seen as f64 / self.total as f64;
+            let value = hi.min(self.max);
+            if seen == self.total {
+                writeln!(w, "{:>12} {:>14.12} {:>10} {:>14}", value, fraction, seen, "inf")?;
+            } else {
+                writeln!(w, "{:>12} {:>14.12} {:>10} {:>14.2}", value, fraction, seen, 1.0 / (1.0 - fraction))?;
+            }
+        }
+        match self.mean() {
+            Some(mean) => writeln!(w, "#[Mean = {:.3}, Max = {}, Total count = {}]", mean, self.max, self.total),
+            None => writeln!(w, "#[Total count = 0]"),
+        }
+    }
+}
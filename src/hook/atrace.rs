@@ -0,0 +1,63 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as ATrace sections, for Perfetto and systrace on Android. Requires the `atrace`
+//! feature, and Android.
+
+use std::os::raw::c_char;
+
+use hook::ScopeHook;
+use ProfileNode;
+
+// from the NDK's <android/trace.h>, in libandroid since API level 23
+#[link(name = "android")]
+extern "C" {
+    fn ATrace_isEnabled() -> bool;
+    fn ATrace_beginSection(section_name: *const c_char);
+    fn ATrace_endSection();
+}
+
+/// Begins an ATrace section for every scope entered and ends it when the scope is left, so
+/// scopes show up in Perfetto system traces next to SurfaceFlinger, binder and scheduler
+/// activity.
+///
+/// Sections are written to the kernel's `trace_marker` by the NDK's ATrace API, which only does
+/// so while the app is being traced: run with the app's package in `atrace_apps` of a Perfetto
+/// config, or with `-a <package>` for systrace. Sections are per thread, so a profiler should
+/// only be given this hook on the thread it is used from.
+#[derive(Clone, Debug, Default)]
+pub struct AtraceHook {
+    // whether a section was begun for each scope in progress, innermost last
+    begun: Vec<bool>,
+    // the NUL-terminated name being written, kept to avoid allocating for every scope
+    buf: Vec<u8>,
+}
+
+impl AtraceHook {
+    pub fn new() -> AtraceHook {
+        AtraceHook::default()
+    }
+}
+
+impl ScopeHook for AtraceHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        if !unsafe { ATrace_isEnabled() } {
+            // keep leaves matched with enters in case tracing starts in between
+            self.begun.push(false);
+            return
+        }
+        self.begun.push(true);
+        self.buf.clear();
+        self.buf.extend(node.name.bytes().filter(|&b| b != 0));
+        self.buf.push(0);
+        unsafe { ATrace_beginSection(self.buf.as_ptr() as *const c_char) }
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        if self.begun.pop() == Some(true) {
+            unsafe { ATrace_endSection() }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as graphics debug markers, for RenderDoc, PIX, Nsight Graphics and other graphics
+//! debuggers. Requires the `debug_markers` feature.
+
+use category::Color;
+use hook::ScopeHook;
+use ProfileNode;
+
+/// Pushes a debug marker for every scope entered and pops it when the scope is left, so GPU
+/// captures show the same hierarchy as the CPU profile.
+///
+/// Graphics debuggers read markers from the graphics API rather than from an API of their own,
+/// so emitting them is left to two callbacks: `push`, given the name of the node and its color if
+/// it has one, and `pop`. With Vulkan these would call `vkCmdBeginDebugUtilsLabelEXT` and
+/// `vkCmdEndDebugUtilsLabelEXT` on the command buffer being recorded, with OpenGL
+/// `glPushDebugGroup` and `glPopDebugGroup`, and with Direct3D 12 `BeginEvent` and `EndEvent`:
+///
+/// ```ignore
+/// profiler.add_hook(DebugMarkerHook::new(
+///     |name, _color| unsafe { gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, -1, cstr(name)) },
+///     || unsafe { gl::PopDebugGroup() },
+/// ));
+/// ```
+///
+/// Markers are usually tied to a context or command buffer used from a single thread, so a
+/// profiler should only be given this hook on that thread.
+pub struct DebugMarkerHook<P, Q> {
+    push: P,
+    pop: Q,
+}
+
+impl<P, Q> DebugMarkerHook<P, Q>
+    where P: FnMut(&str, Option<Color>), Q: FnMut()
+{
+    /// Emit markers through `push` and `pop`.
+    pub fn new(push: P, pop: Q) -> DebugMarkerHook<P, Q> {
+        DebugMarkerHook { push, pop }
+    }
+}
+
+impl<P, Q> ScopeHook for DebugMarkerHook<P, Q>
+    where P: FnMut(&str, Option<Color>), Q: FnMut()
+{
+    fn enter(&mut self, node: &ProfileNode) {
+        (self.push)(node.name, node.effective_color());
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        (self.pop)();
+    }
+}
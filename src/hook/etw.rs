@@ -0,0 +1,159 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as Event Tracing for Windows events, for WPA and PIX. Requires the `etw` feature, and
+//! Windows.
+
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use hook::ScopeHook;
+use ProfileNode;
+
+#[repr(C)]
+struct Guid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+#[repr(C)]
+struct EventDescriptor {
+    id: u16,
+    version: u8,
+    channel: u8,
+    level: u8,
+    opcode: u8,
+    task: u16,
+    keyword: u64,
+}
+
+#[repr(C)]
+struct EventDataDescriptor {
+    ptr: u64,
+    size: u32,
+    kind: u32,
+}
+
+impl EventDataDescriptor {
+    fn new(data: &[u8], kind: u32) -> EventDataDescriptor {
+        EventDataDescriptor { ptr: data.as_ptr() as usize as u64, size: data.len() as u32, kind }
+    }
+}
+
+type EnableCallback = extern "system" fn(*const Guid, u32, u8, u64, u64, *const c_void, *mut c_void);
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn EventRegister(provider: *const Guid, callback: Option<EnableCallback>, context: *mut c_void,
+                     handle: *mut u64) -> u32;
+    fn EventUnregister(handle: u64) -> u32;
+    fn EventEnabled(handle: u64, descriptor: *const EventDescriptor) -> u8;
+    fn EventWriteTransfer(handle: u64, descriptor: *const EventDescriptor, activity: *const Guid,
+                          related: *const Guid, count: u32, data: *const EventDataDescriptor) -> u32;
+}
+
+// Kinds of data descriptor.
+const USER_DATA: u32 = 0;
+const EVENT_METADATA: u32 = 1;
+const PROVIDER_METADATA: u32 = 2;
+
+// The channel TraceLogging events are written to, the verbose level, and the start and stop
+// opcodes of an activity.
+const TRACELOGGING_CHANNEL: u8 = 11;
+const LEVEL_VERBOSE: u8 = 5;
+const OPCODE_START: u8 = 1;
+const OPCODE_STOP: u8 = 2;
+
+// TraceLogging metadata of the "Scope" event: its size, tags, name, then each field's name and
+// type. The single "Name" field is a counted string (0x17) displayed as UTF-8 (0x23).
+static EVENT_METADATA_BLOB: &[u8] = b"\x10\x00\x00Scope\x00Name\x00\x97\x23";
+
+/// Writes a TraceLogging event for every scope entered and left, as the start and stop of an
+/// activity named "Scope", with the node's name in its "Name" field.
+///
+/// Nothing is written unless a trace session has enabled the provider, for example with
+/// `wpr -start` and a profile naming it, or `tracelog`.
+pub struct EtwHook {
+    handle: u64,
+    // TraceLogging metadata of the provider: its size and name
+    provider: Vec<u8>,
+}
+
+impl EtwHook {
+    /// Register a provider called `name` with the given GUID, written as a single integer such as
+    /// `0x3970f9cf_2c0c_4f11_b1cc_e3a1e9958833`.
+    ///
+    /// The GUID is what sessions enable the provider by. Registration failures are logged, and
+    /// leave a hook that writes nothing.
+    pub fn new(name: &str, guid: u128) -> EtwHook {
+        let guid = Guid {
+            data1: (guid >> 96) as u32,
+            data2: (guid >> 80) as u16,
+            data3: (guid >> 64) as u16,
+            data4: (guid as u64).to_be_bytes(),
+        };
+        let mut handle = 0;
+        let status = unsafe { EventRegister(&guid, None, ptr::null_mut(), &mut handle) };
+        if status != 0 {
+            error!("Couldn't register ETW provider {}: error {}", name, status);
+            handle = 0;
+        }
+        let mut provider = Vec::with_capacity(name.len() + 3);
+        provider.extend_from_slice(&((name.len() + 3) as u16).to_le_bytes());
+        provider.extend_from_slice(name.as_bytes());
+        provider.push(0);
+        EtwHook { handle, provider }
+    }
+
+    fn write(&self, opcode: u8, node: &ProfileNode) {
+        if self.handle == 0 {
+            return
+        }
+        let descriptor = EventDescriptor {
+            id: 0,
+            version: 0,
+            channel: TRACELOGGING_CHANNEL,
+            level: LEVEL_VERBOSE,
+            opcode,
+            task: 0,
+            keyword: 0,
+        };
+        if unsafe { EventEnabled(self.handle, &descriptor) } == 0 {
+            return
+        }
+        let name = &node.name.as_bytes()[..node.name.len().min(u16::MAX as usize)];
+        let len = (name.len() as u16).to_le_bytes();
+        let data = [
+            EventDataDescriptor::new(&self.provider, PROVIDER_METADATA),
+            EventDataDescriptor::new(EVENT_METADATA_BLOB, EVENT_METADATA),
+            EventDataDescriptor::new(&len, USER_DATA),
+            EventDataDescriptor::new(name, USER_DATA),
+        ];
+        unsafe {
+            EventWriteTransfer(self.handle, &descriptor, ptr::null(), ptr::null(), data.len() as u32, data.as_ptr());
+        }
+    }
+}
+
+impl ScopeHook for EtwHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        self.write(OPCODE_START, node);
+    }
+
+    fn leave(&mut self, node: &ProfileNode) {
+        self.write(OPCODE_STOP, node);
+    }
+}
+
+impl Drop for EtwHook {
+    fn drop(&mut self) {
+        if self.handle != 0 {
+            unsafe { EventUnregister(mem::replace(&mut self.handle, 0)) };
+        }
+    }
+}
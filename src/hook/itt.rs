@@ -0,0 +1,50 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as Intel ITT tasks, for VTune. Requires the `itt` feature.
+
+use ittapi::{Domain, Task};
+
+use hook::ScopeHook;
+use ProfileNode;
+
+/// Forwards every scope to the Instrumentation and Tracing Technology API as a task, so VTune's
+/// analyses can be grouped by scope.
+///
+/// The API does nothing unless the process was started by VTune or another ITT collector.
+pub struct IttHook {
+    domain: &'static Domain,
+    tasks: Vec<Task<'static>>,
+}
+
+impl IttHook {
+    /// Create a hook emitting tasks in the domain called "hprof".
+    pub fn new() -> IttHook {
+        IttHook::with_domain("hprof")
+    }
+
+    /// Create a hook emitting tasks in the domain called `name`.
+    pub fn with_domain(name: &str) -> IttHook {
+        // ITT never frees domains, so there is nothing lost by leaking ours
+        IttHook { domain: Box::leak(Box::new(Domain::new(name))), tasks: Vec::new() }
+    }
+}
+
+impl Default for IttHook {
+    fn default() -> IttHook {
+        IttHook::new()
+    }
+}
+
+impl ScopeHook for IttHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        self.tasks.push(Task::begin(self.domain, node.name));
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        // dropping a task ends it
+        self.tasks.pop();
+    }
+}
@@ -0,0 +1,58 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Forwarding scopes to other profilers as they are entered and left.
+
+use ProfileNode;
+
+#[cfg(all(feature = "atrace", target_os = "android"))]
+pub mod atrace;
+#[cfg(feature = "debug_markers")]
+pub mod debug_marker;
+#[cfg(all(feature = "etw", windows))]
+pub mod etw;
+#[cfg(feature = "itt")]
+pub mod itt;
+#[cfg(feature = "nvtx")]
+pub mod nvtx;
+#[cfg(all(feature = "signpost", any(target_os = "macos", target_os = "ios")))]
+pub mod signpost;
+#[cfg(feature = "superluminal")]
+pub mod superluminal;
+#[cfg(all(feature = "trace_marker", target_os = "linux"))]
+pub mod trace_marker;
+
+#[cfg(all(feature = "atrace", target_os = "android"))]
+pub use self::atrace::AtraceHook;
+#[cfg(feature = "debug_markers")]
+pub use self::debug_marker::DebugMarkerHook;
+#[cfg(all(feature = "etw", windows))]
+pub use self::etw::EtwHook;
+#[cfg(feature = "itt")]
+pub use self::itt::IttHook;
+#[cfg(feature = "nvtx")]
+pub use self::nvtx::NvtxHook;
+#[cfg(all(feature = "signpost", any(target_os = "macos", target_os = "ios")))]
+pub use self::signpost::SignpostHook;
+#[cfg(feature = "superluminal")]
+pub use self::superluminal::SuperluminalHook;
+#[cfg(all(feature = "trace_marker", target_os = "linux"))]
+pub use self::trace_marker::TraceMarkerHook;
+
+/// Something told about every node a `Profiler` enters and leaves, such as the instrumentation
+/// API of an external profiler.
+///
+/// Add one with `Profiler::add_hook`. As long as the profiler's own `enter` and `leave` calls are
+/// balanced, so are the hook's, with the calls for any nodes entered in between nested inside.
+///
+/// A hook may use the profiler that calls it, for example entering a scope or emitting a
+/// `tracing` span while `layer::HprofLayer` is installed. Scopes entered from a hook are
+/// recorded, but not passed to any hook.
+pub trait ScopeHook {
+    /// Called right after `node` is entered.
+    fn enter(&mut self, node: &ProfileNode);
+    /// Called right before `node` is left.
+    fn leave(&mut self, node: &ProfileNode);
+}
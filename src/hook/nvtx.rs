@@ -0,0 +1,33 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as NVTX ranges, for Nsight Systems. Requires the `nvtx` feature.
+
+use hook::ScopeHook;
+use ProfileNode;
+
+/// Pushes an NVTX range for every scope entered and pops it when the scope is left, so Nsight
+/// timelines show the structure of each frame next to CUDA and graphics activity.
+///
+/// Ranges are pushed on the calling thread, so a profiler should only be given this hook on the
+/// thread it is used from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvtxHook;
+
+impl NvtxHook {
+    pub fn new() -> NvtxHook {
+        NvtxHook
+    }
+}
+
+impl ScopeHook for NvtxHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        range_push!("{}", node.name);
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        range_pop!();
+    }
+}
@@ -0,0 +1,98 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as `os_signpost` intervals, for Instruments. Requires the `signpost` feature, and
+//! macOS or iOS.
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+use hook::ScopeHook;
+use ProfileNode;
+
+// `os_log_t` is an opaque object pointer
+type OsLog = *mut c_void;
+
+const OS_SIGNPOST_INTERVAL_BEGIN: u8 = 1;
+const OS_SIGNPOST_INTERVAL_END: u8 = 2;
+
+// The `os_signpost_interval_*` macros can't be called from Rust, so this emits intervals the way
+// they expand to. Their name and format strings are read from the image they are in, and must be
+// static.
+static NAME: &[u8] = b"scope\0";
+static BEGIN_FORMAT: &[u8] = b"%{public}s\0";
+static END_FORMAT: &[u8] = b"\0";
+
+extern "C" {
+    static __dso_handle: u8;
+    fn os_log_create(subsystem: *const c_char, category: *const c_char) -> OsLog;
+    fn os_signpost_enabled(log: OsLog) -> bool;
+    fn os_signpost_id_generate(log: OsLog) -> u64;
+    fn _os_signpost_emit_with_name_impl(dso: *const c_void, log: OsLog, kind: u8, id: u64, name: *const c_char,
+                                        format: *const c_char, buf: *mut u8, size: u32);
+}
+
+/// Emits an `os_signpost` interval for every scope, so Instruments shows them in the Points of
+/// Interest track, or in a track of their own with the os_signpost instrument.
+///
+/// Every interval is named "scope", with the name of the node as its message. Nothing is
+/// emitted unless the log is being recorded.
+pub struct SignpostHook {
+    log: OsLog,
+    // ids of the intervals in progress, innermost last
+    ids: Vec<u64>,
+}
+
+impl SignpostHook {
+    /// Create a hook emitting intervals in the Points of Interest category of subsystem
+    /// `subsystem`, usually the app's bundle identifier.
+    pub fn new(subsystem: &str) -> SignpostHook {
+        SignpostHook::with_category(subsystem, "PointsOfInterest")
+    }
+
+    /// Create a hook emitting intervals in `category` of `subsystem`.
+    ///
+    /// Panics if either contains a NUL byte.
+    pub fn with_category(subsystem: &str, category: &str) -> SignpostHook {
+        let subsystem = CString::new(subsystem).expect("subsystem contains a NUL byte");
+        let category = CString::new(category).expect("category contains a NUL byte");
+        let log = unsafe { os_log_create(subsystem.as_ptr(), category.as_ptr()) };
+        SignpostHook { log, ids: Vec::new() }
+    }
+
+    fn emit(&self, kind: u8, id: u64, format: &'static [u8], buf: &mut [u8]) {
+        unsafe {
+            _os_signpost_emit_with_name_impl(&__dso_handle as *const u8 as *const c_void, self.log, kind, id,
+                                             NAME.as_ptr() as *const c_char, format.as_ptr() as *const c_char,
+                                             buf.as_mut_ptr(), buf.len() as u32);
+        }
+    }
+}
+
+impl ScopeHook for SignpostHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        if !unsafe { os_signpost_enabled(self.log) } {
+            // keep leaves matched with enters in case recording starts in between
+            self.ids.push(0);
+            return
+        }
+        let id = unsafe { os_signpost_id_generate(self.log) };
+        self.ids.push(id);
+        let message = CString::new(node.name).unwrap_or_default();
+        let ptr = (message.as_ptr() as usize as u64).to_ne_bytes();
+        // an os_log argument buffer: a summary byte (has non-scalar arguments), the argument
+        // count, then a public string argument given by pointer
+        let mut buf = [2, 1, 0x22, 8, 0, 0, 0, 0, 0, 0, 0, 0];
+        buf[4..].copy_from_slice(&ptr);
+        self.emit(OS_SIGNPOST_INTERVAL_BEGIN, id, BEGIN_FORMAT, &mut buf);
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        match self.ids.pop() {
+            Some(0) | None => {}
+            Some(id) => self.emit(OS_SIGNPOST_INTERVAL_END, id, END_FORMAT, &mut [0, 0]),
+        }
+    }
+}
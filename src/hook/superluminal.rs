@@ -0,0 +1,41 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as events on the Superluminal timeline. Requires the `superluminal` feature.
+
+use superluminal_perf;
+
+use hook::ScopeHook;
+use ProfileNode;
+
+/// Forwards every scope to the Superluminal Performance API, so scopes show up as instrumentation
+/// events on its timeline.
+///
+/// Nodes with a category are drawn in its color. The API does nothing unless the process is
+/// being profiled by Superluminal, and is a no-op on platforms it doesn't support.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SuperluminalHook;
+
+impl SuperluminalHook {
+    pub fn new() -> SuperluminalHook {
+        SuperluminalHook
+    }
+}
+
+impl ScopeHook for SuperluminalHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        match node.effective_color() {
+            Some(c) => {
+                let color = (c.r as u32) << 24 | (c.g as u32) << 16 | (c.b as u32) << 8 | 0xff;
+                superluminal_perf::begin_event_with_color(node.name, color);
+            }
+            None => superluminal_perf::begin_event(node.name),
+        }
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        superluminal_perf::end_event();
+    }
+}
@@ -0,0 +1,66 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Scopes as ftrace markers, for `perf`, `trace-cmd` and Perfetto. Requires the `trace_marker`
+//! feature, and Linux.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::process;
+
+use hook::ScopeHook;
+use ProfileNode;
+
+/// Writes a marker to the kernel's `trace_marker` file for every scope entered and left, so
+/// kernel traces recorded at the same time can be lined up with scopes.
+///
+/// Markers are in the `B|pid|name` and `E|pid` format understood by Perfetto and systrace, which
+/// draw them as slices. `perf record -e ftrace:print` and `trace-cmd record -e ftrace:print`
+/// record them as `print` events. Writing a marker costs a system call, so this is best kept to
+/// deep dives.
+pub struct TraceMarkerHook {
+    file: File,
+    pid: u32,
+    // the marker being written, kept to avoid allocating for every scope
+    buf: Vec<u8>,
+}
+
+impl TraceMarkerHook {
+    /// Open the `trace_marker` file of tracefs, mounted at `/sys/kernel/tracing` or, on older
+    /// kernels, `/sys/kernel/debug/tracing`.
+    ///
+    /// Writing to it usually needs root, or permissions given by `chmod` or `chgrp`.
+    pub fn new() -> io::Result<TraceMarkerHook> {
+        TraceMarkerHook::open("/sys/kernel/tracing/trace_marker")
+            .or_else(|_| TraceMarkerHook::open("/sys/kernel/debug/tracing/trace_marker"))
+    }
+
+    /// Open the `trace_marker` file at `path`.
+    pub fn open(path: &str) -> io::Result<TraceMarkerHook> {
+        let file = OpenOptions::new().write(true).open(path)?;
+        Ok(TraceMarkerHook { file, pid: process::id(), buf: Vec::new() })
+    }
+
+    fn write_marker(&mut self) {
+        // the whole marker goes in one write, since each write is a separate marker
+        if let Err(e) = self.file.write_all(&self.buf) {
+            error!("Couldn't write trace marker: {}", e);
+        }
+    }
+}
+
+impl ScopeHook for TraceMarkerHook {
+    fn enter(&mut self, node: &ProfileNode) {
+        self.buf.clear();
+        let _ = write!(self.buf, "B|{}|{}", self.pid, node.name);
+        self.write_marker();
+    }
+
+    fn leave(&mut self, _node: &ProfileNode) {
+        self.buf.clear();
+        let _ = write!(self.buf, "E|{}", self.pid);
+        self.write_marker();
+    }
+}
@@ -0,0 +1,63 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Flat profiles, summing every node of the same name wherever it is in the tree.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ProfileNode;
+
+/// The time spent in every node with one name, as returned by `Profiler::hotspots`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hotspot {
+    pub name: &'static str,
+    /// Number of nodes with this name.
+    pub nodes: usize,
+    /// Total calls made to all of them.
+    pub calls: u64,
+    /// Time spent in them and not in their children.
+    pub self_time: Duration,
+    /// Time spent in them including their children, counting nested calls once.
+    pub total: Duration,
+}
+
+/// Sum the nodes below `root` by name, answering questions such as how much time goes to
+/// "hash lookup" regardless of where it is called from.
+///
+/// Sorted by self time, longest first. Includes the children of mounted profilers.
+pub fn hotspots(root: &ProfileNode) -> Vec<Hotspot> {
+    let mut by_name: HashMap<&'static str, Hotspot> = HashMap::new();
+    let mut open = Vec::new();
+    visit(root, &mut by_name, &mut open);
+    let mut hotspots: Vec<Hotspot> = by_name.into_values().collect();
+    hotspots.sort_by(|a, b| b.self_time.cmp(&a.self_time).then(a.name.cmp(b.name)));
+    hotspots
+}
+
+// `open` holds the names of the ancestors of `node`, so that a node nested in another of the
+// same name doesn't add its total twice.
+fn visit(node: &ProfileNode, by_name: &mut HashMap<&'static str, Hotspot>, open: &mut Vec<&'static str>) {
+    for child in &node.all_children() {
+        {
+            let hotspot = by_name.entry(child.name).or_insert_with(|| Hotspot {
+                name: child.name,
+                nodes: 0,
+                calls: 0,
+                self_time: Duration::from_nanos(0),
+                total: Duration::from_nanos(0),
+            });
+            hotspot.nodes += 1;
+            hotspot.calls += child.calls.get() as u64;
+            hotspot.self_time += child.self_time();
+            if !open.contains(&child.name) {
+                hotspot.total += child.total();
+            }
+        }
+        open.push(child.name);
+        visit(child, by_name, open);
+        open.pop();
+    }
+}
@@ -0,0 +1,48 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Bottom-up trees, with the nodes that spend time themselves at the top.
+
+use std::rc::Rc;
+
+use ProfileNode;
+
+/// Build the bottom-up tree of the tree rooted at `root`.
+///
+/// The children of the returned root are the names of every node below `root`, with the self
+/// time of all the nodes of that name as their total. The children of each of those are the
+/// names of the nodes they were called from, with the part of that self time spent when called
+/// from there, and so on up to `root`. Calls count the calls of the nodes at the top.
+///
+/// This is often the quickest way to see where the time of a hot leaf comes from. The result is
+/// an ordinary tree, so it can be printed with `report::write_tables` or exported through
+/// `ProfileSnapshot::of`. Includes the children of mounted profilers.
+pub fn invert(root: &ProfileNode) -> Rc<ProfileNode> {
+    let inverted = Rc::new(ProfileNode::new(None, root.name));
+    *inverted.label.borrow_mut() = Some(format!("{} (bottom-up)", root.display_name()));
+    inverted.calls.set(root.calls.get());
+    let mut stack = vec![root.name];
+    visit(root, &inverted, &mut stack);
+    inverted
+}
+
+// `stack` holds the names from `root` down to `node`.
+fn visit(node: &ProfileNode, inverted: &Rc<ProfileNode>, stack: &mut Vec<&'static str>) {
+    for child in &node.all_children() {
+        stack.push(child.name);
+        let self_time = child.self_time().as_nanos() as u64;
+        let calls = child.calls.get();
+        inverted.total_time.set(inverted.total_time.get() + self_time);
+        let mut parent = inverted.clone();
+        for &name in stack.iter().rev() {
+            let next = parent.make_child(parent.clone(), name);
+            next.total_time.set(next.total_time.get() + self_time);
+            next.calls.set(next.calls.get() + calls);
+            parent = next;
+        }
+        visit(child, inverted, stack);
+        stack.pop();
+    }
+}
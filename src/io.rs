@@ -0,0 +1,176 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Instrumented readers and writers.
+//!
+//! `ProfiledReader` and `ProfiledWriter` wrap any `Read` or `Write`. Each call is recorded in the
+//! thread-local profiler as a child of the current node, and the bytes it transferred are added
+//! to that child with `Profiler::add_bytes`, so asset loading and serialization show up in the
+//! frame tree with their sizes:
+//!
+//! ```ignore
+//! let mut file = ProfiledReader::named("load level", File::open(path)?);
+//! file.read_to_end(&mut data)?;
+//! ```
+//!
+//! Wrap the unbuffered stream, and put a `BufReader` or `BufWriter` around the wrapper, to record
+//! only the calls that actually do IO rather than every small buffered read or write.
+
+use std::io::{self, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
+
+/// Default name of the node reads are recorded under.
+pub const READ: &str = "read";
+
+/// Default name of the node writes are recorded under.
+pub const WRITE: &str = "write";
+
+/// A reader whose calls are profiled.
+#[derive(Debug)]
+pub struct ProfiledReader<R> {
+    name: &'static str,
+    inner: R,
+    // bytes read over the life of the reader
+    bytes: u64,
+}
+
+impl<R> ProfiledReader<R> {
+    /// Wrap `inner`, recording reads under `"read"`.
+    pub fn new(inner: R) -> ProfiledReader<R> {
+        ProfiledReader::named(READ, inner)
+    }
+
+    /// Wrap `inner`, recording reads under `name`.
+    pub fn named(name: &'static str, inner: R) -> ProfiledReader<R> {
+        ProfiledReader { name, inner, bytes: 0 }
+    }
+
+    /// Bytes read since the reader was created, whether or not the profiler was recording.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Stop profiling, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn record<F: FnOnce(&mut R) -> io::Result<usize>>(&mut self, read: F) -> io::Result<usize> {
+        let _g = ::enter(self.name);
+        let n = read(&mut self.inner)?;
+        self.bytes += n as u64;
+        ::add_bytes(n as u64);
+        Ok(n)
+    }
+}
+
+impl<R: Read> Read for ProfiledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.record(|r| r.read(buf))
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        self.record(|r| r.read_vectored(bufs))
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.record(|r| r.read_to_end(buf))
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        self.record(|r| r.read_to_string(buf))
+    }
+}
+
+impl<R: Seek> Seek for ProfiledReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let _g = ::enter(self.name);
+        self.inner.seek(pos)
+    }
+}
+
+/// A writer whose calls are profiled.
+#[derive(Debug)]
+pub struct ProfiledWriter<W> {
+    name: &'static str,
+    inner: W,
+    // bytes written over the life of the writer
+    bytes: u64,
+}
+
+impl<W> ProfiledWriter<W> {
+    /// Wrap `inner`, recording writes under `"write"`.
+    pub fn new(inner: W) -> ProfiledWriter<W> {
+        ProfiledWriter::named(WRITE, inner)
+    }
+
+    /// Wrap `inner`, recording writes and flushes under `name`.
+    pub fn named(name: &'static str, inner: W) -> ProfiledWriter<W> {
+        ProfiledWriter { name, inner, bytes: 0 }
+    }
+
+    /// Bytes written since the writer was created, whether or not the profiler was recording.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Stop profiling, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ProfiledWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _g = ::enter(self.name);
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        ::add_bytes(n as u64);
+        Ok(n)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        let _g = ::enter(self.name);
+        let n = self.inner.write_vectored(bufs)?;
+        self.bytes += n as u64;
+        ::add_bytes(n as u64);
+        Ok(n)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let _g = ::enter(self.name);
+        self.inner.write_all(buf)?;
+        self.bytes += buf.len() as u64;
+        ::add_bytes(buf.len() as u64);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _g = ::enter(self.name);
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for ProfiledWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let _g = ::enter(self.name);
+        self.inner.seek(pos)
+    }
+}
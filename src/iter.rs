@@ -0,0 +1,76 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Profiled iterators.
+//!
+//! Iterator chains do their work inside `next`, so there is often no block to wrap in a scope.
+//! The `ProfiledIterator` extension trait adds `profiled`, which records the time spent in every
+//! call to `next` under a child of the current node in the thread-local profiler:
+//!
+//! ```ignore
+//! use hprof::iter::ProfiledIterator;
+//!
+//! for entity in entities.iter_mut().map(update).profiled("entity update") {
+//!     render(entity);
+//! }
+//! ```
+//!
+//! Here the time of `update` is recorded under `"entity update"`, with a call per item, but not
+//! the time of `render`.
+
+use std::iter::FusedIterator;
+
+/// Adds `profiled` to every iterator.
+pub trait ProfiledIterator: Iterator + Sized {
+    /// Record the time spent in each call to `next` under `name`.
+    fn profiled(self, name: &'static str) -> Profiled<Self> {
+        Profiled { inner: self, name }
+    }
+}
+
+impl<I: Iterator> ProfiledIterator for I {}
+
+/// An iterator whose calls to `next` are profiled, as returned by `ProfiledIterator::profiled`.
+#[derive(Clone, Debug)]
+pub struct Profiled<I> {
+    inner: I,
+    name: &'static str,
+}
+
+impl<I> Profiled<I> {
+    /// The name calls to `next` are recorded under.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Stop profiling, returning the underlying iterator.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Iterator> Iterator for Profiled<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let _g = ::enter(self.name);
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for Profiled<I> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        let _g = ::enter(self.name);
+        self.inner.next_back()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for Profiled<I> {}
+
+impl<I: FusedIterator> FusedIterator for Profiled<I> {}
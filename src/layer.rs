@@ -0,0 +1,119 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Recording `tracing` spans as profile nodes.
+//!
+//! Requires the `tracing` feature.
+
+use std::fmt;
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::{span, Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use names::intern;
+
+/// A `tracing_subscriber` layer that enters a node of the thread-local profiler whenever a span
+/// is entered, and leaves it when the span is exited.
+///
+/// Spans created by dependencies then show up inside the frame tree, under whichever scope was
+/// current, named after the span:
+///
+/// ```ignore
+/// use tracing_subscriber::prelude::*;
+///
+/// tracing_subscriber::registry().with(hprof::layer::HprofLayer::new()).init();
+/// ```
+///
+/// Spans are entered and exited on the same thread, so they always nest properly within the
+/// thread's profiler, though an async task's span is entered and left again every time the task
+/// is polled.
+#[derive(Clone, Debug, Default)]
+pub struct HprofLayer {
+    target: Option<&'static str>,
+    profiling: bool,
+}
+
+impl HprofLayer {
+    /// Record every span.
+    pub fn new() -> HprofLayer {
+        HprofLayer { target: None, profiling: false }
+    }
+
+    /// Record only the spans whose target starts with `prefix`, such as the name of one crate.
+    pub fn with_target(prefix: &'static str) -> HprofLayer {
+        HprofLayer { target: Some(prefix), profiling: false }
+    }
+
+    /// Record every span, and act as the backend of the `profiling` crate when it is built with
+    /// its `profile-with-tracing` feature.
+    ///
+    /// Scopes from `profiling::scope!` become nodes as usual. Scopes from
+    /// `profiling::function_scope!` are named after their function rather than
+    /// "function_scope", and `profiling::finish_frame!` ends the profiler's frame, if any, and
+    /// starts the next one.
+    pub fn for_profiling() -> HprofLayer {
+        HprofLayer { target: None, profiling: true }
+    }
+
+    fn wants(&self, target: &str) -> bool {
+        self.target.map(|prefix| target.starts_with(prefix)).unwrap_or(true)
+    }
+}
+
+// The name given to a span by `profiling::function_scope!`.
+struct FunctionName(&'static str);
+
+// Finds the message of a span or event.
+struct Message(Option<String>);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for HprofLayer {
+    fn on_new_span(&self, attrs: &span::Attributes, id: &span::Id, ctx: Context<S>) {
+        if !self.profiling || attrs.metadata().name() != "function_scope" {
+            return
+        }
+        let mut message = Message(None);
+        attrs.record(&mut message);
+        if let (Some(message), Some(span)) = (message.0, ctx.span(id)) {
+            span.extensions_mut().insert(FunctionName(intern(message)));
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<S>) {
+        if let Some(span) = ctx.span(id) {
+            if self.wants(span.metadata().target()) {
+                let name = span.extensions().get::<FunctionName>().map(|f| f.0).unwrap_or(span.metadata().name());
+                ::profiler().enter_noguard(name);
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<S>) {
+        if let Some(metadata) = ctx.metadata(id) {
+            if self.wants(metadata.target()) {
+                ::profiler().leave();
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event, _ctx: Context<S>) {
+        if self.profiling && event.metadata().fields().field("tracy.frame_mark").is_some() {
+            let profiler = ::profiler();
+            if profiler.in_frame.get() {
+                profiler.end_frame();
+            }
+            profiler.start_frame();
+        }
+    }
+}
@@ -53,28 +53,314 @@
 //!
 //! Use `Profiler::new()` and pass it around/store it somewhere (for example, using
 //! [`current`](https://github.com/PistonDevelopers/current)).
+//!
+//! # Macros
+//!
+//! `scope!("name")` enters a node of the implicit profiler, and `scope!(profiler, "name")` one of
+//! an explicit profiler. Either way the node also remembers the file, line and module it was
+//! entered from, which shows up in snapshots and exports.
 
 #[macro_use]
 extern crate log;
 extern crate clock_ticks;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "crossbeam-channel")]
+extern crate crossbeam_channel;
+#[cfg(feature = "futures-core")]
+extern crate futures_core;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
+#[cfg(feature = "parquet")]
+extern crate parquet;
+#[cfg(feature = "quanta")]
+extern crate quanta;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "criterion")]
+extern crate criterion;
+#[cfg(feature = "itt")]
+extern crate ittapi;
+#[cfg(feature = "nvtx")]
+#[macro_use]
+extern crate nvtx;
+#[cfg(feature = "superluminal")]
+extern crate superluminal_perf;
+#[cfg(feature = "tracing")]
+extern crate tracing_core;
+#[cfg(feature = "tracing")]
+extern crate tracing_subscriber;
+
+pub mod aggregation;
+#[cfg(feature = "criterion")]
+pub mod bench;
+pub mod builder;
+pub mod butterfly;
+pub mod category;
+pub mod channel;
+pub mod clock;
+pub mod compare;
+pub mod delta;
+pub mod export;
+mod flat;
+pub mod handle;
+pub mod histogram;
+pub mod inverted;
+pub mod io;
+pub mod iter;
+pub mod hook;
+pub mod hotspots;
+#[cfg(feature = "tracing")]
+pub mod layer;
+mod json;
+pub mod location;
+pub mod names;
+mod memory;
+mod published;
+pub mod recorder;
+pub mod recording;
+mod registry;
+pub mod report;
+pub mod sample;
+pub mod sink;
+mod slowest;
+pub mod snapshot;
+#[cfg(feature = "futures-core")]
+pub mod stream;
+pub mod summary;
+pub mod sync;
+pub mod timeline;
+pub mod tsv;
+pub mod watchdog;
 
+pub use aggregation::Aggregation;
+pub use builder::ProfilerBuilder;
+pub use butterfly::Butterfly;
+pub use category::{Category, Color};
+pub use clock::{Clock, Overhead};
+pub use compare::{CompareOptions, Comparison};
+pub use flat::FlatStats;
+pub use handle::ProfilerHandle;
+pub use histogram::Histogram;
+pub use hook::ScopeHook;
+pub use hotspots::Hotspot;
+pub use location::Location;
+pub use memory::MemoryStats;
+pub use published::FrameReader;
+pub use recorder::FlightRecorder;
+pub use recording::{PathStats, Recording};
+pub use registry::{collect_threads, remove_finished_threads, request_thread_snapshots, thread_snapshots,
+                   ThreadSnapshot};
+pub use report::{OverlayRow, PrintOptions, SortOrder, Units};
+pub use sample::Reservoir;
+pub use sink::{FrameInfo, Sink};
+pub use slowest::SlowCall;
+pub use snapshot::{ProfileSnapshot, SnapshotNode};
+pub use summary::{Anomaly, BudgetViolation, FrameSummary, NodeStats};
+pub use timeline::{EventKind, TimelineEvent};
+pub use watchdog::Overrun;
+
+use flat::FlatProfile;
+use published::FramePublisher;
+use registry::Slot;
+use slowest::SlowestCalls;
+
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
-use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Enter a profile node, recording where in the source it was entered.
+///
+/// `scope!("name")` uses the implicit profiler and `scope!(profiler, "name")` an explicit one.
+/// Returns a `ProfileGuard`.
+#[macro_export]
+macro_rules! scope {
+    ($name:expr) => (
+        $crate::enter_at($name, $crate::Location { file: file!(), line: line!(), module: module_path!() })
+    );
+    ($profiler:expr, $name:expr) => (
+        $profiler.enter_at($name, $crate::Location { file: file!(), line: line!(), module: module_path!() })
+    );
+}
+
+/// Enter a profile node of the implicit profiler named with `format!`, such as
+/// `scope_fmt!("chunk {}", id)`, recording where in the source it was entered.
+///
+/// Each callsite caches the names it formatted for the last few distinct values of its
+/// arguments, so a name that comes up every frame is only formatted and allocated the first
+/// time. The arguments must implement `Hash`, and are evaluated a second time when the name
+/// isn't cached, so they should be cheap expressions without side effects, such as variables.
+/// Every distinct name is kept for the rest of the program, as nodes need `&'static str` names.
+/// Returns a `ProfileGuard`.
+#[macro_export]
+macro_rules! scope_fmt {
+    ($fmt:expr, $($arg:expr),+ $(,)*) => ({
+        thread_local!(static NAMES: $crate::names::NameCache = $crate::names::NameCache::new());
+        let name = NAMES.with(|names| names.get(&($(&$arg,)+), || format!($fmt, $($arg),+)));
+        $crate::enter_at(name, $crate::Location { file: file!(), line: line!(), module: module_path!() })
+    });
+}
+
+/// Set up the calling thread's implicit profiler as a worker, in one line at the top of the
+/// worker's main function.
+///
+/// `register_worker!("job worker {}", idx)` formats the name like `format!`, then shows it in
+/// place of the root's name, keeps it as the `thread` metadata key and registers the profiler
+/// under it, so that the trees of every worker can be gathered with `collect_threads`.
+#[macro_export]
+macro_rules! register_worker {
+    ($($arg:tt)*) => (
+        $crate::register_worker(format!($($arg)*))
+    );
+}
+
+/// Metadata key holding the label set with `Profiler::set_run_label`.
+pub const RUN_LABEL: &str = "run";
+
+/// Metadata key holding the name given to `register_worker!`.
+pub const THREAD_NAME: &str = "thread";
+
+/// Name of the child that scopes are recorded under once a node has as many children as
+/// `Profiler::set_max_children` allows.
+pub const OTHER: &str = "(other)";
+
+/// Name of the root that scopes entered outside of any frame are recorded under.
+pub const OUT_OF_FRAME: &str = "out of frame";
 
 thread_local!(static HPROF: Profiler = Profiler::new("root profiler"));
 
+// A callback for frames going over budget, as given to `Profiler::set_watchdog`.
+type Watchdog = Box<dyn FnMut(&Overrun)>;
+
 /// A single tree of profile data.
 pub struct Profiler {
     root: Rc<ProfileNode>,
+    // root of the scopes entered between frames, which accumulate until cleared
+    outside: Rc<ProfileNode>,
     current: RefCell<Rc<ProfileNode>>,
     enabled: Cell<bool>,
+    clock: Rc<dyn Clock>,
+    overhead: Cell<Option<Overhead>>,
+    compensate: Cell<bool>,
+    smoothing: Cell<Option<f64>>,
+    // threshold in ns and number of frames under it after which nodes are collapsed
+    collapse: Cell<Option<(u64, u32)>>,
+    // number of scopes entered inside a collapsed node and not left yet, which aren't recorded
+    collapsed_depth: Cell<u32>,
+    // most children a node may have besides the `OTHER` child
+    max_children: Cell<Option<usize>>,
+    // whether a frame is being recorded, and whether `enabled` changed during it
+    in_frame: Cell<bool>,
+    toggled: Cell<bool>,
+    budget: Cell<Option<u64>>,
+    // budgets in ns of individual nodes by path, applied to the nodes as they are created
+    node_budgets: RefCell<Vec<(String, u64)>>,
+    // colors of individual nodes by path, applied like budgets
+    node_colors: RefCell<Vec<(String, Color)>>,
+    // called when the frame in progress goes over budget, at most once per frame
+    watchdog: RefCell<Option<Watchdog>>,
+    watchdog_fired: Cell<bool>,
+    // preallocated nodes, handed out by `new_child`
+    spare: RefCell<Vec<Rc<ProfileNode>>>,
+    recorder: RefCell<Option<FlightRecorder>>,
+    publisher: RefCell<Option<FramePublisher>>,
+    sinks: RefCell<Vec<Box<dyn Sink>>>,
+    // this profiler's slot in the registry of threads, if registered
+    registration: RefCell<Option<Arc<Slot>>>,
+    hooks: RefCell<Vec<Box<dyn ScopeHook>>>,
+    // the slowest calls of the session, if kept
+    slowest: RefCell<Option<SlowestCalls>>,
+    // where to write the session summary when the profiler is dropped
+    on_drop: RefCell<Option<Box<dyn Write>>>,
+    metadata: RefCell<BTreeMap<String, String>>,
+    // number of frames completed so far
+    frame_index: Cell<u64>,
+    // wall-clock times at which the current or last frame started, and the last frame ended
+    frame_wall_time: Cell<SystemTime>,
+    frame_end_wall_time: Cell<SystemTime>,
+    timeline: Cell<bool>,
+    // whether timeline events record the CPU core they happened on
+    cpu_ids: Cell<bool>,
+    // whether nodes are told apart by callsite as well as name
+    callsites: Cell<bool>,
+    // whether entering the current node's name again counts as recursion
+    fold_recursion: Cell<bool>,
+    // whether the thread's CPU time is measured along with wall time
+    cpu_time: Cell<bool>,
+    // whether scopes are only summed up by name, without building a tree
+    flat: Cell<bool>,
+    flat_profile: RefCell<FlatProfile>,
+    aggregation: Cell<Aggregation>,
+    // timeline events of the current frame
+    events: RefCell<Vec<TimelineEvent>>,
+    // tags of the current frame
+    tags: RefCell<Vec<(String, String)>>,
+    // roots of the frame kinds started with `start_frame_of`
+    frame_roots: RefCell<Vec<Rc<ProfileNode>>>,
+    // nodes that were current when each pending `start_frame_of` was called, and when it was
+    suspended: RefCell<Vec<(Rc<ProfileNode>, SystemTime)>>,
+    // number of live `PauseGuard`s, and when the outermost was created, in wall and CPU time
+    paused: Cell<u32>,
+    pause_start: Cell<u64>,
+    pause_cpu_start: Cell<Option<u64>>,
+    // scopes entered while paused whose leaves are still to come, and are to be ignored
+    paused_depth: Cell<u32>,
+}
+
+// What a node is marked with as it is entered, by `enter_colored`, `enter_in` and `enter_blocking`.
+#[derive(Clone, Copy)]
+enum Mark {
+    None,
+    Color(Color),
+    Category(Category),
+    Blocking,
 }
 
 /// A "guard" for calling `Profiler::leave` when it is destroyed.
-pub struct ProfileGuard<'a>(&'a Profiler);
+///
+/// Guards returned by `enter_if` with a false condition are inert, and do nothing.
+pub struct ProfileGuard<'a>(Option<&'a Profiler>);
+impl<'a> ProfileGuard<'a> {
+    /// Whether dropping the guard will `leave` a node.
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+}
 impl<'a> Drop for ProfileGuard<'a> {
     fn drop(&mut self) {
-        self.0.leave()
+        if let Some(profiler) = self.0 {
+            profiler.leave()
+        }
+    }
+}
+
+/// A "guard" for calling `Profiler::end_frame` when it is destroyed.
+pub struct FrameGuard<'a> {
+    profiler: &'a Profiler,
+    kind: Option<&'static str>,
+}
+impl<'a> Drop for FrameGuard<'a> {
+    fn drop(&mut self) {
+        match self.kind {
+            Some(kind) => self.profiler.end_frame_of(kind),
+            None => self.profiler.end_frame(),
+        }
+    }
+}
+
+/// A "guard" for resuming a paused `Profiler` when it is destroyed.
+pub struct PauseGuard<'a> {
+    profiler: &'a Profiler,
+}
+impl<'a> Drop for PauseGuard<'a> {
+    fn drop(&mut self) {
+        self.profiler.resume()
     }
 }
 
@@ -85,249 +371,2377 @@ macro_rules! early_leave {
 impl Profiler {
     /// Create a new profiler with the given name for the root node.
     pub fn new(name: &'static str) -> Profiler {
+        Profiler::with_aggregation(name, Aggregation::Sum)
+    }
+
+    /// Configure a new profiler, for example
+    /// `Profiler::builder().name("game").budget(Duration::from_micros(16_667)).capacity(1024).build()`.
+    pub fn builder() -> ProfilerBuilder {
+        ProfilerBuilder::new()
+    }
+
+    /// Create a new profiler that keeps statistics about individual calls according to
+    /// `aggregation`.
+    ///
+    /// Totals alone can't tell a scope that always takes 1ms from one that usually takes 0.1ms
+    /// but occasionally 8ms; the other strategies can, at some cost per call.
+    pub fn with_aggregation(name: &'static str, aggregation: Aggregation) -> Profiler {
+        let clock = Rc::new(clock::DefaultClock::default());
         let root = Rc::new(ProfileNode::new(None, name));
-        root.call();
-        Profiler { root: root.clone(), current: RefCell::new(root), enabled: Cell::new(true) }
+        root.call_at(clock.now());
+        Profiler {
+            root: root.clone(),
+            outside: Rc::new(ProfileNode::new(None, OUT_OF_FRAME)),
+            current: RefCell::new(root),
+            enabled: Cell::new(true),
+            clock,
+            overhead: Cell::new(None),
+            compensate: Cell::new(false),
+            smoothing: Cell::new(None),
+            collapse: Cell::new(None),
+            collapsed_depth: Cell::new(0),
+            max_children: Cell::new(None),
+            in_frame: Cell::new(false),
+            toggled: Cell::new(false),
+            budget: Cell::new(None),
+            node_budgets: RefCell::new(Vec::new()),
+            node_colors: RefCell::new(Vec::new()),
+            watchdog: RefCell::new(None),
+            watchdog_fired: Cell::new(false),
+            spare: RefCell::new(Vec::new()),
+            recorder: RefCell::new(None),
+            publisher: RefCell::new(None),
+            sinks: RefCell::new(Vec::new()),
+            registration: RefCell::new(None),
+            hooks: RefCell::new(Vec::new()),
+            slowest: RefCell::new(None),
+            on_drop: RefCell::new(None),
+            metadata: RefCell::new(BTreeMap::new()),
+            frame_index: Cell::new(0),
+            frame_wall_time: Cell::new(UNIX_EPOCH),
+            frame_end_wall_time: Cell::new(UNIX_EPOCH),
+            timeline: Cell::new(false),
+            cpu_ids: Cell::new(false),
+            callsites: Cell::new(false),
+            fold_recursion: Cell::new(true),
+            cpu_time: Cell::new(false),
+            flat: Cell::new(false),
+            flat_profile: RefCell::new(FlatProfile::new()),
+            aggregation: Cell::new(aggregation),
+            events: RefCell::new(Vec::new()),
+            tags: RefCell::new(Vec::new()),
+            frame_roots: RefCell::new(Vec::new()),
+            suspended: RefCell::new(Vec::new()),
+            paused: Cell::new(0),
+            pause_start: Cell::new(0),
+            pause_cpu_start: Cell::new(None),
+            paused_depth: Cell::new(0),
+        }
+    }
+
+    /// Replace the clock used for timestamps, `clock::DefaultClock` unless replaced.
+    ///
+    /// Timestamps from different clocks can't be compared, so this should be done before the
+    /// first frame. The timing data of any frame in progress will be garbage.
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.replace_clock(Rc::new(clock));
+    }
+
+    fn replace_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.root.start_time.set(clock.now());
+        self.clock = clock;
     }
 
     /// Enter a profile node for `name`, returning a guard object that will `leave` on destruction.
-    pub fn enter(&self, name: &'static str) -> ProfileGuard {
+    pub fn enter(&self, name: &'static str) -> ProfileGuard<'_> {
+        self.enter_noguard(name);
+        ProfileGuard(Some(self))
+    }
+
+    /// Enter a profile node for `name`, recording `location` as where it was entered from.
+    ///
+    /// Usually called through the `scope!` macro. A node keeps the first location it was entered
+    /// from.
+    pub fn enter_at(&self, name: &'static str, location: Location) -> ProfileGuard<'_> {
+        self.enter_node(name, Some(location), false);
+        ProfileGuard(Some(self))
+    }
+
+    /// Enter a profile node for `name`, counting it as a recursive call if the current node has
+    /// the same name, even if recursion folding is disabled with `set_fold_recursion`.
+    pub fn enter_recursive(&self, name: &'static str) -> ProfileGuard<'_> {
+        self.enter_node(name, None, true);
+        ProfileGuard(Some(self))
+    }
+
+    /// Enter a profile node for `name`, drawing it in `color` in exports and hooks.
+    ///
+    /// The color sticks to the node, and overrides that of its category. See `set_color` to
+    /// color a path without passing the color to every `enter`.
+    pub fn enter_colored(&self, name: &'static str, color: Color) -> ProfileGuard<'_> {
+        self.enter_node_marked(name, None, false, Mark::Color(color));
+        ProfileGuard(Some(self))
+    }
+
+    /// Enter a profile node for `name`, marking it as time spent waiting rather than working,
+    /// such as `enter_blocking("io wait")`.
+    ///
+    /// All of the node's time counts as blocked, whether or not CPU time is measured. Reports
+    /// and exports set blocked time apart from the rest. See `ProfileNode::blocked_time`.
+    pub fn enter_blocking(&self, name: &'static str) -> ProfileGuard<'_> {
+        self.enter_node_marked(name, None, false, Mark::Blocking);
+        ProfileGuard(Some(self))
+    }
+
+    /// Enter a profile node for `name`, attaching the text produced by `payload` to this call in
+    /// timeline mode, such as `enter_with("think", || format!("entity #{}", id))`.
+    ///
+    /// `payload` is only called when timeline events are being recorded, so formatting costs
+    /// nothing otherwise. The text is kept with the enter event in snapshots, recordings and
+    /// timeline exports.
+    pub fn enter_with<F, D>(&self, name: &'static str, payload: F) -> ProfileGuard<'_>
+        where F: FnOnce() -> D, D: std::fmt::Display
+    {
         self.enter_noguard(name);
-        ProfileGuard(self)
+        if self.timeline.get() && self.entered() {
+            if let Some(event) = self.events.borrow_mut().last_mut() {
+                event.payload = Some(payload().to_string());
+            }
+        }
+        ProfileGuard(Some(self))
+    }
+
+    /// Enter a profile node for `name` only if `cond` is true.
+    ///
+    /// Otherwise nothing is entered and the guard returned is inert, so detailed scopes can be
+    /// turned on for just the entity being debugged without branching at the call site.
+    pub fn enter_if(&self, cond: bool, name: &'static str) -> ProfileGuard<'_> {
+        if cond {
+            self.enter(name)
+        } else {
+            ProfileGuard(None)
+        }
+    }
+
+    /// Enter a profile node for `name`, assigning it to `category`.
+    ///
+    /// The category sticks to the node, so it only needs to be given the first time around,
+    /// though passing it every time is harmless.
+    pub fn enter_in(&self, name: &'static str, category: Category) -> ProfileGuard<'_> {
+        self.enter_node_marked(name, None, false, Mark::Category(category));
+        ProfileGuard(Some(self))
+    }
+
+    /// Attach a note to the node currently entered, explaining for example why it was slow this
+    /// frame.
+    ///
+    /// Notes are shown in the text report and kept in snapshots, recordings and timeline
+    /// exports. They are cleared at the start of each frame.
+    pub fn annotate<S: Into<String>>(&self, note: S) {
+        if self.recording() {
+            self.current.borrow().annotations.borrow_mut().push(note.into());
+        }
+    }
+
+    /// Count `n` bytes as read or written by the node currently entered, as `io::ProfiledReader`
+    /// and `io::ProfiledWriter` do.
+    ///
+    /// Byte counts are shown in the text report and kept in snapshots. They are cleared at the
+    /// start of each frame.
+    pub fn add_bytes(&self, n: u64) {
+        if self.recording() {
+            let current = self.current.borrow();
+            current.bytes.set(current.bytes.get() + n);
+        }
     }
 
     /// Enter a profile node for `name`.
     pub fn enter_noguard(&self, name: &'static str) {
-        early_leave!(self);
-        {
+        self.enter_node(name, None, false)
+    }
+
+    // Enter the node for `name` below the current one. In callsite mode, it must also have been
+    // entered from `location`. If the current node matches, it is entered again when folding
+    // recursion or if `recursive` is set.
+    fn enter_node(&self, name: &'static str, location: Option<Location>, recursive: bool) {
+        self.enter_node_marked(name, location, recursive, Mark::None)
+    }
+
+    // Enter the node for `name` as `enter_node` does, marking it with `mark` before hooks are told
+    // about it.
+    fn enter_node_marked(&self, name: &'static str, location: Option<Location>, recursive: bool, mark: Mark) {
+        if self.enabled.get() && self.is_paused() {
+            self.paused_depth.set(self.paused_depth.get() + 1);
+            return
+        }
+        if self.flat.get() {
+            if self.active() {
+                self.flat_profile.borrow_mut().enter(name, self.clock.now());
+            }
+            return
+        }
+        if !self.recording() {
+            return
+        }
+        if self.collapsed_depth.get() > 0 || self.current.borrow().collapsed.get() {
+            self.collapsed_depth.set(self.collapsed_depth.get() + 1);
+            return
+        }
+        // hooks and the watchdog are only called once `current` is no longer borrowed, so they can
+        // use the profiler
+        let (node, outside) = {
+            let callsites = self.callsites.get();
+            let matches = |node: &ProfileNode| node.name == name && (!callsites || node.location.get() == location);
             let mut curr = self.current.borrow_mut();
-            if curr.name != name {
-                *curr = curr.make_child(curr.clone(), name);
+            let outside = !self.in_frame.get() && Rc::ptr_eq(&curr, &self.root);
+            if outside {
+                *curr = self.outside.clone();
+            }
+            if !((recursive || self.fold_recursion.get()) && matches(&curr)) {
+                let found = curr.children.borrow().iter().find(|c| matches(c)).cloned();
+                *curr = match found {
+                    Some(child) => child,
+                    None => match self.max_children.get() {
+                        Some(max) if curr.children.borrow().len() >= max => self.other_child(&curr),
+                        _ => self.new_child(&curr, name, location),
+                    },
+                };
             }
+            if curr.location.get().is_none() && curr.name != OTHER {
+                curr.location.set(location);
+            }
+            match mark {
+                Mark::None => {}
+                Mark::Color(color) => curr.color.set(Some(color)),
+                Mark::Category(category) => curr.category.set(Some(category)),
+                Mark::Blocking => curr.blocking.set(true),
+            }
+            (curr.clone(), outside)
+        };
+        if outside {
+            // events are only kept for one outermost scope at a time outside of frames, as for
+            // one frame, rather than piling up until the next frame starts
+            self.events.borrow_mut().clear();
+            self.call(&self.outside);
         }
-        self.current.borrow().call();
+        self.call(&node);
+    }
+
+    // Call `f` with every hook. The hooks are taken out of the profiler meanwhile, so a hook can
+    // enter scopes of its own, which no hook is told about, or add hooks, which are kept.
+    fn each_hook<F: FnMut(&mut dyn ScopeHook)>(&self, mut f: F) {
+        let mut hooks = std::mem::take(&mut *self.hooks.borrow_mut());
+        if hooks.is_empty() {
+            return
+        }
+        for hook in &mut hooks {
+            f(&mut **hook);
+        }
+        let mut added = self.hooks.borrow_mut();
+        hooks.append(&mut added);
+        *added = hooks;
+    }
+
+    // Enter `node`, recording an event in timeline mode and telling any hooks.
+    fn call(&self, node: &ProfileNode) {
+        let now = self.clock.now();
+        node.call_at(now);
+        if self.cpu_time.get() && node.recursion.get() == 1 {
+            node.cpu_start.set(clock::thread_cpu_time());
+        }
+        if self.timeline.get() {
+            let event =
+                TimelineEvent { kind: EventKind::Enter, id: node.id, time: now, payload: None, cpu: self.cpu_id() };
+            self.events.borrow_mut().push(event);
+        }
+        self.check_budget(node, now);
+        self.each_hook(|hook| hook.enter(node));
+    }
+
+    // Return from `node`, recording an event in timeline mode and telling any hooks.
+    fn ret(&self, node: &ProfileNode) -> bool {
+        self.each_hook(|hook| hook.leave(node));
+        let now = self.now();
+        if self.timeline.get() {
+            let event =
+                TimelineEvent { kind: EventKind::Leave, id: node.id, time: now, payload: None, cpu: self.cpu_id() };
+            self.events.borrow_mut().push(event);
+        }
+        self.check_budget(node, now);
+        let elapsed = now.saturating_sub(node.start_time.get());
+        if node.recursion.get() == 1 {
+            if let (Some(start), Some(cpu_now)) = (node.cpu_start.take(), self.cpu_now()) {
+                node.cpu_time.set(Some(node.cpu_time.get().unwrap_or(0) + cpu_now.saturating_sub(start)));
+            }
+        }
+        let done = node.ret_at(now);
+        // roots are whole frames, which are better compared with the frame summaries
+        if done && node.parent.is_some() {
+            if let Some(ref mut slowest) = *self.slowest.borrow_mut() {
+                let frame = if self.in_frame.get() {
+                    Some((self.frame_index.get(), self.root.start_time.get()))
+                } else {
+                    None
+                };
+                slowest.record(node, now - elapsed, elapsed, frame);
+            }
+        }
+        if done {
+            match self.aggregation.get() {
+                Aggregation::Sum => {}
+                Aggregation::MinMax => node.record_extremes(elapsed),
+                Aggregation::Histogram => {
+                    node.histogram.borrow_mut().get_or_insert_with(Histogram::new).record(elapsed)
+                }
+                Aggregation::Reservoir(capacity) => node.sample(elapsed, capacity),
+            }
+        }
+        done
+    }
+
+    // Fire the watchdog if the frame went over budget by `now`, while entering or leaving `node`.
+    fn check_budget(&self, node: &ProfileNode, now: u64) {
+        if self.watchdog_fired.get() || !self.in_frame.get() {
+            return
+        }
+        if let Some(budget) = self.budget.get() {
+            let elapsed = now.saturating_sub(self.root.start_time.get());
+            if elapsed > budget {
+                if let Some(ref mut watchdog) = *self.watchdog.borrow_mut() {
+                    self.watchdog_fired.set(true);
+                    watchdog(&Overrun {
+                        elapsed: Duration::from_nanos(elapsed),
+                        budget: Duration::from_nanos(budget),
+                        path: node.path(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Create a child of `parent`, preferably out of the reserved nodes.
+    fn new_child(&self, parent: &Rc<ProfileNode>, name: &'static str, location: Option<Location>) -> Rc<ProfileNode> {
+        let id = match location {
+            Some(location) if self.callsites.get() => callsite_id(parent.id, name, location),
+            _ => node_id(parent.id, name),
+        };
+        let child = match self.spare.borrow_mut().pop() {
+            Some(mut node) => {
+                {
+                    // spare nodes are never shared, so this can't fail
+                    let n = Rc::get_mut(&mut node).unwrap();
+                    n.name = name;
+                    n.id = id;
+                    n.parent = Some(Rc::downgrade(parent));
+                }
+                node
+            }
+            None => {
+                let mut node = ProfileNode::new(Some(parent.clone()), name);
+                node.id = id;
+                Rc::new(node)
+            }
+        };
+        child.location.set(location);
+        parent.children.borrow_mut().push(child.clone());
+        let budgets = self.node_budgets.borrow();
+        let colors = self.node_colors.borrow();
+        if !budgets.is_empty() || !colors.is_empty() {
+            let path = child.path();
+            child.budget.set(budgets.iter().find(|&(p, _)| *p == path).map(|&(_, budget)| budget));
+            child.color.set(colors.iter().find(|&(p, _)| *p == path).map(|&(_, color)| color));
+        }
+        child
+    }
+
+    // The `OTHER` child of `parent`, created if needed.
+    fn other_child(&self, parent: &Rc<ProfileNode>) -> Rc<ProfileNode> {
+        match parent.find_child(OTHER) {
+            Some(child) => child,
+            None => self.new_child(parent, OTHER, None),
+        }
+    }
+
+    /// Allocate storage for `n` more nodes up front.
+    ///
+    /// After warmup, when every scope has been entered at least once, `enter` and `leave` never
+    /// allocate: the tree only grows when a new scope is seen. Reserving nodes moves most of the
+    /// remaining allocation out of the first frames as well, though a node's list of children
+    /// may still grow the first time a new child is added to it.
+    ///
+    /// Keeping a flight recorder allocates until its buffer is full, and
+    /// `end_frame_summary` allocates the path in its summary.
+    pub fn reserve_nodes(&self, n: usize) {
+        let mut spare = self.spare.borrow_mut();
+        spare.reserve(n);
+        for _ in 0..n {
+            spare.push(Rc::new(ProfileNode::new(None, "")));
+        }
+    }
+
+    /// Drop every node in the tree except the roots, along with all of their timing data and the
+    /// slowest calls kept so far.
+    ///
+    /// Useful when a lot of scopes have become garbage, such as per-chunk scopes after leaving a
+    /// level. Nodes are created again as they are entered. Logs an error and does nothing if
+    /// there are pending `leave` calls.
+    pub fn clear(&self) {
+        if !std::ptr::eq(&*self.root, &**self.current.borrow())
+            || !self.suspended.borrow().is_empty() {
+            error!("Pending `leave` calls on Profiler::clear");
+            return
+        }
+        self.root.clear_children();
+        self.outside.clear_children();
+        for root in &*self.frame_roots.borrow() {
+            root.clear_children();
+        }
+        if let Some(ref mut slowest) = *self.slowest.borrow_mut() {
+            slowest.clear();
+        }
+    }
+
+    /// Release any memory that isn't needed to hold the current tree.
+    ///
+    /// This includes nodes set aside by `reserve_nodes`, so it is best followed by another
+    /// warmup.
+    pub fn shrink_to_fit(&self) {
+        *self.spare.borrow_mut() = Vec::new();
+        self.root.shrink_to_fit();
+        let mut roots = self.frame_roots.borrow_mut();
+        for root in &*roots {
+            root.shrink_to_fit();
+        }
+        roots.shrink_to_fit();
+        self.suspended.borrow_mut().shrink_to_fit();
     }
 
     /// Leave the current profile node.
     pub fn leave(&self) {
+        // leaves of scopes entered while paused are ignored, even once resumed, but those of scopes
+        // entered before the pause still leave them
+        if self.paused_depth.get() > 0 {
+            self.paused_depth.set(self.paused_depth.get() - 1);
+            return
+        }
+        if !self.enabled.get() {
+            return
+        }
+        if self.flat.get() {
+            if !self.flat_profile.borrow_mut().leave(self.now()) {
+                error!("Profiler::leave called without a matching enter");
+            }
+            return
+        }
+        if self.collapsed_depth.get() > 0 {
+            self.collapsed_depth.set(self.collapsed_depth.get() - 1);
+            return
+        }
+        // as in `enter_node`, `current` isn't borrowed while hooks and the watchdog are called
+        let node = self.current.borrow().clone();
+        if self.ret(&node) {
+            if let Some(parent) = node.parent() {
+                let outside = Rc::ptr_eq(&parent, &self.outside);
+                *self.current.borrow_mut() = parent;
+                if outside {
+                    self.ret(&self.outside);
+                    *self.current.borrow_mut() = self.root.clone();
+                    self.publish_if_requested();
+                }
+            }
+        }
+    }
+
+    /// Print out the current timing information in a very naive way.
+    pub fn print_timing(&self) {
+        let stdout = std::io::stdout();
+        let _ = self.write_timing(&mut stdout.lock());
+    }
+
+    /// Write out the current timing information in the same format as `print_timing`.
+    pub fn write_timing<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        self.write_timing_with(w, &PrintOptions::default())
+    }
+
+    /// Print out the current timing information, configured by `opts`.
+    pub fn print_timing_with(&self, opts: &PrintOptions) {
+        let stdout = std::io::stdout();
+        let _ = self.write_timing_with(&mut stdout.lock(), opts);
+    }
+
+    /// Write out the current timing information in the same format as `print_timing_with`.
+    pub fn write_timing_with<W: Write>(&self, w: &mut W, opts: &PrintOptions) -> std::io::Result<()> {
+        let roots = self.report_roots();
+        let roots: Vec<&ProfileNode> = roots.iter().map(|r| &**r).collect();
+        if let Some(run) = self.run_label() {
+            writeln!(w, "Run: {}", run)?;
+        }
+        match (opts.sparklines, &*self.recorder.borrow()) {
+            (Some(frames), Some(recorder)) => {
+                let recorded = recorder.frames();
+                let skip = recorded.len().saturating_sub(frames);
+                let history: Vec<&ProfileSnapshot> = recorded.iter().skip(skip).collect();
+                report::write_tables_with_history(w, &roots, &history, 2, opts)
+            }
+            _ => report::write_tables(w, &roots, 2, opts),
+        }
+    }
+
+    // The roots shown in reports: the main one, those of secondary frame kinds, and the one of
+    // scopes entered outside of frames if there were any.
+    fn report_roots(&self) -> Vec<Rc<ProfileNode>> {
+        let mut roots = vec![self.root.clone()];
+        roots.extend(self.frame_roots.borrow().iter().cloned());
+        if !self.outside.children.borrow().is_empty() {
+            roots.push(self.outside.clone());
+        }
+        roots
+    }
+
+    /// Print out the current timing information in the stable tab-separated format described in
+    /// the `tsv` module, for grepping and scripts.
+    pub fn print_tsv(&self) {
+        let stdout = std::io::stdout();
+        let _ = self.write_tsv(&mut stdout.lock());
+    }
+
+    /// Write out the current timing information in the same format as `print_tsv`.
+    pub fn write_tsv<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let roots = self.report_roots();
+        let roots: Vec<&ProfileNode> = roots.iter().map(|r| &**r).collect();
+        tsv::write_tsv(w, &roots)
+    }
+
+    /// Print out the current frame as a Markdown table, ready to paste into an issue. See
+    /// `export::markdown`.
+    pub fn print_markdown(&self) {
+        let stdout = std::io::stdout();
+        let _ = self.write_markdown(&mut stdout.lock());
+    }
+
+    /// Write out the current frame in the same format as `print_markdown`.
+    pub fn write_markdown<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        export::markdown::write_markdown(w, &ProfileSnapshot::of(&self.root))
+    }
+
+    /// Keep the `n` slowest individual calls of the session, or stop keeping them if `n` is 0.
+    ///
+    /// Averages and totals hide the rare call that takes ten times as long as the others; this
+    /// lists those calls with their paths and when they were made. Calls are compared as they
+    /// end, and only those that make the list have their path looked up. Frames themselves are
+    /// not included.
+    pub fn set_slowest_calls(&self, n: usize) {
+        *self.slowest.borrow_mut() = if n > 0 { Some(SlowestCalls::new(n)) } else { None };
+    }
+
+    /// The slowest calls kept since `set_slowest_calls`, slowest first.
+    pub fn slowest_calls(&self) -> Vec<SlowCall> {
+        self.slowest.borrow().as_ref().map(|s| s.calls().to_vec()).unwrap_or_default()
+    }
+
+    /// Print out the calls returned by `slowest_calls`.
+    pub fn print_slowest_calls(&self) {
+        let stdout = std::io::stdout();
+        let _ = self.write_slowest_calls(&mut stdout.lock());
+    }
+
+    /// Write out the slowest calls in the same format as `print_slowest_calls`.
+    pub fn write_slowest_calls<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        writeln!(w, "Slowest calls of {}:", self.root.display_name())?;
+        slowest::write_slowest_calls(w, &self.slowest_calls(), 2)
+    }
+
+    /// Sum the nodes of the current tree by name, sorted by self time. See
+    /// `hotspots::hotspots`.
+    pub fn hotspots(&self) -> Vec<Hotspot> {
+        hotspots::hotspots(&self.root)
+    }
+
+    /// Print out the flat profile returned by `hotspots`.
+    pub fn print_hotspots(&self) {
+        let stdout = std::io::stdout();
+        let _ = self.write_hotspots(&mut stdout.lock());
+    }
+
+    /// Write out the flat profile in the same format as `print_hotspots`.
+    pub fn write_hotspots<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        writeln!(w, "Hot spots of {}:", self.root.display_name())?;
+        report::write_hotspots(w, &self.hotspots(), self.root.total(), 2, &PrintOptions::default())
+    }
+
+    /// Build the bottom-up tree of the current frame. See `inverted::invert`.
+    pub fn inverted(&self) -> Rc<ProfileNode> {
+        inverted::invert(&self.root)
+    }
+
+    /// Print out the bottom-up tree returned by `inverted`, longest first.
+    pub fn print_inverted(&self) {
+        let stdout = std::io::stdout();
+        let opts = PrintOptions { sort: SortOrder::Total, ..PrintOptions::default() };
+        let _ = self.write_inverted_with(&mut stdout.lock(), &opts);
+    }
+
+    /// Write out the bottom-up tree in the same format as `print_timing_with`.
+    pub fn write_inverted_with<W: Write>(&self, w: &mut W, opts: &PrintOptions) -> std::io::Result<()> {
+        report::write_tables(w, &[&self.inverted()], 2, opts)
+    }
+
+    /// Split the time of every node named `name` in the current tree by caller and by callee.
+    /// See `butterfly::butterfly`.
+    pub fn butterfly(&self, name: &str) -> Option<Butterfly> {
+        butterfly::butterfly(&self.root, name)
+    }
+
+    /// Print out the breakdown returned by `butterfly`.
+    pub fn print_butterfly(&self, name: &str) {
+        let stdout = std::io::stdout();
+        let _ = self.write_butterfly(&mut stdout.lock(), name);
+    }
+
+    /// Write out the breakdown of `name` in the same format as `print_butterfly`.
+    ///
+    /// Fails with `ErrorKind::NotFound` if there is no node named `name`.
+    pub fn write_butterfly<W: Write>(&self, w: &mut W, name: &str) -> std::io::Result<()> {
+        let butterfly = match self.butterfly(name) {
+            Some(butterfly) => butterfly,
+            None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("no node named {:?}", name))),
+        };
+        writeln!(w, "Callers and callees of {}:", name)?;
+        butterfly::write_butterfly(w, &butterfly, 2)
+    }
+
+    /// Lay out the last frame as rows of text for a game's own debug overlay, one per node, so
+    /// that drawing it only takes a loop over the rows.
+    ///
+    /// See `overlay_into` to reuse the rows of the previous frame.
+    pub fn overlay(&self, opts: &PrintOptions) -> Vec<OverlayRow> {
+        let mut rows = Vec::new();
+        self.overlay_into(&mut rows, opts);
+        rows
+    }
+
+    /// Replace the contents of `rows` with the layout returned by `overlay`.
+    pub fn overlay_into(&self, rows: &mut Vec<OverlayRow>, opts: &PrintOptions) {
+        rows.clear();
+        report::overlay_rows(rows, &self.root, opts);
+    }
+
+    /// Print out the timing information of the node at the `/`-separated `path` and all of its
+    /// descendants, such as `print_subtree("Physics")`.
+    pub fn print_subtree(&self, path: &str) {
+        let stdout = std::io::stdout();
+        if let Err(e) = self.write_subtree(&mut stdout.lock(), path) {
+            error!("Failed to print the subtree at {:?}: {}", path, e);
+        }
+    }
+
+    /// Write out the timing information of the node at `path` in the same format as
+    /// `print_subtree`.
+    ///
+    /// Fails with `ErrorKind::NotFound` if there is no node at `path`.
+    pub fn write_subtree<W: Write>(&self, w: &mut W, path: &str) -> std::io::Result<()> {
+        let node = match self.root.find_path(path) {
+            Some(node) => node,
+            None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("no node at {:?}", path))),
+        };
+        writeln!(w, "Timing information for {}:", path)?;
+        report::write_node(w, &node, 2, &PrintOptions::default())
+    }
+
+    /// Write a summary of the whole session so far to `w`: the lifetime totals of every node,
+    /// with their averages per call and per frame.
+    pub fn write_session_summary<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let roots = self.report_roots();
+        // scopes outside of frames don't have any frames to average over
+        let roots: Vec<(&ProfileNode, Option<u64>)> = roots.iter().map(|r| {
+            (&**r, if Rc::ptr_eq(r, &self.outside) { None } else { Some(r.lifetime_frames()) })
+        }).collect();
+        if let Some(run) = self.run_label() {
+            writeln!(w, "Run: {}", run)?;
+        }
+        report::write_session(w, &roots, 2, &PrintOptions::default())
+    }
+
+    /// Write the session summary to `w` when the profiler is dropped, for batch tools that
+    /// would rather not remember to print a report at the end.
+    ///
+    /// Replaces any writer set before. Errors are logged.
+    pub fn report_on_drop<W: Write + 'static>(&self, w: W) {
+        *self.on_drop.borrow_mut() = Some(Box::new(w));
+    }
+
+    /// Write the session summary now, to the writer given to `report_on_drop` or else to
+    /// stdout, instead of when the profiler is dropped.
+    pub fn finish(&self) -> std::io::Result<()> {
+        match self.on_drop.borrow_mut().take() {
+            Some(mut w) => {
+                self.write_session_summary(&mut w)?;
+                w.flush()
+            }
+            None => {
+                let stdout = std::io::stdout();
+                self.write_session_summary(&mut stdout.lock())
+            }
+        }
+    }
+
+    /// The `/`-separated path of the node currently entered, not including the root.
+    pub fn current_path(&self) -> String {
+        self.current.borrow().path()
+    }
+
+    // Called from within a panic, so avoid panicking again if `current` is borrowed.
+    fn write_panic_dump<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        match self.current.try_borrow() {
+            Ok(current) => writeln!(w, "hprof: panicked in `{}` of {}", current.path(), self.root.display_name())?,
+            Err(_) => return writeln!(w, "hprof: panicked inside the profiler of {}", self.root.display_name()),
+        }
+        self.write_timing(w)
+    }
+
+    /// Return the root profile node for inspection.
+    ///
+    /// This root will always be valid and reflect the current state of the `Profiler`.
+    /// It is not advised to inspect the data between calls to `start_frame` and `end_frame`.
+    pub fn root(&self) -> Rc<ProfileNode> {
+        self.root.clone()
+    }
+
+    /// Graft the tree of `sub` under the node at `path`, creating the node if needed.
+    ///
+    /// The children of `sub`'s root are reported as children of that node, so a library can keep
+    /// its own `Profiler` and still show up in the right place in the application's reports,
+    /// snapshots and sinks. `sub` keeps its own frames: its nodes hold whatever it last measured,
+    /// and are not reset when this profiler starts a frame. The mount is dropped along with the
+    /// node by `clear`.
+    pub fn mount(&self, path: &'static str, sub: &Profiler) {
+        let mut node = self.root.clone();
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            node = node.make_child(node.clone(), name);
+        }
+        node.mounted.borrow_mut().push(sub.root());
+    }
+
+    /// Remove `sub` from wherever it was mounted with `mount`.
+    pub fn unmount(&self, sub: &Profiler) {
+        fn remove(node: &ProfileNode, root: &Rc<ProfileNode>) {
+            node.mounted.borrow_mut().retain(|r| !Rc::ptr_eq(r, root));
+            for child in &*node.children.borrow() {
+                remove(child, root);
+            }
+        }
+        remove(&self.root, &sub.root);
+    }
+
+    /// Return the root of the scopes entered outside of any frame, such as loading screens and
+    /// initialization.
+    ///
+    /// A scope entered while no frame is in progress, and not inside a frame of a secondary kind,
+    /// is recorded under this root instead of being mixed into the last frame's data. Unlike
+    /// frames, these scopes accumulate: the totals cover every gap between frames until
+    /// `reset_out_of_frame` is called. The text report shows them in a table of their own.
+    pub fn out_of_frame_root(&self) -> Rc<ProfileNode> {
+        self.outside.clone()
+    }
+
+    /// Reset the timing data of the scopes entered outside of any frame.
+    ///
+    /// Does nothing while such a scope is entered.
+    pub fn reset_out_of_frame(&self) {
+        if self.outside.recursion.get() == 0 {
+            self.outside.reset();
+        }
+    }
+
+    /// Statistics of the node at the `/`-separated `path`, such as `"Render/GPU wait"`, if it
+    /// exists.
+    ///
+    /// Meant to be called between frames, for example by systems that adapt their level of detail
+    /// to how long they took; during a frame the values of the last frame are partly overwritten.
+    pub fn stats(&self, path: &str) -> Option<NodeStats> {
+        self.root.find_path(path).map(|node| NodeStats::of(&node))
+    }
+
+    /// Find the node with the given `id`, if it exists.
+    pub fn find_by_id(&self, id: u64) -> Option<Rc<ProfileNode>> {
+        if self.root.id == id {
+            return Some(self.root.clone())
+        }
+        self.root.find_by_id(id)
+    }
+
+    /// Finish a frame.
+    ///
+    /// Logs an error if there are pending `leave` calls, and later attempts to
+    /// print timing data will be met with sadness in the form of `NaN`s.
+    pub fn end_frame(&self) {
+        self.finish_frame();
+    }
+
+    /// Finish a frame, returning a summary of it.
+    ///
+    /// Returns `None` if the profiler is disabled or there are pending `leave` calls, in which
+    /// case an error is logged as for `end_frame`.
+    pub fn end_frame_summary(&self) -> Option<FrameSummary> {
+        if self.finish_frame() {
+            Some(FrameSummary::of(&self.root, self.budget(), self.toggled.get()))
+        } else {
+            None
+        }
+    }
+
+    // Returns whether the frame was balanced and its timing data is valid.
+    fn finish_frame(&self) -> bool {
+        if !self.enabled.get() {
+            return false
+        }
+        self.in_frame.set(false);
+        self.frame_end_wall_time.set(SystemTime::now());
+        if !std::ptr::eq(&*self.root, &**self.current.borrow()) {
+            error!("Pending `leave` calls on Profiler::frame");
+            false
+        } else {
+            self.ret(&self.root);
+            if let (true, Some(overhead)) = (self.compensate.get(), self.overhead.get()) {
+                overhead.compensate(&self.root);
+            }
+            if let Some(alpha) = self.smoothing.get() {
+                self.root.update_smoothed(alpha);
+            }
+            let events = self.events.borrow();
+            let tags = self.tags.borrow();
+            let info = FrameInfo {
+                index: self.frame_index.get(),
+                start: self.frame_wall_time.get(),
+                timestamp: self.frame_end_wall_time.get(),
+                tags: &tags,
+            };
+            if let Some(ref mut recorder) = *self.recorder.borrow_mut() {
+                recorder.record(&info, &self.root, &events);
+            }
+            if let Some(ref mut publisher) = *self.publisher.borrow_mut() {
+                publisher.publish(&info, &self.root, &events);
+            }
+            for sink in self.sinks.borrow_mut().iter_mut() {
+                if let Err(e) = sink.frame(&info, &self.root) {
+                    error!("Failed to write a frame to a sink: {}", e);
+                }
+            }
+            // after the frame was handed out, so that it still has the children of nodes collapsed now
+            if let Some((threshold, frames)) = self.collapse.get() {
+                for child in &*self.root.children.borrow() {
+                    child.update_collapsed(threshold, frames);
+                }
+            }
+            self.frame_index.set(self.frame_index.get() + 1);
+            drop(events);
+            drop(tags);
+            self.publish_if_requested();
+            true
+        }
+    }
+
+    /// Measure the cost of an `enter`/`leave` pair with this profiler's clock.
+    ///
+    /// The estimate is kept for `set_overhead_compensation`. Takes on the order of a
+    /// millisecond, so do it at startup rather than every frame.
+    pub fn calibrate(&self) -> Overhead {
+        const PAIRS: u64 = 1000;
+        let mut scratch = Profiler::new("calibration");
+        scratch.clock = self.clock.clone();
+        scratch.root.start_time.set(scratch.clock.now());
+        let mut best: Option<Overhead> = None;
+        // the cheapest of several runs is the one least disturbed by everything else going on
+        for _ in 0..10 {
+            scratch.start_frame();
+            for _ in 0..PAIRS {
+                scratch.enter_noguard("scope");
+                scratch.leave();
+            }
+            scratch.end_frame();
+            let run = Overhead {
+                inner: Duration::from_nanos(scratch.root.children.borrow()[0].total_time.get() / PAIRS),
+                outer: Duration::from_nanos(scratch.root.total_time.get() / PAIRS),
+            };
+            if best.map(|b| run.outer < b.outer).unwrap_or(true) {
+                best = Some(run);
+            }
+        }
+        self.overhead.set(best);
+        best.unwrap()
+    }
+
+    /// The estimate made by the last call to `calibrate`, if any.
+    pub fn overhead(&self) -> Option<Overhead> {
+        self.overhead.get()
+    }
+
+    /// Enable or disable subtracting the estimated profiler overhead from node totals.
+    ///
+    /// Trees with tens of thousands of scopes per frame systematically over-report, because each
+    /// node's total includes the cost of profiling everything below it. With compensation on,
+    /// that estimated cost is subtracted from every total at `end_frame`, before the frame is
+    /// recorded or published. Does nothing until `calibrate` has been called.
+    pub fn set_overhead_compensation(&self, enabled: bool) {
+        self.compensate.set(enabled);
+    }
+
+    /// Keep an exponential moving average of each node's total per frame, or stop doing so.
+    ///
+    /// At the end of every frame each average moves a fraction `alpha` (between 0 and 1) of the
+    /// way towards the frame's total, so smaller values give steadier numbers that react more
+    /// slowly. At 60 frames per second, an `alpha` around 0.05 settles within a second or two.
+    /// The text report shows the average next to each total.
+    pub fn set_smoothing(&self, alpha: Option<f64>) {
+        self.smoothing.set(alpha);
+    }
+
+    /// Stop recording the detail of subtrees that take less than `threshold` per frame for
+    /// `frames` frames in a row, or record every subtree again with `None`.
+    ///
+    /// At the end of each frame, a node of the frame tree that has stayed under `threshold` that
+    /// long drops its children and is marked collapsed. Scopes entered below a collapsed node
+    /// are only counted so they can be left, not recorded, so its total still includes their
+    /// time while entering them costs next to nothing. A collapsed node that goes over
+    /// `threshold` is expanded again and records its children from the next frame on. Frames in
+    /// which a node isn't called count as under the threshold. The text report marks collapsed
+    /// nodes.
+    pub fn set_collapsing(&self, threshold: Option<Duration>, frames: u32) {
+        self.collapse.set(threshold.map(|t| (t.as_nanos() as u64, frames)));
+        if threshold.is_none() {
+            self.root.expand();
+        }
+    }
+
+    /// Limit each node to `max` children, or lift the limit with `None`.
+    ///
+    /// Once a node has `max` children, scopes with any other name entered directly below it are
+    /// all recorded under one more child named `"(other)"`, which counts their calls and time
+    /// together. This keeps scopes named per entity or per asset from growing the tree without
+    /// bound, while the totals still add up. Children created before the limit was set are kept.
+    pub fn set_max_children(&self, max: Option<usize>) {
+        self.max_children.set(max);
+    }
+
+    /// Set the time a frame is expected to fit in, or `None` for no budget.
+    pub fn set_budget(&self, budget: Option<Duration>) {
+        self.budget.set(budget.map(|b| b.as_nanos() as u64));
+    }
+
+    /// The time a frame is expected to fit in, if any.
+    pub fn budget(&self) -> Option<Duration> {
+        self.budget.get().map(Duration::from_nanos)
+    }
+
+    /// Set the time the node at the `/`-separated `path` is expected to fit in each frame, such
+    /// as `set_node_budget("Render/GPU wait", Some(Duration::from_millis(4)))`, or `None` to
+    /// remove its budget.
+    ///
+    /// The budget applies to the node at that path whenever it exists, including nodes created
+    /// later. Reports mark nodes that went over their budget, and `budget_violations` lists them
+    /// after each frame.
+    pub fn set_node_budget(&self, path: &str, budget: Option<Duration>) {
+        let path = normalize_path(path);
+        let budget = budget.map(|b| b.as_nanos() as u64);
+        {
+            let mut budgets = self.node_budgets.borrow_mut();
+            budgets.retain(|(p, _)| *p != path);
+            if let Some(budget) = budget {
+                budgets.push((path.clone(), budget));
+            }
+        }
+        for root in self.report_roots() {
+            if let Some(node) = root.find_path(&path) {
+                node.budget.set(budget);
+            }
+        }
+    }
+
+    /// The time the node at `path` is expected to fit in each frame, if it was given a budget.
+    pub fn node_budget(&self, path: &str) -> Option<Duration> {
+        let path = normalize_path(path);
+        self.node_budgets.borrow().iter().find(|&(p, _)| *p == path).map(|&(_, b)| Duration::from_nanos(b))
+    }
+
+    /// Draw the node at the `/`-separated `path` in `color` in exports and hooks, or go back to
+    /// the color of its category with `None`.
+    ///
+    /// Like budgets, colors apply to the node at that path whenever it exists, including nodes
+    /// created later, so a subsystem can keep the same color across tools without passing it
+    /// to every `enter`. Descendants without a color or category of their own inherit it. See
+    /// `enter_colored`.
+    pub fn set_color(&self, path: &str, color: Option<Color>) {
+        let path = normalize_path(path);
+        {
+            let mut colors = self.node_colors.borrow_mut();
+            colors.retain(|(p, _)| *p != path);
+            if let Some(color) = color {
+                colors.push((path.clone(), color));
+            }
+        }
+        for root in self.report_roots() {
+            if let Some(node) = root.find_path(&path) {
+                node.color.set(color);
+            }
+        }
+    }
+
+    /// The nodes that went over their budget in the last frame, in depth-first order.
+    ///
+    /// Meant to be checked after `end_frame`, for example to raise an alert. See
+    /// `set_node_budget`.
+    pub fn budget_violations(&self) -> Vec<BudgetViolation> {
+        self.root.budget_violations()
+    }
+
+    /// Call `watchdog` as soon as a frame goes over budget, rather than after it ends.
+    ///
+    /// The check is made on every `enter` and `leave`, so a frame stuck in a loop can be
+    /// diagnosed before `end_frame` is ever reached, as long as the loop enters some scope. The
+    /// callback gets the path of the scope being entered or left at the time, and is called at
+    /// most once per frame. It must not use the profiler, which is in the middle of an `enter` or
+    /// `leave`. Does nothing without a budget.
+    pub fn set_watchdog<F: FnMut(&Overrun) + 'static>(&self, watchdog: F) {
+        *self.watchdog.borrow_mut() = Some(Box::new(watchdog));
+    }
+
+    /// Stop calling the watchdog set with `set_watchdog`.
+    pub fn clear_watchdog(&self) {
+        *self.watchdog.borrow_mut() = None;
+    }
+
+    /// Start a frame.
+    ///
+    /// Resets timing data. Logs an error if there are pending `leave` calls, but there are
+    /// otherwise no ill effects.
+    pub fn start_frame(&self) {
+        early_leave!(self);
+        if !std::ptr::eq(&*self.root, &**self.current.borrow()) {
+            error!("Pending `leave` calls on Profiler::frame");
+            // abandon any scope entered before the frame
+            self.outside.recursion.set(0);
+        }
+        *self.current.borrow_mut() = self.root.clone();
+        self.collapsed_depth.set(0);
+        self.suspended.borrow_mut().clear();
+        self.events.borrow_mut().clear();
+        self.tags.borrow_mut().clear();
+        self.in_frame.set(true);
+        self.toggled.set(false);
+        self.watchdog_fired.set(false);
+        self.root.reset();
+        self.frame_wall_time.set(SystemTime::now());
+        self.call(&self.root);
+    }
+
+    /// Tag the current frame with the state it is recorded in, such as the level being played.
+    ///
+    /// Tags are kept in snapshots, recordings and sinks, so frames can be grouped by them when
+    /// analysing a capture later. Tagging a frame with `key` again replaces the value. Tags are
+    /// cleared by `start_frame`.
+    pub fn tag_frame<K: Into<String>, V: Into<String>>(&self, key: K, value: V) {
+        let (key, value) = (key.into(), value.into());
+        let mut tags = self.tags.borrow_mut();
+        match tags.iter_mut().find(|t| t.0 == key) {
+            Some(tag) => tag.1 = value,
+            None => tags.push((key, value)),
+        }
+    }
+
+    /// The tags of the current frame.
+    pub fn frame_tags(&self) -> Vec<(String, String)> {
+        self.tags.borrow().clone()
+    }
+
+    /// Start a frame, returning a guard object that will `end_frame` on destruction.
+    ///
+    /// This keeps the frame balanced even if the main loop body returns early.
+    pub fn frame(&self) -> FrameGuard<'_> {
+        self.start_frame();
+        FrameGuard { profiler: self, kind: None }
+    }
+
+    /// Start a frame of kind `kind`, returning a guard object that will `end_frame_of` on
+    /// destruction.
+    pub fn frame_of(&self, kind: &'static str) -> FrameGuard<'_> {
+        self.start_frame_of(kind);
+        FrameGuard { profiler: self, kind: Some(kind) }
+    }
+
+    /// Start a frame of a secondary kind, such as a fixed-timestep simulation tick.
+    ///
+    /// Each kind has its own root node, named `kind`, which is reset by this call. Until the
+    /// matching `end_frame_of`, nodes are entered below that root rather than the current node,
+    /// so a tick running in the middle of a render frame gets a tree of its own. Frames of
+    /// different kinds may nest, but must be ended in the reverse order they were started.
+    pub fn start_frame_of(&self, kind: &'static str) {
         early_leave!(self);
-        let mut curr = self.current.borrow_mut();
-        if curr.ret() == true {
-            if let Some(parent) = curr.parent.clone() {
-                *curr = parent;
+        let root = {
+            let mut roots = self.frame_roots.borrow_mut();
+            match roots.iter().position(|r| r.name == kind) {
+                Some(i) => roots[i].clone(),
+                None => {
+                    let root = Rc::new(ProfileNode::new(None, kind));
+                    roots.push(root.clone());
+                    root
+                }
+            }
+        };
+        let curr = std::mem::replace(&mut *self.current.borrow_mut(), root.clone());
+        self.suspended.borrow_mut().push((curr, SystemTime::now()));
+        root.reset();
+        self.call(&root);
+    }
+
+    /// Finish a frame of a secondary kind, returning to where `start_frame_of` was called.
+    ///
+    /// The finished frame is handed to every sink's `Sink::frame_of`. It isn't recorded by the
+    /// flight recorder nor published to `FrameReader`s, which only ever see the main frames.
+    ///
+    /// Logs an error if `kind` isn't the innermost frame or there are pending `leave` calls;
+    /// pending nodes are abandoned and their timing data will be garbage.
+    pub fn end_frame_of(&self, kind: &'static str) {
+        self.finish_frame_of(kind);
+    }
+
+    /// `end_frame_of`, returning a summary of the finished frame.
+    ///
+    /// Returns `None` where `end_frame_of` would log an error.
+    pub fn end_frame_of_summary(&self, kind: &'static str) -> Option<FrameSummary> {
+        self.finish_frame_of(kind).map(|root| FrameSummary::of(&root, None, false))
+    }
+
+    // Finish a frame of kind `kind`, returning its root if it was balanced.
+    fn finish_frame_of(&self, kind: &'static str) -> Option<Rc<ProfileNode>> {
+        if !self.enabled.get() {
+            return None;
+        }
+        let curr = self.current.borrow().clone();
+        let popped = self.suspended.borrow_mut().pop();
+        let (node, start) = match popped {
+            Some(s) => s,
+            None => {
+                error!("Profiler::end_frame_of({:?}) without start_frame_of", kind);
+                return None;
             }
+        };
+        *self.current.borrow_mut() = node;
+        if curr.name != kind || curr.parent.is_some() {
+            error!("Pending `leave` calls on Profiler::end_frame_of({:?})", kind);
+            return None;
+        }
+        self.ret(&curr);
+        if let Some(alpha) = self.smoothing.get() {
+            curr.update_smoothed(alpha);
+        }
+        let info = FrameInfo {
+            index: curr.lifetime_frames() - 1,
+            start,
+            timestamp: SystemTime::now(),
+            tags: &[],
+        };
+        for sink in self.sinks.borrow_mut().iter_mut() {
+            if let Err(e) = sink.frame_of(kind, &info, &curr) {
+                error!("Failed to write a frame to a sink: {}", e);
+            }
+        }
+        Some(curr)
+    }
+
+    /// Return the root of the secondary frame kind `kind`, if it was ever started.
+    pub fn frame_root(&self, kind: &'static str) -> Option<Rc<ProfileNode>> {
+        self.frame_roots.borrow().iter().find(|r| r.name == kind).cloned()
+    }
+
+    /// Take an owned copy of the current profile tree, and its timeline events if any.
+    ///
+    /// Once a frame has been started, the snapshot has the index and wall-clock start time of
+    /// the frame in progress, or the index and wall-clock start and end times of the last one if
+    /// none is.
+    pub fn snapshot(&self) -> ProfileSnapshot {
+        let mut snapshot = ProfileSnapshot::of(&self.root);
+        let index = self.frame_index.get();
+        if self.in_frame.get() || index > 0 {
+            snapshot.index = if self.in_frame.get() { index } else { index - 1 };
+            snapshot.wall_time = Some(self.frame_wall_time.get());
+            if !self.in_frame.get() {
+                snapshot.end_wall_time = Some(self.frame_end_wall_time.get());
+            }
+        }
+        snapshot.events.extend_from_slice(&self.events.borrow());
+        snapshot.tags.extend_from_slice(&self.tags.borrow());
+        snapshot
+    }
+
+    /// Estimate the memory used by the profiler itself: its trees, the frames kept for history
+    /// and the buffers of the frame being recorded.
+    ///
+    /// Walks every node, so this is best called every few seconds rather than every frame, for
+    /// example to keep an eye on the footprint of a long-running server.
+    pub fn memory_usage(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        memory::add_tree(&mut stats, &self.root);
+        memory::add_tree(&mut stats, &self.outside);
+        for root in self.frame_roots.borrow().iter() {
+            memory::add_tree(&mut stats, root);
+        }
+        let spare = self.spare.borrow();
+        stats.spare_nodes = spare.len();
+        stats.node_bytes += spare.iter().map(|n| memory::node_bytes(n)).sum::<usize>();
+        stats.node_bytes += spare.capacity() * std::mem::size_of::<Rc<ProfileNode>>();
+        if let Some(ref recorder) = *self.recorder.borrow() {
+            stats.history_frames = recorder.frames().len();
+            stats.history_bytes += recorder.frames().iter().map(memory::snapshot_bytes).sum::<usize>();
+        }
+        if let Some(ref publisher) = *self.publisher.borrow() {
+            stats.history_bytes += publisher.heap_size();
+        }
+        let events = self.events.borrow();
+        stats.event_capacity = events.capacity();
+        stats.recording_bytes = memory::events_bytes(&events) + memory::tags_bytes(&self.tags.borrow());
+        stats
+    }
+
+    /// Enable or disable timeline recording.
+    ///
+    /// In timeline mode every `enter` and `leave` is also recorded as an individual timestamped
+    /// event, in addition to being folded into the node totals. Events are kept until the next
+    /// `start_frame` and included in snapshots, so exports such as the Chrome trace show when
+    /// within the frame each scope actually ran.
+    pub fn set_timeline(&self, enabled: bool) {
+        self.timeline.set(enabled);
+    }
+
+    /// Whether timeline recording is enabled.
+    pub fn timeline(&self) -> bool {
+        self.timeline.get()
+    }
+
+    /// Enable or disable recording the CPU core of every timeline event.
+    ///
+    /// In timeline mode, each `enter` and `leave` then also records the core the thread was on,
+    /// as read by `clock::current_cpu`, so exports show which cores each scope ran on. A call
+    /// that starts on one core and ends on another was migrated in between, which in job-system
+    /// heavy frames is often behind scopes that are slow for no visible reason. Has no effect
+    /// outside timeline mode, or on platforms where the core isn't known.
+    pub fn set_cpu_ids(&self, enabled: bool) {
+        self.cpu_ids.set(enabled);
+    }
+
+    /// Whether timeline events record the CPU core they happened on.
+    pub fn cpu_ids(&self) -> bool {
+        self.cpu_ids.get()
+    }
+
+    // The current CPU core, if it is being recorded.
+    fn cpu_id(&self) -> Option<u32> {
+        if self.cpu_ids.get() {
+            clock::current_cpu()
+        } else {
+            None
+        }
+    }
+
+    /// Enable or disable telling nodes apart by callsite.
+    ///
+    /// By default, every scope named "tick" under the same parent is merged into one node, even
+    /// if they are unrelated functions that happen to share a name. With callsites enabled, a
+    /// node is identified by its parent, its name and the location it was entered from through
+    /// `scope!`, and the text report shows the location of nodes whose names would otherwise
+    /// be ambiguous. Scopes entered without a location are still merged by name.
+    ///
+    /// Best set before the first frame, as existing nodes are kept as they are.
+    pub fn set_callsites(&self, enabled: bool) {
+        self.callsites.set(enabled);
+    }
+
+    /// Whether nodes are told apart by callsite.
+    pub fn callsites(&self) -> bool {
+        self.callsites.get()
+    }
+
+    /// Enable or disable treating a scope entered inside another of the same name as recursion.
+    ///
+    /// By default, entering "visit" while "visit" is the current node enters that node again,
+    /// adding to its recursion depth, which suits recursive functions. With folding disabled, a
+    /// new "visit" node is made below it instead, as for any other name, so that nested but
+    /// unrelated scopes that share a label keep their structure. `enter_recursive` always
+    /// folds.
+    pub fn set_fold_recursion(&self, enabled: bool) {
+        self.fold_recursion.set(enabled);
+    }
+
+    /// Whether scopes entered inside another of the same name are treated as recursion.
+    pub fn fold_recursion(&self) -> bool {
+        self.fold_recursion.get()
+    }
+
+    /// Enable or disable measuring the CPU time of the thread along with the wall time of every
+    /// call.
+    ///
+    /// A scope that spends its time waiting, on the GPU, IO, a lock or a sleep, uses far less
+    /// CPU time than wall time, while one doing work uses about as much of both. Reports show
+    /// each node's CPU time next to its total. Reading the thread's CPU time is a system call
+    /// on most platforms, so this roughly doubles the cost of each scope. See
+    /// `clock::thread_cpu_time`.
+    pub fn set_cpu_time(&self, enabled: bool) {
+        self.cpu_time.set(enabled);
+    }
+
+    /// Whether the CPU time of the thread is measured.
+    pub fn cpu_time(&self) -> bool {
+        self.cpu_time.get()
+    }
+
+    /// Enable or disable flat mode.
+    ///
+    /// In flat mode, scopes are only summed up by name into `flat_totals`, without building a
+    /// tree, for tools that just want the calls and total time of each name with as little
+    /// overhead as possible. There is no need for frames, and nothing is added to the tree,
+    /// timeline, hooks, or anything else derived from it. Scopes entered when switching modes
+    /// are abandoned, so this is best done before any are entered.
+    pub fn set_flat(&self, enabled: bool) {
+        if enabled != self.flat.get() {
+            self.flat_profile.borrow_mut().abandon();
+            self.flat.set(enabled);
+        }
+    }
+
+    /// Whether flat mode is enabled.
+    pub fn flat(&self) -> bool {
+        self.flat.get()
+    }
+
+    /// The calls and total time of every name entered in flat mode, longest first.
+    pub fn flat_totals(&self) -> Vec<FlatStats> {
+        self.flat_profile.borrow().stats()
+    }
+
+    /// Print out the totals returned by `flat_totals`.
+    pub fn print_flat(&self) {
+        let stdout = std::io::stdout();
+        let _ = self.write_flat(&mut stdout.lock());
+    }
+
+    /// Write out the totals of flat mode in the same format as `print_flat`.
+    pub fn write_flat<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        writeln!(w, "Flat timing information for {}:", self.root.display_name())?;
+        flat::write_flat(w, &self.flat_totals(), 2)
+    }
+
+    /// Reset the totals of flat mode to zero.
+    pub fn clear_flat(&self) {
+        self.flat_profile.borrow_mut().clear(self.clock.now());
+    }
+
+    /// Change what is kept about individual calls, such as for the thread-local profiler, which
+    /// is created with `Aggregation::Sum`.
+    ///
+    /// Best done before the first frame, since statistics gathered with the previous strategy are
+    /// kept, and still shown, until the tree is cleared with `clear`.
+    pub fn set_aggregation(&self, aggregation: Aggregation) {
+        self.aggregation.set(aggregation);
+    }
+
+    /// What is kept about individual calls.
+    pub fn aggregation(&self) -> Aggregation {
+        self.aggregation.get()
+    }
+
+    /// The timeline events recorded since the last `start_frame`, or outside of frames, since the
+    /// last outermost scope was entered.
+    pub fn events(&self) -> Vec<TimelineEvent> {
+        self.events.borrow().clone()
+    }
+
+    /// Make this profiler's tree available to other threads through `collect_threads` and
+    /// `thread_snapshots`, under `name`.
+    ///
+    /// Recording stays entirely local to this thread: `enter` and `leave` never touch memory
+    /// shared with other threads, so contention between many workers can't serialize the code
+    /// being measured. When another thread asks for the tree, this one notices with a single
+    /// atomic load the next time it leaves its outermost scope or ends a frame, and publishes a
+    /// snapshot of the scopes entered outside of frames, or of the last frame if it uses frames.
+    /// When the profiler is dropped, as the implicit one is when its thread exits, it publishes a
+    /// final snapshot.
+    ///
+    /// Logs an error and does nothing if the profiler is already registered.
+    pub fn register<S: Into<String>>(&self, name: S) {
+        let mut registration = self.registration.borrow_mut();
+        if registration.is_some() {
+            error!("Profiler::register called on a registered profiler");
+            return
+        }
+        *registration = Some(registry::register(name.into()));
+    }
+
+    // Publish a snapshot to the registry if another thread asked for one.
+    fn publish_if_requested(&self) {
+        if let Some(ref slot) = *self.registration.borrow() {
+            if slot.requested() {
+                slot.publish(self.registry_snapshot(), false);
+            }
+        }
+    }
+
+    // The tree published to the registry.
+    fn registry_snapshot(&self) -> ProfileSnapshot {
+        if self.frame_index.get() > 0 || self.in_frame.get() {
+            self.snapshot()
+        } else {
+            ProfileSnapshot::of(&self.outside)
+        }
+    }
+
+    /// Return a handle to the last completed frame that can be sent to another thread.
+    ///
+    /// Once the first reader is created, every completed frame is copied out at `end_frame` for
+    /// readers to pick up, so a UI thread can display stable results while this thread is in the
+    /// middle of recording the next frame.
+    pub fn frame_reader(&self) -> FrameReader {
+        self.publisher.borrow_mut().get_or_insert_with(FramePublisher::new).reader()
+    }
+
+    /// Hand every frame completed from now on to `sink`, as well as any sinks already added.
+    ///
+    /// Errors from sinks are logged and otherwise ignored.
+    pub fn add_sink<S: Sink + 'static>(&self, sink: S) {
+        self.sinks.borrow_mut().push(Box::new(sink));
+    }
+
+    /// Remove all sinks.
+    pub fn clear_sinks(&self) {
+        self.sinks.borrow_mut().clear();
+    }
+
+    /// Tell `hook` about every node entered and left from now on, as well as any hooks already
+    /// added.
+    ///
+    /// Hooks are called inside the timed region of their node, so slow hooks inflate its times.
+    /// A hook may use the profiler, for example to enter scopes of its own, but no hook is told
+    /// about the scopes a hook enters, and `clear_hooks` has no effect from inside a hook.
+    pub fn add_hook<H: ScopeHook + 'static>(&self, hook: H) {
+        self.hooks.borrow_mut().push(Box::new(hook));
+    }
+
+    /// Remove all hooks.
+    pub fn clear_hooks(&self) {
+        self.hooks.borrow_mut().clear();
+    }
+
+    /// Start keeping a copy of the last `frames` completed frames in a flight recorder.
+    ///
+    /// Any frames already recorded are discarded.
+    pub fn enable_flight_recorder(&self, frames: usize) {
+        *self.recorder.borrow_mut() = Some(FlightRecorder::new(frames));
+    }
+
+    /// Stop recording frames, discarding those recorded so far.
+    pub fn disable_flight_recorder(&self) {
+        *self.recorder.borrow_mut() = None;
+    }
+
+    /// Write the frames held by the flight recorder to `w` and empty it.
+    ///
+    /// Writes an empty recording if the flight recorder isn't enabled.
+    pub fn flush_flight_recorder<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        match *self.recorder.borrow_mut() {
+            Some(ref mut recorder) => recorder.flush_to(w, &self.metadata.borrow()),
+            None => recording::write_header(w, &self.metadata.borrow()),
         }
     }
 
-    /// Print out the current timing information in a very naive way.
-    pub fn print_timing(&self) {
-        println!("Timing information for {}:", self.root.name);
-        for child in &*self.root.children.borrow() {
-            child.print(2);
-        }
+    /// Write the frames held by the flight recorder to the file at `path` and empty it.
+    pub fn flush_flight_recorder_to<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.flush_flight_recorder(&mut w)?;
+        w.flush()
     }
 
-    /// Return the root profile node for inspection.
+    /// Describe the session, for example with the build id, git hash, hardware or settings.
     ///
-    /// This root will always be valid and reflect the current state of the `Profiler`.
-    /// It is not advised to inspect the data between calls to `start_frame` and `end_frame`.
-    pub fn root(&self) -> Rc<ProfileNode> {
-        self.root.clone()
+    /// Metadata is written into flight recorder dumps, so that capture files can be identified
+    /// later. Setting a key again replaces its value.
+    pub fn set_metadata<K: Into<String>, V: Into<String>>(&self, key: K, value: V) {
+        self.metadata.borrow_mut().insert(key.into(), value.into());
     }
 
-    /// Finish a frame.
+    /// Show `label` instead of the root's name in reports, snapshots, recordings and exports.
     ///
-    /// Logs an error if there are pending `leave` calls, and later attempts to
-    /// print timing data will be met with sadness in the form of `NaN`s.
-    pub fn end_frame(&self) {
-        early_leave!(self);
-        if &*self.root as *const ProfileNode as usize != &**self.current.borrow() as *const ProfileNode as usize {
-            error!("Pending `leave` calls on Profiler::frame");
-        } else {
-            self.root.ret();
-        }
+    /// The root keeps its name for everything else, such as node IDs.
+    pub fn set_label<S: Into<String>>(&self, label: S) {
+        *self.root.label.borrow_mut() = Some(label.into());
     }
 
-    /// Start a frame.
+    /// Show the root's own name again.
+    pub fn clear_label(&self) {
+        *self.root.label.borrow_mut() = None;
+    }
+
+    /// Describe this run, such as "run #3, high settings", to tell captures apart when comparing
+    /// them side by side.
     ///
-    /// Resets timing data. Logs an error if there are pending `leave` calls, but there are
-    /// otherwise no ill effects.
-    pub fn start_frame(&self) {
-        early_leave!(self);
-        if &*self.root as *const ProfileNode as usize != &**self.current.borrow() as *const ProfileNode as usize {
-            error!("Pending `leave` calls on Profiler::frame");
-        }
-        *self.current.borrow_mut() = self.root.clone();
-        self.root.reset();
-        self.root.call();
+    /// The label is shown at the top of the text reports, and kept as the `run` metadata key so
+    /// that it ends up in recordings and exports along with the rest of the metadata.
+    pub fn set_run_label<S: Into<String>>(&self, label: S) {
+        self.set_metadata(RUN_LABEL, label);
+    }
+
+    /// The label set with `set_run_label`, if any.
+    pub fn run_label(&self) -> Option<String> {
+        self.metadata.borrow().get(RUN_LABEL).cloned()
+    }
+
+    /// The metadata set with `set_metadata`.
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        self.metadata.borrow().clone()
     }
 
     /// Disable the profiler.
     ///
     /// All calls until `enable` will do nothing.
     pub fn disable(&self) {
-        self.enabled.set(false);
+        self.set_enabled(false);
     }
 
     /// Enable the profiler.
     ///
     /// Calls will take effect until `disable` is called.
     pub fn enable(&self) {
-        self.enabled.set(true);
+        self.set_enabled(true);
     }
 
     /// Toggle the profiler enabledness.
     pub fn toggle(&self) {
-        self.enabled.set(!self.enabled.get());
+        self.set_enabled(!self.enabled.get());
+    }
+
+    /// Stop recording scopes until the returned guard is destroyed.
+    ///
+    /// Unlike `disable`, pausing keeps the tree consistent: scopes entered while paused are
+    /// ignored along with their `leave`s, even if those come after resuming, while scopes that
+    /// were open can still be left. The time spent paused is taken out of the scopes that were
+    /// open, their CPU time and the timeline, so a blocking file dialog doesn't show up as a
+    /// slow frame. Frames can still be started and ended while paused. Pauses nest; recording
+    /// resumes when the last guard is destroyed.
+    pub fn pause(&self) -> PauseGuard<'_> {
+        if self.paused.get() == 0 {
+            self.pause_start.set(self.clock.now());
+            if self.cpu_time.get() {
+                self.pause_cpu_start.set(clock::thread_cpu_time());
+            }
+        }
+        self.paused.set(self.paused.get() + 1);
+        PauseGuard { profiler: self }
+    }
+
+    /// Whether the profiler is paused by a `PauseGuard`.
+    pub fn is_paused(&self) -> bool {
+        self.paused.get() > 0
+    }
+
+    fn resume(&self) {
+        self.paused.set(self.paused.get() - 1);
+        if self.paused.get() > 0 {
+            return
+        }
+        let now = self.clock.now();
+        let paused = now.saturating_sub(self.pause_start.get());
+        // shift the start of every open node past the pause, or to now if it started during it
+        let mut open: Vec<Rc<ProfileNode>> = Vec::new();
+        let current = self.current.borrow().clone();
+        for node in Some(current).into_iter().chain(self.suspended.borrow().iter().map(|s| s.0.clone())) {
+            let mut node = Some(node);
+            while let Some(n) = node {
+                if open.iter().any(|o| Rc::ptr_eq(o, &n)) {
+                    break
+                }
+                node = n.parent();
+                open.push(n);
+            }
+        }
+        let shift = |start: u64| start + paused.min(now.saturating_sub(start));
+        let cpu_paused = match (self.pause_cpu_start.take(), clock::thread_cpu_time()) {
+            (Some(start), Some(cpu_now)) => cpu_now.saturating_sub(start),
+            _ => 0,
+        };
+        for node in &open {
+            node.start_time.set(shift(node.start_time.get()));
+            if let Some(cpu_start) = node.cpu_start.get() {
+                node.cpu_start.set(Some(cpu_start + cpu_paused));
+            }
+        }
+        // and every event of the frame, so the pause is cut out of the timeline too
+        for event in self.events.borrow_mut().iter_mut() {
+            event.time = shift(event.time);
+        }
+    }
+
+    // The time in ns to record leaves at: now, or the start of the pause if paused, so the time
+    // spent paused isn't counted.
+    fn now(&self) -> u64 {
+        if self.is_paused() {
+            self.pause_start.get()
+        } else {
+            self.clock.now()
+        }
+    }
+
+    // The thread's CPU time to record leaves at, likewise.
+    fn cpu_now(&self) -> Option<u64> {
+        if self.is_paused() {
+            self.pause_cpu_start.get()
+        } else {
+            clock::thread_cpu_time()
+        }
+    }
+
+    // Whether the tree is being recorded into.
+    fn recording(&self) -> bool {
+        self.active() && !self.flat.get()
     }
 
+    // Whether the last scope entered was recorded in the tree, rather than skipped because the
+    // profiler is off or the scope is inside a collapsed node.
+    fn entered(&self) -> bool {
+        self.recording() && self.collapsed_depth.get() == 0
+    }
+
+    // Whether scopes are being recorded, into the tree or the flat profile.
+    fn active(&self) -> bool {
+        self.enabled.get() && self.paused.get() == 0
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        if self.in_frame.get() && enabled != self.enabled.get() {
+            // leaves may have been skipped, so this frame's data can't be trusted
+            self.toggled.set(true);
+        }
+        self.enabled.set(enabled);
+    }
+
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        if let Some(slot) = self.registration.borrow_mut().take() {
+            slot.publish(self.registry_snapshot(), true);
+        }
+        if let Some(mut w) = self.on_drop.borrow_mut().take() {
+            if let Err(e) = self.write_session_summary(&mut w).and_then(|_| w.flush()) {
+                error!("Failed to write the session summary: {}", e);
+            }
+        }
+    }
 }
 
 /// A single node in the profile tree.
 ///
 /// *NOTE*: While the fields are public and are a cell, it is not advisable to modify them.
+///
+/// The time fields are the counters the profiler updates on every `enter` and `leave`, so they
+/// stay raw ns, as `total_time` and `start_time` always were, rather than being converted on the
+/// hot path. Read times through the `Duration` getters instead: `total`, `self_time`,
+/// `average`, `lifetime_time`, `time_budget`, `cpu`, `blocked_time`, `min`, `max`, `previous` and
+/// `smoothed`. `start_time` is a timestamp of the profiler's clock rather than a duration, and
+/// only differences between timestamps mean anything.
 pub struct ProfileNode {
     pub name: &'static str,
+    /// Name shown instead of `name` in reports and exports, if set. See `Profiler::set_label`.
+    pub label: RefCell<Option<String>>,
+    /// Identifier of this node's path, stable across frames and runs.
+    ///
+    /// Derived from the names on the path from the root, so the same path always gets the same
+    /// ID, even in a different process.
+    pub id: u64,
+    /// Category explicitly assigned to this node, if any.
+    pub category: Cell<Option<Category>>,
+    /// Color explicitly assigned to this node, if any, which overrides that of its category.
+    /// See `Profiler::enter_colored` and `Profiler::set_color`.
+    pub color: Cell<Option<Color>>,
+    /// Where the node was first entered from, if it was entered through `scope!`.
+    pub location: Cell<Option<Location>>,
     /// Number of calls made to this node.
     pub calls: Cell<u32>,
+    /// Time in ns this node is expected to fit in each frame. See `Profiler::set_node_budget`.
+    pub budget: Cell<Option<u64>>,
     /// Total time in ns used by this node and all of its children.
     ///
     /// Computed after the last pending `ret`.
     pub total_time: Cell<u64>,
     /// Timestamp in ns when the first `call` was made to this node.
     pub start_time: Cell<u64>,
+    /// CPU time in ns used by the thread during calls to this node, including its children, if
+    /// measured. See `Profiler::set_cpu_time`.
+    pub cpu_time: Cell<Option<u64>>,
+    /// CPU time in ns of the thread when the pending outermost call was made, if measured.
+    pub cpu_start: Cell<Option<u64>>,
+    /// Whether the node was entered with `Profiler::enter_blocking`, so that all of its time
+    /// counts as blocked.
+    pub blocking: Cell<bool>,
+    /// Bytes read or written during calls to this node this frame, as counted by
+    /// `Profiler::add_bytes`.
+    pub bytes: Cell<u64>,
+    /// Whether the node's children have been dropped and scopes entered below it aren't
+    /// recorded. See `Profiler::set_collapsing`.
+    pub collapsed: Cell<bool>,
+    /// Number of frames in a row the node's total has been under the collapsing threshold.
+    pub quiet_frames: Cell<u32>,
     /// Number of recursive calls made to this node since the first `call`.
     pub recursion: Cell<u32>,
+    /// Deepest the node was nested in itself this frame, where 1 means it wasn't recursive.
+    pub max_recursion: Cell<u32>,
+    /// Number of calls made this frame while already inside this node, by recursion depth: the
+    /// first element counts calls made directly inside a call, the second calls made inside
+    /// those, and so on.
+    ///
+    /// Empty if the node wasn't recursive.
+    pub recursive_calls: RefCell<Vec<u32>>,
+    /// Exponential moving average of the total per frame in ns, if smoothing is enabled.
+    ///
+    /// Updated at the end of each frame rather than reset.
+    pub smoothed_time: Cell<Option<f64>>,
+    /// Number of calls that appeared to end before they started, and were ignored.
+    pub clock_errors: Cell<u32>,
     /// Parent in the profile tree.
-    pub parent: Option<Rc<ProfileNode>>,
+    ///
+    /// Weak, so that dropping the `Profiler` frees the whole tree.
+    pub parent: Option<Weak<ProfileNode>>,
+    /// Duration in ns of the shortest call, with `Aggregation::MinMax`.
+    ///
+    /// Not reset at the start of each frame.
+    pub min_time: Cell<Option<u64>>,
+    /// Duration in ns of the longest call, with `Aggregation::MinMax`.
+    ///
+    /// Not reset at the start of each frame.
+    pub max_time: Cell<Option<u64>>,
+    /// Sample of individual call durations in ns, with `Aggregation::Reservoir`.
+    ///
+    /// Unlike the other fields, this is not reset at the start of each frame.
+    pub samples: RefCell<Option<Reservoir>>,
+    /// Histogram of call durations in ns, with `Aggregation::Histogram`.
+    ///
+    /// Not reset at the start of each frame.
+    pub histogram: RefCell<Option<Histogram>>,
+    /// Notes attached with `Profiler::annotate` during the current frame.
+    pub annotations: RefCell<Vec<String>>,
+    /// Calls made to this node in previous frames, added up when each frame is reset.
+    pub past_calls: Cell<u64>,
+    /// Total time in ns used by this node in previous frames.
+    pub past_time: Cell<u64>,
+    /// Number of previous frames in which this node was called.
+    pub past_frames: Cell<u64>,
+    /// Most calls made to this node in any previous frame.
+    pub past_max_calls: Cell<u32>,
+    /// Total time in ns in the previous frame, unless there was none.
+    pub previous_time: Cell<Option<u64>>,
     // TODO: replace this Vec with an intrusive list. Use containerof?
     /// Child nodes.
     pub children: RefCell<Vec<Rc<ProfileNode>>>,
+    /// Roots of other profilers mounted here with `Profiler::mount`.
+    ///
+    /// Their children are reported as children of this node, but they are never reset or
+    /// otherwise modified by this node's profiler.
+    pub mounted: RefCell<Vec<Rc<ProfileNode>>>,
 }
 
 impl ProfileNode {
     pub fn new(parent: Option<Rc<ProfileNode>>, name: &'static str) -> ProfileNode {
+        let id = node_id(parent.as_ref().map(|p| p.id).unwrap_or(0), name);
         ProfileNode {
-            name: name,
+            name,
+            label: RefCell::new(None),
+            id,
+            category: Cell::new(None),
+            color: Cell::new(None),
+            location: Cell::new(None),
             calls: Cell::new(0),
+            budget: Cell::new(None),
             total_time: Cell::new(0),
             start_time: Cell::new(0),
+            cpu_time: Cell::new(None),
+            cpu_start: Cell::new(None),
+            blocking: Cell::new(false),
+            bytes: Cell::new(0),
+            collapsed: Cell::new(false),
+            quiet_frames: Cell::new(0),
             recursion: Cell::new(0),
-            parent: parent,
-            children: RefCell::new(Vec::new())
+            max_recursion: Cell::new(0),
+            recursive_calls: RefCell::new(Vec::new()),
+            smoothed_time: Cell::new(None),
+            clock_errors: Cell::new(0),
+            parent: parent.as_ref().map(Rc::downgrade),
+            min_time: Cell::new(None),
+            max_time: Cell::new(None),
+            samples: RefCell::new(None),
+            histogram: RefCell::new(None),
+            annotations: RefCell::new(Vec::new()),
+            past_calls: Cell::new(0),
+            past_time: Cell::new(0),
+            past_frames: Cell::new(0),
+            past_max_calls: Cell::new(0),
+            previous_time: Cell::new(None),
+            children: RefCell::new(Vec::new()),
+            mounted: RefCell::new(Vec::new()),
         }
     }
 
+    /// The name to show for this node: its label if it has one, or else its name.
+    pub fn display_name(&self) -> Cow<'static, str> {
+        match *self.label.borrow() {
+            Some(ref label) => Cow::Owned(label.clone()),
+            None => Cow::Borrowed(self.name),
+        }
+    }
+
+    /// The parent of this node, unless it is a root.
+    pub fn parent(&self) -> Option<Rc<ProfileNode>> {
+        self.parent.as_ref().and_then(|p| p.upgrade())
+    }
+
     /// Reset this node and its children, seting relevant fields to 0.
+    ///
+    /// Completed calls are first added to the lifetime totals.
     pub fn reset(&self) {
+        if self.calls.get() > 0 && self.recursion.get() == 0 {
+            self.past_calls.set(self.past_calls.get() + self.calls.get() as u64);
+            self.past_time.set(self.past_time.get() + self.total_time.get());
+            self.past_frames.set(self.past_frames.get() + 1);
+            self.past_max_calls.set(self.past_max_calls.get().max(self.calls.get()));
+        }
+        if self.recursion.get() == 0 {
+            self.previous_time.set(Some(self.total_time.get()));
+        }
         self.calls.set(0);
         self.total_time.set(0);
         self.start_time.set(0);
+        self.cpu_time.set(None);
+        self.bytes.set(0);
         self.recursion.set(0);
+        self.max_recursion.set(0);
+        self.recursive_calls.borrow_mut().clear();
+        self.clock_errors.set(0);
+        self.annotations.borrow_mut().clear();
         for child in &*self.children.borrow() {
             child.reset()
         }
     }
 
-    /// Create a child named `name`.
+    /// Calls made to this node over the life of the profiler, including the current frame.
+    pub fn lifetime_calls(&self) -> u64 {
+        self.past_calls.get() + if self.recursion.get() == 0 { self.calls.get() as u64 } else { 0 }
+    }
+
+    /// Total time used by this node over the life of the profiler, including the current frame
+    /// once the node has been left.
+    pub fn lifetime_time(&self) -> Duration {
+        Duration::from_nanos(self.past_time.get() + if self.recursion.get() == 0 { self.total_time.get() } else { 0 })
+    }
+
+    /// Number of frames in which this node was called, including the current one.
+    pub fn lifetime_frames(&self) -> u64 {
+        self.past_frames.get() + if self.recursion.get() == 0 && self.calls.get() > 0 { 1 } else { 0 }
+    }
+
+    /// Most calls made to this node in a single frame, including the current one.
+    pub fn max_calls_per_frame(&self) -> u32 {
+        self.past_max_calls.get().max(if self.recursion.get() == 0 { self.calls.get() } else { 0 })
+    }
+
+    /// Calls made to this node this frame at each recursion depth, starting with the calls that
+    /// weren't made from inside the node itself. Empty if the node wasn't called.
+    pub fn calls_by_depth(&self) -> Vec<u32> {
+        let recursive = self.recursive_calls.borrow();
+        if self.calls.get() == 0 {
+            return Vec::new()
+        }
+        let mut counts = vec![self.calls.get() - recursive.iter().sum::<u32>()];
+        counts.extend(recursive.iter().cloned());
+        counts
+    }
+
+    /// Create a child named `name`, or return the existing one.
     pub fn make_child(&self, me: Rc<ProfileNode>, name: &'static str) -> Rc<ProfileNode> {
+        if let Some(child) = self.find_child(name) {
+            return child
+        }
+        let new = Rc::new(ProfileNode::new(Some(me), name));
+        self.children.borrow_mut().push(new.clone());
+        new
+    }
+
+    /// Drop all descendants of this node.
+    pub fn clear_children(&self) {
+        self.children.borrow_mut().clear();
+    }
+
+    /// Count this frame towards collapsing this node and each of its descendants, collapsing
+    /// those that have been under `threshold` ns for `frames` frames and expanding collapsed
+    /// ones that went over it.
+    pub fn update_collapsed(&self, threshold: u64, frames: u32) {
+        if self.total_time.get() < threshold {
+            self.quiet_frames.set(self.quiet_frames.get().saturating_add(1));
+        } else {
+            self.quiet_frames.set(0);
+            self.collapsed.set(false);
+        }
+        if self.collapsed.get() {
+            return
+        }
+        if self.quiet_frames.get() >= frames && !self.children.borrow().is_empty() {
+            self.collapsed.set(true);
+            self.clear_children();
+            return
+        }
+        for child in &*self.children.borrow() {
+            child.update_collapsed(threshold, frames);
+        }
+    }
+
+    /// Expand this node and each of its descendants, forgetting how long they were under the
+    /// collapsing threshold.
+    pub fn expand(&self) {
+        self.collapsed.set(false);
+        self.quiet_frames.set(0);
+        for child in &*self.children.borrow() {
+            child.expand();
+        }
+    }
+
+    /// The children of this node followed by the children of every mounted root.
+    pub fn all_children(&self) -> Vec<Rc<ProfileNode>> {
+        let mut children = self.children.borrow().clone();
+        for root in &*self.mounted.borrow() {
+            children.extend(root.children.borrow().iter().cloned());
+        }
+        children
+    }
+
+    /// Shrink the storage of this node and its descendants to fit.
+    pub fn shrink_to_fit(&self) {
         let mut children = self.children.borrow_mut();
+        children.shrink_to_fit();
         for child in &*children {
+            child.shrink_to_fit();
+        }
+    }
+
+    /// Return the descendant at the `/`-separated `path` below this node, looking into mounted
+    /// profilers too.
+    pub fn find_path(&self, path: &str) -> Option<Rc<ProfileNode>> {
+        let mut node: Option<Rc<ProfileNode>> = None;
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            let next = node.as_deref().unwrap_or(self).find_grafted(name);
+            node = Some(next?);
+        }
+        node
+    }
+
+    // The child named `name`, among the children of this node and of its mounted profilers.
+    fn find_grafted(&self, name: &str) -> Option<Rc<ProfileNode>> {
+        if let Some(child) = self.children.borrow().iter().find(|c| c.name == name) {
+            return Some(child.clone())
+        }
+        for root in &*self.mounted.borrow() {
+            if let Some(child) = root.children.borrow().iter().find(|c| c.name == name) {
+                return Some(child.clone())
+            }
+        }
+        None
+    }
+
+    /// The nodes among this node and its descendants that went over their budget, in depth-first
+    /// order. See `Profiler::set_node_budget`.
+    pub fn budget_violations(&self) -> Vec<BudgetViolation> {
+        let mut violations = Vec::new();
+        self.collect_violations(&mut violations);
+        violations
+    }
+
+    fn collect_violations(&self, violations: &mut Vec<BudgetViolation>) {
+        if let Some(budget) = self.budget.get() {
+            if self.total_time.get() > budget {
+                violations.push(BudgetViolation {
+                    path: self.path(),
+                    total: self.total(),
+                    budget: Duration::from_nanos(budget),
+                });
+            }
+        }
+        for child in &*self.children.borrow() {
+            child.collect_violations(violations);
+        }
+    }
+
+    /// Return the child named `name`, if there is one.
+    pub fn find_child(&self, name: &'static str) -> Option<Rc<ProfileNode>> {
+        for child in &*self.children.borrow() {
             if child.name == name {
-                return child.clone()
+                return Some(child.clone())
             }
         }
-        let new = Rc::new(ProfileNode::new(Some(me), name));
-        children.push(new.clone());
-        new
+        None
+    }
+
+    /// The category of this node, inherited from the nearest ancestor that has one.
+    pub fn effective_category(&self) -> Option<Category> {
+        match self.category.get() {
+            Some(c) => Some(c),
+            None => self.parent().and_then(|p| p.effective_category()),
+        }
+    }
+
+    /// The color to draw this node in: its own, or else that of its category, or else that of
+    /// its parent.
+    pub fn effective_color(&self) -> Option<Color> {
+        match (self.color.get(), self.category.get()) {
+            (Some(color), _) => Some(color),
+            (None, Some(category)) => Some(category.color),
+            (None, None) => self.parent().and_then(|p| p.effective_color()),
+        }
+    }
+
+    /// Time spent in this node but not in any of its children, including those of mounted
+    /// profilers.
+    pub fn self_time(&self) -> Duration {
+        let children: u64 = self.all_children().iter().map(|c| c.total_time.get()).sum();
+        Duration::from_nanos(self.total_time.get().saturating_sub(children))
+    }
+
+    /// Total time used by this node and all of its children in the current frame.
+    pub fn total(&self) -> Duration {
+        Duration::from_nanos(self.total_time.get())
+    }
+
+    /// Average time per call in the current frame, or zero if the node wasn't called.
+    pub fn average(&self) -> Duration {
+        match self.calls.get() {
+            0 => Duration::from_nanos(0),
+            calls => Duration::from_nanos(self.total_time.get() / calls as u64),
+        }
+    }
+
+    /// Duration of the shortest call, with `Aggregation::MinMax`.
+    pub fn min(&self) -> Option<Duration> {
+        self.min_time.get().map(Duration::from_nanos)
+    }
+
+    /// Duration of the longest call, with `Aggregation::MinMax`.
+    pub fn max(&self) -> Option<Duration> {
+        self.max_time.get().map(Duration::from_nanos)
     }
 
-    /// Enter this profile node.
+    /// Exponential moving average of the total per frame, if smoothing is enabled.
+    pub fn smoothed(&self) -> Option<Duration> {
+        self.smoothed_time.get().map(|t| Duration::from_nanos(t as u64))
+    }
+
+    /// Time this node is expected to fit in each frame, if any. See `Profiler::set_node_budget`.
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.budget.get().map(Duration::from_nanos)
+    }
+
+    /// Total time in the previous frame, unless there was none.
+    pub fn previous(&self) -> Option<Duration> {
+        self.previous_time.get().map(Duration::from_nanos)
+    }
+
+    /// The CPU time used by the thread during calls to this node, if measured.
+    pub fn cpu(&self) -> Option<Duration> {
+        self.cpu_time.get().map(Duration::from_nanos)
+    }
+
+    /// Time this node spent waiting rather than running on the CPU, if known.
+    ///
+    /// That is all of its time if it was entered with `Profiler::enter_blocking`, or else the
+    /// difference between its wall time and CPU time if CPU time was measured, or else the
+    /// blocked time of its children, if any of them is known. Nodes blocked for more than half
+    /// of their total are shown as waiting in reports and exports.
+    pub fn blocked_time(&self) -> Option<Duration> {
+        if self.blocking.get() {
+            return Some(self.total())
+        }
+        if let Some(cpu) = self.cpu_time.get() {
+            return Some(Duration::from_nanos(self.total_time.get().saturating_sub(cpu)))
+        }
+        self.all_children().iter().filter_map(|c| c.blocked_time())
+            .fold(None, |sum, t| Some(sum.unwrap_or_default() + t))
+    }
+
+    /// Whether this node was blocked for more than half of its total, and counts as waiting.
+    pub fn waiting(&self) -> bool {
+        self.blocked_time().map(|b| b > self.total() / 2).unwrap_or(false)
+    }
+
+    /// Find the node with the given `id` in this subtree, if it exists.
+    pub fn find_by_id(&self, id: u64) -> Option<Rc<ProfileNode>> {
+        for child in &*self.children.borrow() {
+            if child.id == id {
+                return Some(child.clone())
+            }
+            if let Some(found) = child.find_by_id(id) {
+                return Some(found)
+            }
+        }
+        None
+    }
+
+    /// Enter this profile node, at the current time by the default clock.
+    ///
+    /// The node's profiler may have another clock, given to `Profiler::set_clock`; use `call_at`
+    /// with its timestamps then.
     pub fn call(&self) {
+        self.call_at(clock::now());
+    }
+
+    /// Enter this profile node at timestamp `now`, in ns.
+    pub fn call_at(&self, now: u64) {
         self.calls.set(self.calls.get() + 1);
         let rec = self.recursion.get();
         if rec == 0 {
-            self.start_time.set(clock_ticks::precise_time_ns());
+            self.start_time.set(now);
+        } else {
+            let mut counts = self.recursive_calls.borrow_mut();
+            if counts.len() < rec as usize {
+                counts.resize(rec as usize, 0);
+            }
+            counts[rec as usize - 1] += 1;
         }
         self.recursion.set(rec + 1);
+        self.max_recursion.set(self.max_recursion.get().max(rec + 1));
     }
 
     /// Return from this profile node, returning true if there are no pending recursive calls.
+    ///
+    /// Like `call`, uses the default clock.
     pub fn ret(&self) -> bool {
+        self.ret_at(clock::now())
+    }
+
+    /// Return from this profile node at timestamp `now`, in ns.
+    pub fn ret_at(&self, now: u64) -> bool {
         let rec = self.recursion.get();
         if rec == 1 {
-            let time = clock_ticks::precise_time_ns();
-            let durr = time - self.start_time.get();
-            self.total_time.set(self.total_time.get() + durr);
+            match now.checked_sub(self.start_time.get()) {
+                Some(durr) => self.total_time.set(self.total_time.get() + durr),
+                None => self.clock_errors.set(self.clock_errors.get() + 1),
+            }
         }
         self.recursion.set(rec - 1);
         rec == 1
     }
 
+    /// Move the moving averages of this node and its descendants a fraction `alpha` towards
+    /// their current totals.
+    pub fn update_smoothed(&self, alpha: f64) {
+        let total = self.total_time.get() as f64;
+        self.smoothed_time.set(Some(match self.smoothed_time.get() {
+            Some(avg) => avg + alpha * (total - avg),
+            None => total,
+        }));
+        for child in &*self.children.borrow() {
+            child.update_smoothed(alpha);
+        }
+    }
+
+    /// Update the shortest and longest call durations with a single call.
+    pub fn record_extremes(&self, duration: u64) {
+        self.min_time.set(Some(self.min_time.get().map_or(duration, |m| m.min(duration))));
+        self.max_time.set(Some(self.max_time.get().map_or(duration, |m| m.max(duration))));
+    }
+
+    /// Add the duration of a single call to the sample, creating it with `capacity` if needed.
+    pub fn sample(&self, duration: u64, capacity: usize) {
+        self.samples.borrow_mut().get_or_insert_with(|| Reservoir::new(capacity)).add(duration);
+    }
+
     /// Print out the current timing information in a very naive way.
     ///
     /// Uses `indent` to determine how deep to indent the line.
     pub fn print(&self, indent: u32) {
-        for _ in 0..indent {
-            print!(" ");
-        }
-        let parent_time = self.parent
-                              .as_ref()
-                              .map(|p| p.total_time.get())
-                              .unwrap_or(self.total_time.get()) as f64;
-        let percent = 100.0 * (self.total_time.get() as f64 / parent_time);
-        if percent.is_infinite() {
-            println!("{name} - {calls} * {each} = {total} @ {hz:.1}hz",
-                name  = self.name,
-                calls = self.calls.get(),
-                each = Nanoseconds((self.total_time.get() as f64 / self.calls.get() as f64) as u64),
-                total = Nanoseconds(self.total_time.get()),
-                hz = self.calls.get() as f64 / self.total_time.get() as f64 * 1e9f64
-            );
-        } else {
-            println!("{name} - {calls} * {each} = {total} ({percent:.1}%)",
-                name  = self.name,
-                calls = self.calls.get(),
-                each = Nanoseconds((self.total_time.get() as f64 / self.calls.get() as f64) as u64),
-                total = Nanoseconds(self.total_time.get()),
-                percent = percent
-            );
+        let stdout = std::io::stdout();
+        let _ = self.write(&mut stdout.lock(), indent);
+    }
+
+    /// Write out the current timing information in the same format as `print`.
+    pub fn write<W: Write>(&self, w: &mut W, indent: u32) -> std::io::Result<()> {
+        report::write_node(w, self, indent, &PrintOptions::default())
+    }
+
+    /// The `/`-separated path of this node, not including the root.
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut parent = self.parent();
+        if parent.is_some() {
+            names.push(self.name);
         }
-        for c in &*self.children.borrow() {
-            c.print(indent+2);
+        while let Some(p) = parent {
+            if p.parent.is_some() {
+                names.push(p.name);
+            }
+            parent = p.parent();
         }
+        names.reverse();
+        names.join("/")
+    }
+}
+
+/// Install a panic hook that dumps the thread-local profiler of the panicking thread.
+///
+/// The current profile tree and the path of the node that was entered when the panic happened
+/// are written to stderr, after which the previously installed hook runs as usual. Explicit
+/// `Profiler`s are not known to the hook and aren't dumped.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = HPROF.try_with(|p| {
+            let stderr = std::io::stderr();
+            let mut w = stderr.lock();
+            let _ = p.write_panic_dump(&mut w);
+        });
+        previous(info)
+    }));
+}
+
+// `path` without empty names, as returned by `ProfileNode::path`.
+fn normalize_path(path: &str) -> String {
+    path.split('/').filter(|n| !n.is_empty()).collect::<Vec<_>>().join("/")
+}
+
+// FNV-1a over the parent's ID and the node name.
+fn node_id(parent: u64, name: &str) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = 0xcbf29ce484222325;
+    for i in 0..8 {
+        hash = (hash ^ ((parent >> (i * 8)) & 0xff)).wrapping_mul(PRIME);
     }
+    for b in name.bytes() {
+        hash = (hash ^ b as u64).wrapping_mul(PRIME);
+    }
+    hash
+}
+
+// The ID of a node in callsite mode, which also depends on where it was entered from.
+fn callsite_id(parent: u64, name: &str, location: Location) -> u64 {
+    node_id(node_id(node_id(parent, name), location.file), &location.line.to_string())
 }
 
 pub fn profiler() -> &'static Profiler {
-    HPROF.with(|p| unsafe { std::mem::transmute(p) } )
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) } )
 }
 
 pub fn enter(name: &'static str) -> ProfileGuard<'static> {
-    HPROF.with(|p| unsafe { std::mem::transmute::<_, &'static Profiler>(p) }.enter(name) )
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter(name) )
+}
+
+pub fn pause() -> PauseGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.pause() )
+}
+
+pub fn enter_at(name: &'static str, location: Location) -> ProfileGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_at(name, location) )
+}
+
+pub fn enter_recursive(name: &'static str) -> ProfileGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_recursive(name) )
+}
+
+pub fn enter_colored(name: &'static str, color: Color) -> ProfileGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_colored(name, color) )
+}
+
+pub fn enter_blocking(name: &'static str) -> ProfileGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_blocking(name) )
+}
+
+pub fn enter_with<F, D>(name: &'static str, payload: F) -> ProfileGuard<'static>
+    where F: FnOnce() -> D, D: std::fmt::Display
+{
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_with(name, payload) )
+}
+
+pub fn enter_if(cond: bool, name: &'static str) -> ProfileGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_if(cond, name) )
+}
+
+pub fn enter_in(name: &'static str, category: Category) -> ProfileGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.enter_in(name, category) )
+}
+
+pub fn frame() -> FrameGuard<'static> {
+    HPROF.with(|p| unsafe { std::mem::transmute::<&Profiler, &'static Profiler>(p) }.frame() )
 }
 
 pub fn start_frame() {
     HPROF.with(|p| p.start_frame())
 }
 
+pub fn register_thread<S: Into<String>>(name: S) {
+    HPROF.with(|p| p.register(name))
+}
+
+pub fn register_worker(name: String) {
+    HPROF.with(|p| {
+        p.set_label(name.clone());
+        p.set_metadata(THREAD_NAME, name.clone());
+        p.register(name);
+    })
+}
+
 pub fn end_frame() {
     HPROF.with(|p| p.end_frame())
 }
 
-// used to do a pretty printing of time
-struct Nanoseconds(u64);
+pub fn annotate<S: Into<String>>(note: S) {
+    HPROF.with(|p| p.annotate(note))
+}
+
+pub fn add_bytes(n: u64) {
+    HPROF.with(|p| p.add_bytes(n))
+}
+
+pub fn tag_frame<K: Into<String>, V: Into<String>>(key: K, value: V) {
+    HPROF.with(|p| p.tag_frame(key, value))
+}
+
+pub fn end_frame_summary() -> Option<FrameSummary> {
+    HPROF.with(|p| p.end_frame_summary())
+}
+
+pub fn start_frame_of(kind: &'static str) {
+    HPROF.with(|p| p.start_frame_of(kind))
+}
+
+pub fn end_frame_of(kind: &'static str) {
+    HPROF.with(|p| p.end_frame_of(kind))
+}
+
+pub fn end_frame_of_summary(kind: &'static str) -> Option<FrameSummary> {
+    HPROF.with(|p| p.end_frame_of_summary(kind))
+}
+
+/// A time in ns, displayed in whichever of ns, us, ms or s reads best, such as `4.2ms`.
+///
+/// Width and alignment are honored, so times can be lined up in columns.
+pub struct Nanoseconds(pub u64);
 
 impl std::fmt::Display for Nanoseconds {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if self.0 < 1_000 {
-            write!(f, "{}ns", self.0)
+        let s = if self.0 < 1_000 {
+            format!("{}ns", self.0)
         } else if self.0 < 1_000_000 {
-            write!(f, "{:.1}us", self.0 as f64 / 1_000.)
+            format!("{:.1}us", self.0 as f64 / 1_000.)
         } else if self.0 < 1_000_000_000 {
-            write!(f, "{:.1}ms", self.0 as f64 / 1_000_000.)
+            format!("{:.1}ms", self.0 as f64 / 1_000_000.)
         } else {
-            write!(f, "{:.1}s", self.0 as f64 / 1_000_000_000.)
-        }
+            format!("{:.1}s", self.0 as f64 / 1_000_000_000.)
+        };
+        f.pad(&s)
+    }
+}
+
+impl From<Duration> for Nanoseconds {
+    fn from(d: Duration) -> Nanoseconds {
+        Nanoseconds(d.as_nanos() as u64)
     }
 }
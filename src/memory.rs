@@ -0,0 +1,102 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Memory used by the profiler itself.
+
+use std::borrow::Cow;
+use std::mem;
+
+use snapshot::{ProfileSnapshot, SnapshotNode};
+use timeline::TimelineEvent;
+use ProfileNode;
+
+/// The profiler's own memory footprint, as returned by `Profiler::memory_usage`.
+///
+/// Sizes in bytes are estimates: they count the capacity of the profiler's buffers and the
+/// strings they hold, but not allocator overhead, hooks or sinks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Number of nodes in the profile trees, including roots and the scopes entered between
+    /// frames.
+    pub nodes: usize,
+    /// Number of preallocated nodes not in use yet. See `Profiler::reserve_nodes`.
+    pub spare_nodes: usize,
+    /// Combined capacity of the nodes' child vectors, in entries.
+    pub child_capacity: usize,
+    /// Bytes used by the nodes, spare or not, and what they own.
+    pub node_bytes: usize,
+    /// Number of frames kept by the flight recorder.
+    pub history_frames: usize,
+    /// Bytes used by the flight recorder and the frames published to `FrameReader`s.
+    pub history_bytes: usize,
+    /// Capacity of the current frame's timeline event buffer, in events.
+    pub event_capacity: usize,
+    /// Bytes used by the current frame's timeline events and tags.
+    pub recording_bytes: usize,
+}
+
+impl MemoryStats {
+    /// Estimated bytes used in total.
+    pub fn total_bytes(&self) -> usize {
+        self.node_bytes + self.history_bytes + self.recording_bytes
+    }
+}
+
+// Add `node` and its descendants to `stats`, but not the trees mounted in them, which belong to
+// other profilers.
+pub fn add_tree(stats: &mut MemoryStats, node: &ProfileNode) {
+    let children = node.children.borrow();
+    stats.nodes += 1;
+    stats.child_capacity += children.capacity();
+    stats.node_bytes += node_bytes(node);
+    for child in children.iter() {
+        add_tree(stats, child);
+    }
+}
+
+// Bytes used by `node` itself, not counting its children.
+pub fn node_bytes(node: &ProfileNode) -> usize {
+    // reference counts are allocated along with the node
+    let mut bytes = mem::size_of::<ProfileNode>() + 2 * mem::size_of::<usize>();
+    bytes += node.children.borrow().capacity() * mem::size_of::<usize>();
+    bytes += node.mounted.borrow().capacity() * mem::size_of::<usize>();
+    bytes += node.label.borrow().as_ref().map_or(0, |l| l.capacity());
+    bytes += strings_bytes(&node.annotations.borrow());
+    bytes += node.recursive_calls.borrow().capacity() * mem::size_of::<u32>();
+    if let Some(ref samples) = *node.samples.borrow() {
+        bytes += mem::size_of_val(samples.samples());
+    }
+    bytes
+}
+
+// Bytes used by `events`, and the payloads they hold.
+pub fn events_bytes(events: &Vec<TimelineEvent>) -> usize {
+    events.capacity() * mem::size_of::<TimelineEvent>()
+        + events.iter().map(|e| e.payload.as_ref().map_or(0, |p| p.capacity())).sum::<usize>()
+}
+
+// Bytes used by `tags`.
+pub fn tags_bytes(tags: &Vec<(String, String)>) -> usize {
+    tags.capacity() * mem::size_of::<(String, String)>()
+        + tags.iter().map(|(k, v)| k.capacity() + v.capacity()).sum::<usize>()
+}
+
+// Bytes used by `strings`.
+fn strings_bytes(strings: &Vec<String>) -> usize {
+    strings.capacity() * mem::size_of::<String>() + strings.iter().map(|s| s.capacity()).sum::<usize>()
+}
+
+// Bytes used by `snapshot`, including itself.
+pub fn snapshot_bytes(snapshot: &ProfileSnapshot) -> usize {
+    let mut bytes = mem::size_of::<ProfileSnapshot>();
+    bytes += snapshot.nodes.capacity() * mem::size_of::<SnapshotNode>();
+    for node in &snapshot.nodes {
+        if let Cow::Owned(ref name) = node.name {
+            bytes += name.capacity();
+        }
+        bytes += strings_bytes(&node.annotations);
+    }
+    bytes + events_bytes(&snapshot.events) + tags_bytes(&snapshot.tags)
+}
@@ -0,0 +1,71 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Node names built at runtime, as used by `scope_fmt!`.
+//!
+//! Nodes are named by `&'static str`, so a formatted name has to live for the rest of the
+//! program. Each distinct name is only ever leaked once, and a small cache per callsite means
+//! names that come up every frame are neither formatted nor allocated again.
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+// every name returned by `intern`
+static INTERNED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+/// Number of names a `NameCache` keeps.
+pub const CACHE_SIZE: usize = 16;
+
+/// A `&'static str` with the same contents as `name`, leaking `name` unless an equal string was
+/// interned before.
+pub fn intern(name: String) -> &'static str {
+    let mut interned = INTERNED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(&name) = interned.get(&*name) {
+        return name
+    }
+    let name: &'static str = Box::leak(name.into_boxed_str());
+    interned.insert(name);
+    name
+}
+
+/// The names last formatted at one callsite on one thread, keyed by a hash of the values they
+/// were formatted from.
+pub struct NameCache {
+    // (hash of the values, name), oldest first
+    names: RefCell<Vec<(u64, &'static str)>>,
+}
+
+impl NameCache {
+    pub const fn new() -> NameCache {
+        NameCache { names: RefCell::new(Vec::new()) }
+    }
+
+    /// The name for the values `key`, calling `format` to build and `intern` it if it isn't
+    /// among the last `CACHE_SIZE` distinct names.
+    pub fn get<K: Hash + ?Sized, F: FnOnce() -> String>(&self, key: &K, format: F) -> &'static str {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let mut names = self.names.borrow_mut();
+        if let Some(&(_, name)) = names.iter().find(|&&(h, _)| h == hash) {
+            return name
+        }
+        let name = intern(format());
+        if names.len() == CACHE_SIZE {
+            names.remove(0);
+        }
+        names.push((hash, name));
+        name
+    }
+}
+
+impl Default for NameCache {
+    fn default() -> NameCache {
+        NameCache::new()
+    }
+}
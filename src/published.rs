@@ -0,0 +1,110 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Publishing completed frames to other threads.
+
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use memory;
+use sink::FrameInfo;
+use snapshot::ProfileSnapshot;
+use timeline::TimelineEvent;
+use ProfileNode;
+
+struct Shared {
+    frame: Option<Arc<ProfileSnapshot>>,
+    generation: u64,
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    // the data is always consistent, so a panic while holding the lock doesn't matter
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A handle to the last completed frame of a `Profiler`, usable from any thread.
+///
+/// The recording thread fills a separate buffer and swaps it in at `end_frame`, so readers
+/// always see a whole frame, never one that is still being recorded.
+#[derive(Clone)]
+pub struct FrameReader {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl FrameReader {
+    /// A copy of the last completed frame, if one has completed since the reader was created.
+    pub fn latest(&self) -> Option<ProfileSnapshot> {
+        lock(&self.shared).frame.as_ref().map(|frame| (**frame).clone())
+    }
+
+    /// The last completed frame, shared with the reader rather than copied.
+    ///
+    /// Cloning the result is cheap, so it can be handed to worker threads for heavy analysis or
+    /// export while the recording thread carries on. The recording thread reuses the frame's
+    /// storage once every clone is dropped, and allocates a new one in the meantime.
+    pub fn latest_shared(&self) -> Option<Arc<ProfileSnapshot>> {
+        lock(&self.shared).frame.clone()
+    }
+
+    /// Call `f` with the last completed frame, without copying it.
+    pub fn with_latest<R, F: FnOnce(&ProfileSnapshot) -> R>(&self, f: F) -> Option<R> {
+        self.latest_shared().map(|frame| f(&frame))
+    }
+
+    /// The number of frames completed since the reader was created.
+    ///
+    /// Useful for checking whether there is anything new to display.
+    pub fn generation(&self) -> u64 {
+        lock(&self.shared).generation
+    }
+}
+
+/// The recording side of a `FrameReader`.
+pub struct FramePublisher {
+    shared: Arc<Mutex<Shared>>,
+    // the frame being filled, never shared with readers
+    back: Arc<ProfileSnapshot>,
+}
+
+impl FramePublisher {
+    pub fn new() -> FramePublisher {
+        FramePublisher {
+            shared: Arc::new(Mutex::new(Shared { frame: None, generation: 0 })),
+            back: Arc::default(),
+        }
+    }
+
+    // Bytes used by the published frame and the one being filled.
+    pub fn heap_size(&self) -> usize {
+        memory::snapshot_bytes(&self.back) + lock(&self.shared).frame.as_ref().map_or(0, |f| memory::snapshot_bytes(f))
+    }
+
+    pub fn reader(&self) -> FrameReader {
+        FrameReader { shared: self.shared.clone() }
+    }
+
+    /// Publish the tree rooted at `root`, along with its timeline `events` and the index, start
+    /// time and tags in `info`, as the latest completed frame.
+    pub fn publish(&mut self, info: &FrameInfo, root: &ProfileNode, events: &[TimelineEvent]) {
+        {
+            let back = Arc::make_mut(&mut self.back);
+            back.fill(root);
+            back.index = info.index;
+            back.wall_time = Some(info.start);
+            back.end_wall_time = Some(info.timestamp);
+            back.events.extend_from_slice(events);
+            back.tags.extend_from_slice(info.tags);
+        }
+        let mut shared = lock(&self.shared);
+        // readers can only get at the old frame through the lock, so if they hold no other
+        // reference to it now, it can be filled next time
+        let old = match shared.frame.take() {
+            Some(old) if Arc::strong_count(&old) == 1 => old,
+            _ => Arc::default(),
+        };
+        shared.frame = Some(mem::replace(&mut self.back, old));
+        shared.generation += 1;
+    }
+}
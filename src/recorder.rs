@@ -0,0 +1,83 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! A black-box flight recorder for recent frames.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+
+use recording::{self, Recording};
+use sink::FrameInfo;
+use snapshot::ProfileSnapshot;
+use timeline::TimelineEvent;
+use ProfileNode;
+
+/// A fixed-size ring buffer holding fully detailed copies of the most recent frames.
+///
+/// Once full, recording a frame overwrites the oldest one, reusing its storage. The buffer can be
+/// flushed to a recording at any time, for example from a crash handler or on a hotkey.
+#[derive(Debug)]
+pub struct FlightRecorder {
+    frames: VecDeque<ProfileSnapshot>,
+    capacity: usize,
+}
+
+impl FlightRecorder {
+    /// Create a recorder that keeps the last `capacity` frames.
+    pub fn new(capacity: usize) -> FlightRecorder {
+        FlightRecorder { frames: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// The maximum number of frames kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Record the tree rooted at `root`, along with its timeline `events` and the index, start
+    /// time and tags in `info`, as the newest frame.
+    pub fn record(&mut self, info: &FrameInfo, root: &ProfileNode, events: &[TimelineEvent]) {
+        if self.capacity == 0 {
+            return
+        }
+        let mut snapshot = if self.frames.len() == self.capacity {
+            self.frames.pop_front().unwrap()
+        } else {
+            ProfileSnapshot::default()
+        };
+        snapshot.fill(root);
+        snapshot.index = info.index;
+        snapshot.wall_time = Some(info.start);
+        snapshot.end_wall_time = Some(info.timestamp);
+        snapshot.events.extend_from_slice(events);
+        snapshot.tags.extend_from_slice(info.tags);
+        self.frames.push_back(snapshot);
+    }
+
+    /// The recorded frames, oldest first.
+    pub fn frames(&self) -> &VecDeque<ProfileSnapshot> {
+        &self.frames
+    }
+
+    /// Copy the recorded frames into a `Recording`.
+    pub fn to_recording(&self) -> Recording {
+        Recording { metadata: BTreeMap::new(), frames: self.frames.iter().cloned().collect() }
+    }
+
+    /// Write the recorded frames to `w` in recording format, along with the session's
+    /// `metadata`, and empty the buffer.
+    pub fn flush_to<W: Write>(&mut self, w: &mut W, metadata: &BTreeMap<String, String>) -> io::Result<()> {
+        recording::write_header(w, metadata)?;
+        for frame in &self.frames {
+            recording::write_frame(w, frame)?;
+        }
+        self.frames.clear();
+        Ok(())
+    }
+
+    /// Discard all recorded frames.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
@@ -0,0 +1,475 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Recordings: sequences of frames that can be saved and loaded.
+//!
+//! # File format
+//!
+//! Recordings are stored as UTF-8 text, one record per line, with tab-separated fields. The
+//! first line is the header `hprof-recording 2`, followed by any metadata of the session as
+//! `meta <key> <value>` lines. Each frame starts with a line
+//!
+//! ```text
+//! frame <start ns> <index> [<wall-clock ns> [<end wall-clock ns>]]
+//! ```
+//!
+//! where `index` counts the frames of the session from 0, and the wall-clock times the frame
+//! started and ended, in ns since the Unix epoch, are only written when known. Recordings written
+//! before these fields existed are read with each frame's index in the recording. The `frame`
+//! line is followed by one `node` line per node in depth-first order:
+//!
+//! ```text
+//! node <depth> <id> <calls> <total ns> <category> <color> <name>
+//! ```
+//!
+//! `category` and `color` are empty when the node has none; colors are written as `#rrggbb`.
+//! Notes attached to a node follow it as `annotation <text>` lines. Its CPU time and blocked
+//! time in ns, when known, follow it as `cputime <ns>` and `blocked <ns>` lines.
+//! Backslashes, tabs and newlines in names, notes, metadata keys and values are escaped as `\\`,
+//! `\t` and `\n`.
+//!
+//! Tags of a frame, if any, follow its `frame` line as `tag <key> <value>` lines, escaped like
+//! metadata.
+//!
+//! Timeline events, if any, follow the nodes of their frame as `event` lines, where `kind` is
+//! `enter` or `leave`:
+//!
+//! ```text
+//! event <kind> <node id> <timestamp ns> [<payload>]
+//! ```
+//!
+//! The payload, given to `Profiler::enter_with`, is only written when the event has one, and is
+//! escaped like names. The CPU core an event happened on, if recorded, follows it as a
+//! `cpu <core>` line.
+//!
+//! Version 1 recordings, which have no metadata, can still be read.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use compare::{self, CompareOptions, Comparison};
+use delta;
+use snapshot::{ProfileSnapshot, SnapshotNode};
+use timeline::{EventKind, TimelineEvent};
+use Color;
+
+const HEADER: &str = "hprof-recording 2";
+const HEADER_V1: &str = "hprof-recording 1";
+
+/// A sequence of recorded frames.
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    /// Information about the session the frames were recorded in, such as the build or the
+    /// hardware. See `Profiler::set_metadata`.
+    pub metadata: BTreeMap<String, String>,
+    pub frames: Vec<ProfileSnapshot>,
+}
+
+impl Recording {
+    /// Create an empty recording.
+    pub fn new() -> Recording {
+        Recording::default()
+    }
+
+    /// Write the recording to `w`.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_header(w, &self.metadata)?;
+        for frame in &self.frames {
+            write_frame(w, frame)?;
+        }
+        Ok(())
+    }
+
+    /// Write the recording to the file at `path`, replacing it if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    /// Write the recording to `w` in the much smaller delta-encoded format. See the `delta`
+    /// module.
+    pub fn write_delta_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        delta::write_delta(w, self)
+    }
+
+    /// Write the recording to the file at `path` in the delta-encoded format, replacing it if it
+    /// exists.
+    pub fn save_delta<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_delta_to(&mut w)?;
+        w.flush()
+    }
+
+    /// Read a recording from `r`, in either the text or the delta-encoded format.
+    pub fn read_from<R: BufRead>(mut r: R) -> io::Result<Recording> {
+        if r.fill_buf()?.starts_with(delta::MAGIC) {
+            return delta::read_delta(r)
+        }
+        let mut lines = r.lines();
+        match lines.next() {
+            Some(line) => match line?.trim_end() {
+                HEADER | HEADER_V1 => {}
+                _ => return Err(invalid("not an hprof recording")),
+            },
+            None => return Err(invalid("empty recording")),
+        }
+
+        let mut recording = Recording::new();
+        // stack of (depth, index) for the nodes on the path to the last node read
+        let mut stack: Vec<(u32, usize)> = Vec::new();
+        for line in lines {
+            let line = line?;
+            let mut fields = line.split('\t');
+            match fields.next() {
+                Some("meta") => {
+                    let key = unescape(fields.next().ok_or_else(|| invalid("missing field"))?);
+                    let value = unescape(fields.next().ok_or_else(|| invalid("missing field"))?);
+                    recording.metadata.insert(key.into_owned(), value.into_owned());
+                }
+                Some("frame") => {
+                    let start_time = parse(fields.next())?;
+                    let index = match fields.next() {
+                        Some(index) => parse(Some(index))?,
+                        None => recording.frames.len() as u64,
+                    };
+                    let wall_time = match fields.next() {
+                        Some(ns) => Some(UNIX_EPOCH + Duration::from_nanos(parse(Some(ns))?)),
+                        None => None,
+                    };
+                    let end_wall_time = match fields.next() {
+                        Some(ns) => Some(UNIX_EPOCH + Duration::from_nanos(parse(Some(ns))?)),
+                        None => None,
+                    };
+                    recording.frames.push(ProfileSnapshot {
+                        start_time,
+                        index,
+                        wall_time,
+                        end_wall_time,
+                        ..ProfileSnapshot::default()
+                    });
+                    stack.clear();
+                }
+                Some("tag") => {
+                    let frame = match recording.frames.last_mut() {
+                        Some(frame) => frame,
+                        None => return Err(invalid("tag outside of a frame")),
+                    };
+                    let key = unescape(fields.next().ok_or_else(|| invalid("missing field"))?);
+                    let value = unescape(fields.next().ok_or_else(|| invalid("missing field"))?);
+                    frame.tags.push((key.into_owned(), value.into_owned()));
+                }
+                Some("node") => {
+                    let frame = match recording.frames.last_mut() {
+                        Some(frame) => frame,
+                        None => return Err(invalid("node outside of a frame")),
+                    };
+                    let depth: u32 = parse(fields.next())?;
+                    while stack.last().map(|&(d, _)| d >= depth).unwrap_or(false) {
+                        stack.pop();
+                    }
+                    let parent = stack.last().map(|&(_, i)| i);
+                    if parent.is_none() && depth != 0 {
+                        return Err(invalid("node has no parent"))
+                    }
+                    let node = SnapshotNode {
+                        depth,
+                        parent,
+                        id: parse(fields.next())?,
+                        calls: parse(fields.next())?,
+                        total_time: parse(fields.next())?,
+                        category: optional(fields.next()).map(unescape),
+                        color: match optional(fields.next()) {
+                            Some(s) => Some(parse_color(s)?),
+                            None => None,
+                        },
+                        name: unescape(fields.next().ok_or_else(|| invalid("missing field"))?),
+                        annotations: Vec::new(),
+                        location: None,
+                        cpu_time: None,
+                        blocked_time: None,
+                        bytes: 0,
+                    };
+                    stack.push((depth, frame.nodes.len()));
+                    frame.nodes.push(node);
+                }
+                Some("annotation") => {
+                    let node = match recording.frames.last_mut().and_then(|f| f.nodes.last_mut()) {
+                        Some(node) => node,
+                        None => return Err(invalid("annotation outside of a node")),
+                    };
+                    let text = unescape(fields.next().ok_or_else(|| invalid("missing field"))?);
+                    node.annotations.push(text.into_owned());
+                }
+                Some("cputime") => {
+                    let node = match recording.frames.last_mut().and_then(|f| f.nodes.last_mut()) {
+                        Some(node) => node,
+                        None => return Err(invalid("cputime outside of a node")),
+                    };
+                    node.cpu_time = Some(parse(fields.next())?);
+                }
+                Some("blocked") => {
+                    let node = match recording.frames.last_mut().and_then(|f| f.nodes.last_mut()) {
+                        Some(node) => node,
+                        None => return Err(invalid("blocked outside of a node")),
+                    };
+                    node.blocked_time = Some(parse(fields.next())?);
+                }
+                Some("event") => {
+                    let frame = match recording.frames.last_mut() {
+                        Some(frame) => frame,
+                        None => return Err(invalid("event outside of a frame")),
+                    };
+                    let kind = match fields.next() {
+                        Some("enter") => EventKind::Enter,
+                        Some("leave") => EventKind::Leave,
+                        _ => return Err(invalid("unknown event kind")),
+                    };
+                    frame.events.push(TimelineEvent {
+                        kind,
+                        id: parse(fields.next())?,
+                        time: parse(fields.next())?,
+                        payload: fields.next().map(|s| unescape(s).into_owned()),
+                        cpu: None,
+                    });
+                }
+                Some("cpu") => {
+                    let event = match recording.frames.last_mut().and_then(|f| f.events.last_mut()) {
+                        Some(event) => event,
+                        None => return Err(invalid("cpu outside of an event")),
+                    };
+                    event.cpu = Some(parse(fields.next())?);
+                }
+                Some("") => {}
+                _ => return Err(invalid("unknown record")),
+            }
+        }
+        Ok(recording)
+    }
+
+    /// Read a recording from the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Recording> {
+        Recording::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Combine several recordings, for example from different runs or machines, into one
+    /// holding all of their frames in order.
+    ///
+    /// Where recordings have different metadata values for the same key, the first one wins.
+    pub fn merge<I: IntoIterator<Item=Recording>>(recordings: I) -> Recording {
+        let mut merged = Recording::new();
+        for recording in recordings {
+            for (key, value) in recording.metadata {
+                merged.metadata.entry(key).or_insert(value);
+            }
+            merged.frames.extend(recording.frames);
+        }
+        merged
+    }
+
+    /// A copy of the recording with only the frames whose tag `key` has the value `value`.
+    pub fn filter_tag(&self, key: &str, value: &str) -> Recording {
+        Recording {
+            metadata: self.metadata.clone(),
+            frames: self.frames.iter().filter(|f| f.tag(key) == Some(value)).cloned().collect(),
+        }
+    }
+
+    /// The index of the frame that was in progress at the wall-clock time `time`, such as the
+    /// time of a spike in a server log or a player's bug report. See `ProfileSnapshot::spans`.
+    pub fn frame_at(&self, time: SystemTime) -> Option<usize> {
+        self.frames.iter().position(|f| f.spans(time))
+    }
+
+    /// Compare the mean time per frame of every path in `other` with this recording, with the
+    /// default thresholds of `CompareOptions`.
+    ///
+    /// This recording is taken as the baseline, so paths that take longer in `other` are
+    /// regressions. Recordings of different lengths can be compared.
+    pub fn compare(&self, other: &Recording) -> Comparison {
+        self.compare_with(other, &CompareOptions::default())
+    }
+
+    /// Compare `other` with this recording like `compare`, counting only changes above the
+    /// thresholds in `opts`.
+    pub fn compare_with(&self, other: &Recording, opts: &CompareOptions) -> Comparison {
+        compare::compare(self, other, opts)
+    }
+
+    /// Statistics of every path that appears in the recording, other than the root, in the
+    /// order each first appears.
+    pub fn path_stats(&self) -> Vec<PathStats> {
+        let mut stats: Vec<PathStats> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for frame in &self.frames {
+            for (idx, node) in frame.nodes.iter().enumerate().skip(1) {
+                let path = frame.path(idx);
+                let i = match index.get(&path) {
+                    Some(&i) => i,
+                    None => {
+                        index.insert(path.clone(), stats.len());
+                        stats.push(PathStats {
+                            path,
+                            frames: 0,
+                            calls: 0,
+                            total: Duration::from_nanos(0),
+                            min: Duration::from_nanos(u64::MAX),
+                            max: Duration::from_nanos(0),
+                        });
+                        stats.len() - 1
+                    }
+                };
+                let s = &mut stats[i];
+                s.frames += 1;
+                s.calls += node.calls as u64;
+                let total = Duration::from_nanos(node.total_time);
+                s.total += total;
+                s.min = s.min.min(total);
+                s.max = s.max.max(total);
+            }
+        }
+        stats
+    }
+}
+
+/// Statistics of one path over all the frames of a recording it appears in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathStats {
+    /// The `/`-separated path, not including the root.
+    pub path: String,
+    /// Number of frames containing the path.
+    pub frames: u64,
+    /// Number of calls over all those frames.
+    pub calls: u64,
+    /// Total time over all those frames.
+    pub total: Duration,
+    /// Shortest time of the path in a single frame.
+    pub min: Duration,
+    /// Longest time of the path in a single frame.
+    pub max: Duration,
+}
+
+impl PathStats {
+    /// Mean time per frame containing the path.
+    pub fn mean(&self) -> Duration {
+        match self.frames {
+            0 => Duration::from_nanos(0),
+            frames => Duration::from_nanos((self.total.as_nanos() / frames as u128) as u64),
+        }
+    }
+
+    /// Mean number of calls per frame containing the path.
+    pub fn calls_per_frame(&self) -> f64 {
+        if self.frames == 0 { 0.0 } else { self.calls as f64 / self.frames as f64 }
+    }
+}
+
+/// Write the lines that start every recording: the header and the session's `metadata`.
+pub fn write_header<W: Write>(w: &mut W, metadata: &BTreeMap<String, String>) -> io::Result<()> {
+    writeln!(w, "{}", HEADER)?;
+    for (key, value) in metadata {
+        writeln!(w, "meta\t{}\t{}", escape(key), escape(value))?;
+    }
+    Ok(())
+}
+
+/// Write a single frame in recording format, without the header.
+pub fn write_frame<W: Write>(w: &mut W, frame: &ProfileSnapshot) -> io::Result<()> {
+    write!(w, "frame\t{}\t{}", frame.start_time, frame.index)?;
+    if let Some(time) = frame.unix_time() {
+        write!(w, "\t{}", time)?;
+        if let Some(end) = frame.unix_end_time() {
+            write!(w, "\t{}", end)?;
+        }
+    }
+    writeln!(w)?;
+    for (key, value) in &frame.tags {
+        writeln!(w, "tag\t{}\t{}", escape(key), escape(value))?;
+    }
+    for node in &frame.nodes {
+        writeln!(w, "node\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                 node.depth, node.id, node.calls, node.total_time,
+                 node.category.as_ref().map(|c| escape(c)).unwrap_or(Cow::Borrowed("")),
+                 node.color.map(|c| c.to_string()).unwrap_or(String::new()),
+                 escape(&node.name))?;
+        for note in &node.annotations {
+            writeln!(w, "annotation\t{}", escape(note))?;
+        }
+        if let Some(cpu) = node.cpu_time {
+            writeln!(w, "cputime\t{}", cpu)?;
+        }
+        if let Some(blocked) = node.blocked_time {
+            writeln!(w, "blocked\t{}", blocked)?;
+        }
+    }
+    for event in &frame.events {
+        let kind = match event.kind {
+            EventKind::Enter => "enter",
+            EventKind::Leave => "leave",
+        };
+        write!(w, "event\t{}\t{}\t{}", kind, event.id, event.time)?;
+        if let Some(ref payload) = event.payload {
+            write!(w, "\t{}", escape(payload))?;
+        }
+        writeln!(w)?;
+        if let Some(cpu) = event.cpu {
+            writeln!(w, "cpu\t{}", cpu)?;
+        }
+    }
+    Ok(())
+}
+
+fn escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['\\', '\t', '\n']) {
+        return Cow::Borrowed(s)
+    }
+    Cow::Owned(s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n"))
+}
+
+fn unescape(s: &str) -> Cow<'static, str> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn optional(field: Option<&str>) -> Option<&str> {
+    match field {
+        Some("") | None => None,
+        Some(s) => Some(s),
+    }
+}
+
+fn parse<T: ::std::str::FromStr>(field: Option<&str>) -> io::Result<T> {
+    field.and_then(|s| s.parse().ok()).ok_or_else(|| invalid("malformed number"))
+}
+
+fn parse_color(s: &str) -> io::Result<Color> {
+    let bad = || invalid("malformed color");
+    if s.len() != 7 || !s.starts_with('#') {
+        return Err(bad())
+    }
+    let c = u32::from_str_radix(&s[1..], 16).map_err(|_| bad())?;
+    Ok(Color::rgb((c >> 16) as u8, (c >> 8) as u8, c as u8))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,136 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Collecting profiles from many threads.
+//!
+//! Every thread keeps recording into its own profiler, so `enter` and `leave` never touch memory
+//! shared with other threads. A registered profiler also has a slot here, which other threads
+//! use to ask for its tree. The owning thread checks for a request with a single relaxed atomic
+//! load at quiet points, and only takes the slot's lock when it publishes.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use snapshot::ProfileSnapshot;
+
+static REGISTRY: Mutex<Vec<Arc<Slot>>> = Mutex::new(Vec::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // the data is always consistent, so a panic while holding the lock doesn't matter
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The part of a registered profiler shared with the registry.
+pub struct Slot {
+    name: String,
+    thread: ThreadId,
+    // set by `request_thread_snapshots`, cleared by the owning thread when it publishes
+    requested: AtomicBool,
+    // number of snapshots published
+    generation: AtomicU64,
+    finished: AtomicBool,
+    latest: Mutex<Option<Arc<ProfileSnapshot>>>,
+}
+
+impl Slot {
+    /// Whether another thread asked for a snapshot since the last one was published.
+    #[inline]
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Publish `snapshot` as the thread's latest, and mark the thread finished if `finished`.
+    pub fn publish(&self, snapshot: ProfileSnapshot, finished: bool) {
+        self.requested.store(false, Ordering::Relaxed);
+        *lock(&self.latest) = Some(Arc::new(snapshot));
+        if finished {
+            self.finished.store(true, Ordering::Relaxed);
+        }
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Add a slot for the calling thread, named `name`.
+pub fn register(name: String) -> Arc<Slot> {
+    let slot = Arc::new(Slot {
+        name,
+        thread: thread::current().id(),
+        requested: AtomicBool::new(false),
+        generation: AtomicU64::new(0),
+        finished: AtomicBool::new(false),
+        latest: Mutex::new(None),
+    });
+    lock(&REGISTRY).push(slot.clone());
+    slot
+}
+
+/// The latest tree published by a registered thread, as returned by `thread_snapshots`.
+#[derive(Clone, Debug)]
+pub struct ThreadSnapshot {
+    /// Name the thread was registered with.
+    pub name: String,
+    pub thread: ThreadId,
+    /// Number of snapshots the thread has published, this one included.
+    pub generation: u64,
+    /// Whether the thread's profiler has been dropped, so this snapshot is its last.
+    pub finished: bool,
+    pub snapshot: Arc<ProfileSnapshot>,
+}
+
+/// Ask every registered thread that is still running to publish a snapshot of its tree the next
+/// time it leaves its outermost scope or ends a frame.
+pub fn request_thread_snapshots() {
+    for slot in lock(&REGISTRY).iter() {
+        if !slot.finished.load(Ordering::Relaxed) {
+            slot.requested.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The latest snapshot published by each registered thread, in the order they registered.
+///
+/// Threads that haven't published anything yet are left out.
+pub fn thread_snapshots() -> Vec<ThreadSnapshot> {
+    lock(&REGISTRY).iter().filter_map(|slot| {
+        let generation = slot.generation.load(Ordering::Acquire);
+        lock(&slot.latest).clone().map(|snapshot| ThreadSnapshot {
+            name: slot.name.clone(),
+            thread: slot.thread,
+            generation,
+            finished: slot.finished.load(Ordering::Relaxed),
+            snapshot,
+        })
+    }).collect()
+}
+
+/// Request snapshots from every registered thread that is still running, then wait up to
+/// `timeout` for each to publish one before returning `thread_snapshots`.
+///
+/// Threads busy in a long scope, or idle outside of any, may not answer in time; their previous
+/// snapshot is returned, if any.
+pub fn collect_threads(timeout: Duration) -> Vec<ThreadSnapshot> {
+    let before: Vec<(Arc<Slot>, u64)> = lock(&REGISTRY).iter()
+        .map(|slot| (slot.clone(), slot.generation.load(Ordering::Acquire)))
+        .collect();
+    request_thread_snapshots();
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        let answered = before.iter().all(|&(ref slot, generation)| {
+            slot.finished.load(Ordering::Relaxed) || slot.generation.load(Ordering::Acquire) != generation
+        });
+        if answered {
+            break
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    thread_snapshots()
+}
+
+/// Forget the threads whose profilers have been dropped, along with their last snapshots.
+pub fn remove_finished_threads() {
+    lock(&REGISTRY).retain(|slot| !slot.finished.load(Ordering::Relaxed));
+}
@@ -0,0 +1,660 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Configurable text reports.
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::io::{self, Write};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use {Hotspot, Nanoseconds, ProfileNode, ProfileSnapshot};
+
+/// Order in which the children of each node are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The order in which the nodes were first entered.
+    Insertion,
+    /// Longest total time first.
+    Total,
+    /// Longest self time first.
+    SelfTime,
+    /// Most calls first.
+    Calls,
+    /// Longest average time per call first.
+    Average,
+    /// Alphabetical by name.
+    Name,
+}
+
+/// Unit that times are printed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Units {
+    /// Whichever unit suits each value best.
+    Auto,
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+/// Options for `Profiler::print_timing_with` and `Profiler::write_timing_with`.
+///
+/// The default options give the same report as `Profiler::print_timing`.
+#[derive(Clone, Debug)]
+pub struct PrintOptions {
+    /// Order of the children of each node.
+    pub sort: SortOrder,
+    /// Whether to reverse `sort`, for example to list the cheapest nodes first.
+    pub reverse: bool,
+    /// Deepest level to print, where the children of the root are at depth 1.
+    pub max_depth: Option<u32>,
+    /// Nodes taking less than this percentage of their parent are left out, along with their
+    /// children.
+    pub min_percent: f64,
+    /// Unit that times are printed in.
+    pub units: Units,
+    /// Whether to highlight expensive nodes and anomalies with ANSI color codes.
+    pub colors: bool,
+    /// Whether to include a column with each node's self time, the time not spent in any of its
+    /// children.
+    pub self_time: bool,
+    /// Whether to include a column with each node's share of the whole frame, next to its share
+    /// of its parent.
+    pub percent_of_root: bool,
+    /// Whether to include a column with the change in each node's total since the previous frame.
+    pub delta: bool,
+    /// Whether to show the average and maximum number of calls per frame over the whole session,
+    /// so that a call count that exploded in one frame is still visible after it.
+    pub calls_per_frame: bool,
+    /// Width in characters of a bar drawn after the columns of each node, showing its share of
+    /// the whole frame, or `None` for no bars.
+    pub bars: Option<u32>,
+    /// Number of past frames to draw a sparkline of each node's total over, or `None` for no
+    /// sparklines. Needs the frames to be kept by `Profiler::enable_flight_recorder`.
+    pub sparklines: Option<usize>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> PrintOptions {
+        PrintOptions {
+            sort: SortOrder::Insertion,
+            reverse: false,
+            max_depth: None,
+            min_percent: 0.0,
+            units: Units::Auto,
+            colors: false,
+            self_time: true,
+            percent_of_root: false,
+            delta: false,
+            calls_per_frame: false,
+            bars: None,
+            sparklines: None,
+        }
+    }
+}
+
+struct Time(u64, Units);
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.1 {
+            Units::Auto => write!(f, "{}", Nanoseconds(self.0)),
+            Units::Nanoseconds => write!(f, "{}ns", self.0),
+            Units::Microseconds => write!(f, "{:.3}us", self.0 as f64 / 1_000.),
+            Units::Milliseconds => write!(f, "{:.3}ms", self.0 as f64 / 1_000_000.),
+            Units::Seconds => write!(f, "{:.3}s", self.0 as f64 / 1_000_000_000.),
+        }
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+fn paint<W: Write>(w: &mut W, opts: &PrintOptions, color: Option<&str>, args: fmt::Arguments) -> io::Result<()> {
+    match (opts.colors, color) {
+        (true, Some(color)) => write!(w, "{}{}{}", color, args, RESET),
+        _ => w.write_fmt(args),
+    }
+}
+
+fn percent_color(percent: f64) -> Option<&'static str> {
+    if percent >= 50.0 {
+        Some(RED)
+    } else if percent >= 20.0 {
+        Some(YELLOW)
+    } else {
+        None
+    }
+}
+
+// Percentage of `parent_total`, or of the node's own total if it has no parent.
+fn percent_of_parent(node: &ProfileNode, parent_total: Option<u64>) -> f64 {
+    let parent_time = parent_total.unwrap_or(node.total_time.get()) as f64;
+    100.0 * (node.total_time.get() as f64 / parent_time)
+}
+
+// One line of the report, with every column already formatted so widths can be measured.
+struct Row {
+    indent: u32,
+    name: String,
+    calls: String,
+    avg: String,
+    total: String,
+    percent: (String, Option<&'static str>),
+    root_percent: String,
+    self_time: String,
+    delta: (String, Option<&'static str>),
+    // share of the whole frame, from 0 to 1, drawn as a bar
+    share: f64,
+    // Everything after the columns, such as anomalies and percentiles.
+    extra: Vec<(String, Option<&'static str>)>,
+    dim: bool,
+}
+
+/// Write a heading for each of `roots` followed by a table of its children and their
+/// descendants, at indentation `indent`.
+///
+/// Columns are aligned across all of the tables.
+pub fn write_tables<W: Write>(w: &mut W, roots: &[&ProfileNode], indent: u32, opts: &PrintOptions) -> io::Result<()> {
+    write_tables_with_history(w, roots, &[], indent, opts)
+}
+
+/// Like `write_tables`, with a sparkline of each node's total over the frames of `history`,
+/// oldest first, if `opts.sparklines` is set.
+pub fn write_tables_with_history<W: Write>(w: &mut W, roots: &[&ProfileNode], history: &[&ProfileSnapshot],
+                                           indent: u32, opts: &PrintOptions) -> io::Result<()> {
+    let history = if opts.sparklines.is_some() { totals_by_id(history) } else { HashMap::new() };
+    let tables: Vec<Vec<Row>> = roots.iter().map(|root| {
+        let mut rows = Vec::new();
+        rows_below(&mut rows, root, root.total_time.get(), &history, indent, 1, opts);
+        rows
+    }).collect();
+    let widths = Widths::of(tables.iter().flat_map(|t| t.iter()), indent, &TIMING);
+    for (root, rows) in roots.iter().zip(&tables) {
+        writeln!(w, "Timing information for {}:", root.display_name())?;
+        write_rows(w, rows, &widths, indent, &TIMING, opts)?;
+    }
+    Ok(())
+}
+
+/// Write a summary of the whole session for each of `roots`, from the lifetime totals of their
+/// nodes, at indentation `indent`.
+///
+/// Each root comes with the number of frames it covers, if any, to give the average time per
+/// frame. Only `sort`, `max_depth`, `min_percent`, `units` and `colors` apply.
+pub fn write_session<W: Write>(w: &mut W, roots: &[(&ProfileNode, Option<u64>)], indent: u32, opts: &PrintOptions)
+    -> io::Result<()> {
+    let opts = &PrintOptions { self_time: true, percent_of_root: false, delta: false, bars: None, ..opts.clone() };
+    let tables: Vec<Vec<Row>> = roots.iter().map(|&(root, frames)| {
+        let mut rows = Vec::new();
+        session_rows(&mut rows, root, frames, indent, 1, opts);
+        rows
+    }).collect();
+    let widths = Widths::of(tables.iter().flat_map(|t| t.iter()), indent, &SESSION);
+    for (&(root, frames), rows) in roots.iter().zip(&tables) {
+        match frames {
+            Some(frames) => writeln!(w, "Session summary for {} over {} frames:", root.display_name(), frames)?,
+            None => writeln!(w, "Session summary for {}:", root.display_name())?,
+        }
+        write_rows(w, rows, &widths, indent, &SESSION, opts)?;
+    }
+    Ok(())
+}
+
+/// Write a table of `hotspots` at indentation `indent`, with percentages of `frame_total`.
+///
+/// Only `min_percent`, `units`, `colors` and `bars` apply, with bars showing shares of the
+/// self time.
+pub fn write_hotspots<W: Write>(w: &mut W, hotspots: &[Hotspot], frame_total: Duration, indent: u32,
+                                opts: &PrintOptions) -> io::Result<()> {
+    let opts = &PrintOptions { self_time: true, percent_of_root: false, delta: false, ..opts.clone() };
+    let rows: Vec<Row> = hotspots.iter().filter_map(|hotspot| {
+        let percent = 100.0 * (hotspot.self_time.as_secs_f64() / frame_total.as_secs_f64());
+        if percent < opts.min_percent {
+            return None
+        }
+        Some(Row {
+            indent,
+            name: hotspot.name.to_string(),
+            calls: hotspot.calls.to_string(),
+            avg: Time((hotspot.total.as_nanos() as f64 / hotspot.calls as f64) as u64, opts.units).to_string(),
+            total: Time(hotspot.total.as_nanos() as u64, opts.units).to_string(),
+            percent: (format!("{:.1}%", percent), percent_color(percent)),
+            root_percent: String::new(),
+            self_time: Time(hotspot.self_time.as_nanos() as u64, opts.units).to_string(),
+            delta: (String::new(), None),
+            share: percent / 100.0,
+            extra: Vec::new(),
+            dim: false,
+        })
+    }).collect();
+    let widths = Widths::of(&rows, indent, &HOTSPOTS);
+    write_rows(w, &rows, &widths, indent, &HOTSPOTS, opts)
+}
+
+// Collect the lifetime rows for the children of `node`, like `rows_below`.
+fn session_rows(rows: &mut Vec<Row>, node: &ProfileNode, frames: Option<u64>, indent: u32, depth: u32,
+                opts: &PrintOptions) {
+    if opts.max_depth.map(|max| depth > max).unwrap_or(false) {
+        return
+    }
+    let lifetime = |n: &ProfileNode| n.lifetime_time().as_nanos() as u64;
+    let mut children: Vec<Rc<ProfileNode>> = node.all_children();
+    sort_nodes(&mut children, opts, |n| {
+        let children: u64 = n.all_children().iter().map(|c| lifetime(c)).sum();
+        (lifetime(n), lifetime(n).saturating_sub(children), n.lifetime_calls())
+    });
+    let parent_total = lifetime(node);
+    for child in &children {
+        let total = lifetime(child);
+        let calls = child.lifetime_calls();
+        let percent = 100.0 * (total as f64 / parent_total as f64);
+        if calls == 0 || percent < opts.min_percent {
+            continue
+        }
+        rows.push(Row {
+            indent,
+            name: child.name.to_string(),
+            calls: calls.to_string(),
+            avg: Time((total as f64 / calls as f64) as u64, opts.units).to_string(),
+            total: Time(total, opts.units).to_string(),
+            percent: (format!("{:.1}%", percent), percent_color(percent)),
+            root_percent: String::new(),
+            self_time: match frames {
+                Some(frames) if frames > 0 => Time(total / frames, opts.units).to_string(),
+                _ => String::new(),
+            },
+            delta: (String::new(), None),
+            share: 0.0,
+            extra: Vec::new(),
+            dim: false,
+        });
+        session_rows(rows, child, frames, indent + 2, depth + 1, opts);
+    }
+}
+
+/// Write `node` and its descendants, with `node` at indentation `indent`.
+pub fn write_node<W: Write>(w: &mut W, node: &ProfileNode, indent: u32, opts: &PrintOptions) -> io::Result<()> {
+    let parent_total = node.parent().map(|p| p.total_time.get());
+    let mut root = node.parent();
+    while let Some(parent) = root.as_ref().and_then(|r| r.parent()) {
+        root = Some(parent);
+    }
+    let root_total = root.map(|r| r.total_time.get()).unwrap_or(node.total_time.get());
+    let mut rows = vec![row(node, parent_total, root_total, indent, opts)];
+    rows_below(&mut rows, node, root_total, &HashMap::new(), indent + 2, 1, opts);
+    let widths = Widths::of(&rows, indent, &TIMING);
+    write_rows(w, &rows, &widths, indent, &TIMING, opts)
+}
+
+/// A line of a game's debug overlay, as returned by `Profiler::overlay`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlayRow {
+    /// Depth of the node, where the root is at depth 0. Usually drawn as indentation.
+    pub depth: u32,
+    /// The node's name, total time and, if it was called more than once, number of calls, such
+    /// as `physics 2.1ms x3`.
+    pub text: String,
+    /// Share of the whole frame taken by the node, from 0 to 1. Usually drawn as a bar.
+    pub fraction: f64,
+}
+
+/// Append a row to `rows` for `root` and each of its descendants, in the order they should be
+/// drawn.
+///
+/// Honors the sort order, reversal, maximum depth, minimum percentage and units of `opts`.
+pub fn overlay_rows(rows: &mut Vec<OverlayRow>, root: &ProfileNode, opts: &PrintOptions) {
+    overlay_row(rows, root, root.total_time.get(), 0, opts);
+}
+
+fn overlay_row(rows: &mut Vec<OverlayRow>, node: &ProfileNode, root_total: u64, depth: u32, opts: &PrintOptions) {
+    let total = node.total_time.get();
+    let mut text = format!("{} {}", node.display_name(), Time(total, opts.units));
+    if node.calls.get() > 1 {
+        text.push_str(&format!(" x{}", node.calls.get()));
+    }
+    rows.push(OverlayRow {
+        depth,
+        text,
+        fraction: if root_total == 0 { 0.0 } else { total as f64 / root_total as f64 },
+    });
+    if opts.max_depth.map(|max| depth + 1 > max).unwrap_or(false) {
+        return
+    }
+    let mut children = node.all_children();
+    sort_nodes(&mut children, opts, |n| (n.total_time.get(), n.self_time().as_nanos() as u64, n.calls.get() as u64));
+    for child in &children {
+        if percent_of_parent(child, Some(total)) >= opts.min_percent {
+            overlay_row(rows, child, root_total, depth + 1, opts);
+        }
+    }
+}
+
+// Sort `nodes` as configured, with `stats` giving the total, self time and calls of each.
+fn sort_nodes<F: Fn(&ProfileNode) -> (u64, u64, u64)>(nodes: &mut [Rc<ProfileNode>], opts: &PrintOptions, stats: F) {
+    let average = |n: &ProfileNode| {
+        let (total, _, calls) = stats(n);
+        total.checked_div(calls).unwrap_or(0)
+    };
+    match opts.sort {
+        SortOrder::Insertion => {}
+        SortOrder::Total => nodes.sort_by_key(|n| Reverse(stats(n).0)),
+        SortOrder::SelfTime => nodes.sort_by_key(|n| Reverse(stats(n).1)),
+        SortOrder::Calls => nodes.sort_by_key(|n| Reverse(stats(n).2)),
+        SortOrder::Average => nodes.sort_by_key(|n| Reverse(average(n))),
+        SortOrder::Name => nodes.sort_by_key(|n| n.name),
+    }
+    if opts.reverse {
+        nodes.reverse();
+    }
+}
+
+// Collect the rows for the children of `node`, which are at depth `depth`.
+// `root_total` is the total of the root of the table.
+// `history` holds the totals of each node by ID in past frames, for sparklines.
+fn rows_below(rows: &mut Vec<Row>, node: &ProfileNode, root_total: u64, history: &HashMap<u64, Vec<u64>>, indent: u32,
+              depth: u32, opts: &PrintOptions) {
+    if opts.max_depth.map(|max| depth > max).unwrap_or(false) {
+        return
+    }
+    let mut children: Vec<Rc<ProfileNode>> = node.all_children();
+    sort_nodes(&mut children, opts, |n| (n.total_time.get(), n.self_time().as_nanos() as u64, n.calls.get() as u64));
+    for child in &children {
+        let parent_total = Some(node.total_time.get());
+        if percent_of_parent(child, parent_total) < opts.min_percent {
+            continue
+        }
+        let mut row = row(child, parent_total, root_total, indent, opts);
+        // siblings only share a name when told apart by callsite
+        if let Some(location) = child.location.get() {
+            if children.iter().filter(|c| c.name == child.name).count() > 1 {
+                row.name = format!("{} ({})", row.name, location);
+            }
+        }
+        if let Some(totals) = history.get(&child.id) {
+            row.extra.insert(0, (sparkline(totals), None));
+        }
+        rows.push(row);
+        rows_below(rows, child, root_total, history, indent + 2, depth + 1, opts);
+    }
+    if let Some(row) = unaccounted_row(node, root_total, indent, opts) {
+        rows.push(row);
+    }
+}
+
+// `parent_total` is the total of the node the row is printed under, which for the children of a
+// mounted profiler isn't their real parent.
+fn row(node: &ProfileNode, parent_total: Option<u64>, root_total: u64, indent: u32, opts: &PrintOptions) -> Row {
+    let units = opts.units;
+    let total = node.total_time.get();
+    let calls = node.calls.get();
+    let percent = percent_of_parent(node, parent_total);
+    let mut extra = Vec::new();
+    let percent = if percent.is_infinite() {
+        (format!("{:.1}hz", calls as f64 / total as f64 * 1e9f64), None)
+    } else {
+        if percent > 100.0 {
+            extra.push(("[anomaly: longer than parent]".to_string(), Some(RED)));
+        }
+        (format!("{:.1}%", percent), percent_color(percent))
+    };
+    match node.budget.get() {
+        Some(budget) if total > budget => {
+            let over = format!("[over budget of {} by {}]", Time(budget, units), Time(total - budget, units));
+            extra.push((over, Some(RED)));
+        }
+        _ => {}
+    }
+    if let Some(blocked) = node.blocked_time().filter(|_| calls > 0) {
+        let blocked = blocked.as_nanos() as u64;
+        let text = match node.cpu_time.get() {
+            Some(cpu) => format!("[cpu {}, blocked {}]", Time(cpu, units), Time(blocked, units)),
+            None => format!("[blocked {}]", Time(blocked, units)),
+        };
+        extra.push((text, if node.waiting() { Some(BLUE) } else { None }));
+    }
+    if node.bytes.get() > 0 {
+        extra.push((format!("[{} bytes]", node.bytes.get()), None));
+    }
+    if node.collapsed.get() {
+        extra.push(("[collapsed]".to_string(), None));
+    }
+    if let Some(avg) = node.smoothed_time.get() {
+        extra.push((format!("~{}", Time(avg as u64, units)), None));
+    }
+    if opts.calls_per_frame && node.lifetime_frames() > 0 {
+        let mean = node.lifetime_calls() as f64 / node.lifetime_frames() as f64;
+        extra.push((format!("[{:.1} calls/frame, max {}]", mean, node.max_calls_per_frame()), None));
+    }
+    if node.max_recursion.get() > 1 {
+        let depths: Vec<String> = node.calls_by_depth().iter().map(|c| c.to_string()).collect();
+        extra.push((format!("[recursion: max depth {}, calls by depth {}]", node.max_recursion.get(), depths.join("/")),
+                    None));
+    }
+    for note in &*node.annotations.borrow() {
+        extra.push((format!("{:?}", note), None));
+    }
+    if node.clock_errors.get() > 0 {
+        extra.push((format!("[anomaly: clock went backwards {} times]", node.clock_errors.get()), Some(RED)));
+    }
+    if let Some(ref histogram) = *node.histogram.borrow() {
+        if let (Some(p50), Some(p99), Some(p999), Some(max)) =
+                (histogram.quantile(0.5), histogram.quantile(0.99), histogram.quantile(0.999), histogram.max()) {
+            extra.push((format!("[p50 {}, p99 {}, p99.9 {}, max {}]",
+                                Time(p50, units), Time(p99, units), Time(p999, units), Time(max, units)), None));
+        }
+    } else if let Some(ref samples) = *node.samples.borrow() {
+        if let (Some(p50), Some(p99), Some(&max)) =
+                (samples.quantile(0.5), samples.quantile(0.99), samples.samples().iter().max()) {
+            extra.push((format!("[p50 {}, p99 {}, max {}]", Time(p50, units), Time(p99, units), Time(max, units)), None));
+        }
+    } else if let (Some(min), Some(max)) = (node.min_time.get(), node.max_time.get()) {
+        extra.push((format!("[min {}, max {}]", Time(min, units), Time(max, units)), None));
+    }
+    Row {
+        indent,
+        name: node.name.to_string(),
+        calls: calls.to_string(),
+        avg: Time((total as f64 / calls as f64) as u64, units).to_string(),
+        total: Time(total, units).to_string(),
+        percent,
+        root_percent: format!("{:.1}%", 100.0 * (total as f64 / root_total as f64)),
+        self_time: Time(node.self_time().as_nanos() as u64, units).to_string(),
+        delta: delta(node),
+        share: total as f64 / root_total as f64,
+        extra,
+        dim: false,
+    }
+}
+
+// The change in the total of `node` since the previous frame, colored when it got much slower.
+fn delta(node: &ProfileNode) -> (String, Option<&'static str>) {
+    match node.previous_time.get() {
+        // a node first seen this frame, unless this is the first frame
+        None if node.parent().map(|p| p.previous_time.get().is_some()).unwrap_or(false) => ("new".to_string(), None),
+        None => (String::new(), None),
+        Some(0) if node.total_time.get() == 0 => ("+0.0%".to_string(), None),
+        Some(0) => ("new".to_string(), None),
+        Some(previous) => {
+            let change = 100.0 * (node.total_time.get() as f64 - previous as f64) / previous as f64;
+            (format!("{:+.1}%", change), percent_color(change))
+        }
+    }
+}
+
+// The time spent in `node` itself, or in scopes nobody instrumented, as if it were another
+// child.
+fn unaccounted_row(node: &ProfileNode, root_total: u64, indent: u32, opts: &PrintOptions) -> Option<Row> {
+    let unaccounted = node.self_time().as_nanos() as u64;
+    let percent = 100.0 * (unaccounted as f64 / node.total_time.get() as f64);
+    if node.all_children().is_empty() || unaccounted == 0 || percent < opts.min_percent {
+        return None
+    }
+    Some(Row {
+        indent,
+        name: "<unaccounted>".to_string(),
+        calls: String::new(),
+        avg: String::new(),
+        total: Time(unaccounted, opts.units).to_string(),
+        percent: (format!("{:.1}%", percent), None),
+        root_percent: format!("{:.1}%", 100.0 * (unaccounted as f64 / root_total as f64)),
+        self_time: String::new(),
+        delta: (String::new(), None),
+        share: unaccounted as f64 / root_total as f64,
+        extra: Vec::new(),
+        dim: true,
+    })
+}
+
+// A bar `width` characters long when `share` is 1, drawn to an eighth of a character.
+fn bar(share: f64, width: u32) -> String {
+    const PARTS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    let eighths = (share.clamp(0.0, 1.0) * width as f64 * 8.0).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        bar.push(PARTS[eighths % 8]);
+    }
+    bar
+}
+
+// The total of every node in each of `frames`, by ID, with 0 for frames a node wasn't in.
+fn totals_by_id(frames: &[&ProfileSnapshot]) -> HashMap<u64, Vec<u64>> {
+    let mut totals = HashMap::new();
+    for (i, frame) in frames.iter().enumerate() {
+        for node in &frame.nodes {
+            totals.entry(node.id).or_insert_with(|| vec![0; frames.len()])[i] = node.total_time;
+        }
+    }
+    totals
+}
+
+// A line of one character per value, as high as the value is compared to the largest, with a
+// space for 0.
+fn sparkline(values: &[u64]) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().cloned().max().unwrap_or(0);
+    values.iter().map(|&v| {
+        if v == 0 {
+            ' '
+        } else {
+            LEVELS[((v as f64 / max as f64 * 8.0).ceil() as usize).clamp(1, 8) - 1]
+        }
+    }).collect()
+}
+
+// Column headings, in order.
+type Labels = [&'static str; 8];
+
+const TIMING: Labels = ["name", "calls", "avg", "total", "%", "% root", "self", "Δ%"];
+// the self column holds the average time per frame
+const SESSION: Labels = ["name", "calls", "avg", "total", "%", "", "per frame", ""];
+// the percentage is of the self time
+const HOTSPOTS: Labels = ["name", "calls", "avg", "total", "% self", "", "self", ""];
+
+struct Widths {
+    name: usize,
+    calls: usize,
+    avg: usize,
+    total: usize,
+    percent: usize,
+    root_percent: usize,
+    self_time: usize,
+    delta: usize,
+}
+
+impl Widths {
+    fn of<'a, I: IntoIterator<Item=&'a Row>>(rows: I, indent: u32, labels: &Labels) -> Widths {
+        let mut widths = Widths {
+            name: labels[0].len() + indent as usize,
+            calls: labels[1].len(),
+            avg: labels[2].len(),
+            total: labels[3].len(),
+            percent: labels[4].len(),
+            root_percent: labels[5].len(),
+            self_time: labels[6].len(),
+            delta: labels[7].chars().count(),
+        };
+        for row in rows {
+            widths.name = widths.name.max(row.indent as usize + row.name.chars().count());
+            widths.calls = widths.calls.max(row.calls.len());
+            widths.avg = widths.avg.max(row.avg.len());
+            widths.total = widths.total.max(row.total.len());
+            widths.percent = widths.percent.max(row.percent.0.len());
+            widths.root_percent = widths.root_percent.max(row.root_percent.len());
+            widths.self_time = widths.self_time.max(row.self_time.len());
+            widths.delta = widths.delta.max(row.delta.0.len());
+        }
+        widths
+    }
+}
+
+fn write_rows<W: Write>(w: &mut W, rows: &[Row], widths: &Widths, indent: u32, labels: &Labels,
+                        opts: &PrintOptions) -> io::Result<()> {
+    let pad = widths.name - indent as usize;
+    write!(w, "{:indent$}{:<pad$}  {:>calls$}  {:>avg$}  {:>total$}  {:>percent$}",
+           "", labels[0], labels[1], labels[2], labels[3], labels[4],
+           indent = indent as usize, pad = pad, calls = widths.calls, avg = widths.avg,
+           total = widths.total, percent = widths.percent)?;
+    if opts.percent_of_root {
+        write!(w, "  {:>width$}", labels[5], width = widths.root_percent)?;
+    }
+    if opts.self_time {
+        write!(w, "  {:>width$}", labels[6], width = widths.self_time)?;
+    }
+    if opts.delta {
+        write!(w, "  {:>width$}", labels[7], width = widths.delta)?;
+    }
+    writeln!(w)?;
+    for row in rows {
+        let pad = widths.name - row.indent as usize;
+        let name = format!("{:indent$}{:<pad$}", "", row.name, indent = row.indent as usize, pad = pad);
+        paint(w, opts, if row.dim { Some(DIM) } else { None }, format_args!("{}", name))?;
+        write!(w, "  {:>calls$}  {:>avg$}  {:>total$}  ", row.calls, row.avg, row.total,
+               calls = widths.calls, avg = widths.avg, total = widths.total)?;
+        let percent = format!("{:>width$}", row.percent.0, width = widths.percent);
+        paint(w, opts, row.percent.1, format_args!("{}", percent))?;
+        if opts.percent_of_root {
+            write!(w, "  {:>width$}", row.root_percent, width = widths.root_percent)?;
+        }
+        if opts.self_time && !row.self_time.is_empty() {
+            write!(w, "  {:>width$}", row.self_time, width = widths.self_time)?;
+        }
+        if opts.delta && !row.delta.0.is_empty() {
+            if opts.self_time && row.self_time.is_empty() {
+                write!(w, "  {:width$}", "", width = widths.self_time)?;
+            }
+            let delta = format!("{:>width$}", row.delta.0, width = widths.delta);
+            write!(w, "  ")?;
+            paint(w, opts, row.delta.1, format_args!("{}", delta))?;
+        }
+        if let Some(width) = opts.bars {
+            // pad out the columns this row left empty, so bars line up
+            if opts.self_time && row.self_time.is_empty() && (!opts.delta || row.delta.0.is_empty()) {
+                write!(w, "  {:width$}", "", width = widths.self_time)?;
+            }
+            if opts.delta && row.delta.0.is_empty() {
+                write!(w, "  {:width$}", "", width = widths.delta)?;
+            }
+            let bar = bar(row.share, width);
+            if row.extra.is_empty() {
+                write!(w, "  {}", bar)?;
+            } else {
+                write!(w, "  {:<width$}", bar, width = width as usize)?;
+            }
+        }
+        for &(ref text, color) in &row.extra {
+            write!(w, " ")?;
+            paint(w, opts, color, format_args!("{}", text))?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Sampling individual call durations.
+
+/// A fixed-size uniform random sample of call durations.
+///
+/// Uses reservoir sampling, so every call ever recorded has the same chance of being in the
+/// sample no matter how many calls were made, while memory use stays bounded.
+#[derive(Clone, Debug)]
+pub struct Reservoir {
+    samples: Vec<u64>,
+    capacity: usize,
+    seen: u64,
+    rng: u64,
+}
+
+impl Reservoir {
+    /// Create a reservoir holding at most `capacity` samples.
+    pub fn new(capacity: usize) -> Reservoir {
+        Reservoir { samples: Vec::with_capacity(capacity), capacity, seen: 0, rng: 0x2545f4914f6cdd1d }
+    }
+
+    /// Offer a duration in ns to the sample.
+    pub fn add(&mut self, duration: u64) {
+        self.seen += 1;
+        if self.samples.len() < self.capacity {
+            self.samples.push(duration);
+        } else if self.capacity > 0 {
+            let i = self.next_random() % self.seen;
+            if (i as usize) < self.capacity {
+                self.samples[i as usize] = duration;
+            }
+        }
+    }
+
+    // xorshift64*
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// The sampled durations in ns, in no particular order.
+    pub fn samples(&self) -> &[u64] {
+        &self.samples
+    }
+
+    /// The number of durations offered so far.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Estimate the duration below which a fraction `q` (between 0 and 1) of calls fall.
+    ///
+    /// Returns `None` if nothing was sampled.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.samples.is_empty() {
+            return None
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let idx = (q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64).round() as usize;
+        Some(sorted[idx])
+    }
+
+    /// Forget all samples.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.seen = 0;
+    }
+}
@@ -0,0 +1,113 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! InfluxDB line protocol.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use super::{FrameInfo, Sink};
+use ProfileNode;
+
+/// A sink writing a point per node per frame in InfluxDB line protocol.
+///
+/// The measurement is the node's path, or the root's name for the root, and the point is
+/// timestamped with the end of the frame in ns. The frame's tags become the point's tags. Each
+/// point has the fields `total` and `avg`, in ns, and `calls`:
+///
+/// ```text
+/// physics/collision,level=forest_03 total=1140731i,calls=1i,avg=1140731 1444000000123456789
+/// ```
+pub struct InfluxSink<W: Write> {
+    w: W,
+}
+
+impl InfluxSink<BufWriter<File>> {
+    /// Append points to the file at `path`, creating it if needed.
+    pub fn append<P: AsRef<Path>>(path: P) -> io::Result<InfluxSink<BufWriter<File>>> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(InfluxSink::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> InfluxSink<W> {
+    /// Write points to `w`.
+    pub fn new(w: W) -> InfluxSink<W> {
+        InfluxSink { w }
+    }
+
+    /// Stop writing, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    fn write_point(&mut self, measurement: &str, tags: &str, node: &ProfileNode, timestamp: u64) -> io::Result<()> {
+        let calls = node.calls.get();
+        let total = node.total_time.get();
+        let avg = if calls == 0 { 0.0 } else { total as f64 / calls as f64 };
+        writeln!(self.w, "{}{} total={}i,calls={}i,avg={} {}", measurement, tags, total, calls, avg, timestamp)
+    }
+
+    fn write_children(&mut self, node: &ProfileNode, path: &mut String, tags: &str, timestamp: u64) -> io::Result<()> {
+        for child in &node.all_children() {
+            let len = path.len();
+            if len > 0 {
+                path.push('/');
+            }
+            escape_measurement(path, child.name);
+            self.write_point(path, tags, child, timestamp)?;
+            self.write_children(child, path, tags, timestamp)?;
+            path.truncate(len);
+        }
+        Ok(())
+    }
+}
+
+// Append `name` to `measurement`, escaping commas and spaces, the only characters line protocol
+// treats specially in measurements. Newlines, which it can't represent, are written as `\n`.
+fn escape_measurement(measurement: &mut String, name: &str) {
+    escape(measurement, name, &[',', ' ']);
+}
+
+// Append `s` to `tags`, escaping commas, spaces and `=`, as line protocol requires in tag keys
+// and values.
+fn escape_tag(tags: &mut String, s: &str) {
+    escape(tags, s, &[',', ' ', '=']);
+}
+
+fn escape(out: &mut String, s: &str, special: &[char]) {
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            c if special.contains(&c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+impl<W: Write> Sink for InfluxSink<W> {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let timestamp = info.timestamp.duration_since(UNIX_EPOCH)
+                                      .map(|d| d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64)
+                                      .unwrap_or(0);
+        let mut tags = String::new();
+        for (key, value) in info.tags {
+            tags.push(',');
+            escape_tag(&mut tags, key);
+            tags.push('=');
+            escape_tag(&mut tags, value);
+        }
+        let mut measurement = String::new();
+        escape_measurement(&mut measurement, &root.display_name());
+        self.write_point(&measurement, &tags, root, timestamp)?;
+        self.write_children(root, &mut String::new(), &tags, timestamp)?;
+        self.w.flush()
+    }
+}
@@ -0,0 +1,101 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! One JSON object per frame, one frame per line.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use json;
+use super::{FrameInfo, Sink};
+use ProfileNode;
+
+/// A sink writing each frame as a line of JSON, for `jq`, scripts and log pipelines.
+///
+/// Each line looks like
+///
+/// ```text
+/// {"frame":0,"start":1444000000.119,"timestamp":1444000000.123,"tags":{"level":"forest_03"},"total":4201,"nodes":[{"path":"physics","depth":1,"calls":1,"total":2100,"self":300},...]}
+/// ```
+///
+/// where `start` and `timestamp` are when the frame started and ended in seconds since the Unix
+/// epoch, `tags` are the frame's tags and times are in ns. Nodes are listed parent first, not
+/// including the root, whose time is `total`. Nodes entered through `scope!` also have `file`,
+/// `line` and `module` fields.
+pub struct JsonLinesSink<W: Write> {
+    w: W,
+}
+
+impl JsonLinesSink<BufWriter<File>> {
+    /// Append frames to the file at `path`, creating it if needed.
+    pub fn append<P: AsRef<Path>>(path: P) -> io::Result<JsonLinesSink<BufWriter<File>>> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(JsonLinesSink::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    /// Write frames to `w`.
+    pub fn new(w: W) -> JsonLinesSink<W> {
+        JsonLinesSink { w }
+    }
+
+    /// Stop writing, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    fn write_nodes(&mut self, node: &ProfileNode, path: &mut String, depth: u32, first: &mut bool) -> io::Result<()> {
+        for child in &node.all_children() {
+            let len = path.len();
+            if depth > 0 {
+                path.push('/');
+            }
+            path.push_str(child.name);
+            if !*first {
+                write!(self.w, ",")?;
+            }
+            *first = false;
+            write!(self.w, "{{\"path\":")?;
+            json::write_str(&mut self.w, path)?;
+            write!(self.w, ",\"depth\":{},\"calls\":{},\"total\":{},\"self\":{}",
+                   depth + 1, child.calls.get(), child.total_time.get(), child.self_time().as_nanos())?;
+            if let Some(location) = child.location.get() {
+                write!(self.w, ",\"file\":")?;
+                json::write_str(&mut self.w, location.file)?;
+                write!(self.w, ",\"line\":{},\"module\":", location.line)?;
+                json::write_str(&mut self.w, location.module)?;
+            }
+            write!(self.w, "}}")?;
+            self.write_nodes(child, path, depth + 1, first)?;
+            path.truncate(len);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Sink for JsonLinesSink<W> {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH)
+                                             .map(|d| d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9)
+                                             .unwrap_or(0.0);
+        write!(self.w, "{{\"frame\":{},\"start\":{:.6},\"timestamp\":{:.6},\"tags\":{{", info.index,
+               seconds(info.start), seconds(info.timestamp))?;
+        for (i, (key, value)) in info.tags.iter().enumerate() {
+            if i > 0 {
+                write!(self.w, ",")?;
+            }
+            json::write_str(&mut self.w, key)?;
+            write!(self.w, ":")?;
+            json::write_str(&mut self.w, value)?;
+        }
+        write!(self.w, "}},\"total\":{},\"nodes\":[", root.total_time.get())?;
+        self.write_nodes(root, &mut String::new(), 0, &mut true)?;
+        writeln!(self.w, "]}}")?;
+        self.w.flush()
+    }
+}
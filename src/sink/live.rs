@@ -0,0 +1,76 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! A report redrawn in place in a terminal.
+
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use report::{self, PrintOptions};
+use super::{FrameInfo, Sink};
+use ProfileNode;
+
+/// A sink that clears the terminal and redraws the timing report of the latest frame, so it
+/// can be watched live instead of scrolling past.
+///
+/// The report is drawn with ANSI escape codes, so `w` should be a terminal.
+pub struct LiveSink<W: Write> {
+    w: W,
+    opts: PrintOptions,
+    interval: Option<Duration>,
+    last: Option<Instant>,
+    // the next screen, written in one go to avoid flicker
+    buf: Vec<u8>,
+}
+
+impl LiveSink<Stdout> {
+    /// Redraw the report on stdout.
+    pub fn stdout() -> LiveSink<Stdout> {
+        LiveSink::new(io::stdout())
+    }
+}
+
+impl<W: Write> LiveSink<W> {
+    /// Redraw the report on `w` after every frame, with the default options.
+    pub fn new(w: W) -> LiveSink<W> {
+        LiveSink { w, opts: PrintOptions::default(), interval: None, last: None, buf: Vec::new() }
+    }
+
+    /// Draw the report configured by `opts`.
+    pub fn with_options(mut self, opts: PrintOptions) -> LiveSink<W> {
+        self.opts = opts;
+        self
+    }
+
+    /// Only redraw when at least `interval` has passed since the last redraw, to keep fast frame
+    /// rates readable.
+    pub fn every(mut self, interval: Duration) -> LiveSink<W> {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Stop drawing, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+impl<W: Write> Sink for LiveSink<W> {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let now = Instant::now();
+        if let (Some(interval), Some(last)) = (self.interval, self.last) {
+            if now.duration_since(last) < interval {
+                return Ok(())
+            }
+        }
+        self.last = Some(now);
+        self.buf.clear();
+        // move the cursor to the top left and clear the screen
+        writeln!(self.buf, "\x1b[H\x1b[2JFrame {}", info.index)?;
+        report::write_tables(&mut self.buf, &[root], 2, &self.opts)?;
+        self.w.write_all(&self.buf)?;
+        self.w.flush()
+    }
+}
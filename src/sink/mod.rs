@@ -0,0 +1,61 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Streaming completed frames somewhere as they happen.
+
+use std::io;
+use std::time::SystemTime;
+
+use ProfileNode;
+
+pub mod influx;
+pub mod jsonl;
+pub mod live;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod statsd;
+
+pub use self::influx::InfluxSink;
+pub use self::jsonl::JsonLinesSink;
+pub use self::live::LiveSink;
+#[cfg(feature = "otel")]
+pub use self::otel::OtelSink;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteSink;
+pub use self::statsd::StatsdSink;
+
+/// Information about a completed frame, besides its profile tree.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameInfo<'a> {
+    /// Number of frames the profiler completed before this one.
+    pub index: u64,
+    /// Wall-clock time at which the frame started.
+    pub start: SystemTime,
+    /// Wall-clock time at which the frame ended.
+    pub timestamp: SystemTime,
+    /// Tags of the frame, as `(key, value)` pairs. See `Profiler::tag_frame`.
+    pub tags: &'a [(String, String)],
+}
+
+/// Something that receives every frame completed by a `Profiler`.
+///
+/// Add one with `Profiler::add_sink`.
+pub trait Sink {
+    /// Handle the frame described by `info`, whose tree is rooted at `root`.
+    ///
+    /// Called from `end_frame`, so this should be quick.
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()>;
+
+    /// Handle a frame of the secondary kind `kind`, finished by `Profiler::end_frame_of`.
+    ///
+    /// `info.index` counts the frames of that kind, and `info.tags` is empty. Ignored by
+    /// default, so that sinks keep seeing only the main frames unless they ask for more.
+    fn frame_of(&mut self, kind: &'static str, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let _ = (kind, info, root);
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! OpenTelemetry traces.
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use opentelemetry::trace::{TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+
+use super::{FrameInfo, Sink};
+use ProfileNode;
+
+/// A sink turning each frame into a trace, with a span per node.
+///
+/// Only totals are known for each frame, so every node becomes a single span as long as its
+/// total time, with children laid out back to back from the start of their parent. Each span has
+/// `hprof.calls` and `hprof.self_ns` attributes, and `hprof.category` if the node has one. The
+/// frame's tags are attributes of the root span, as `hprof.tag.<key>`.
+pub struct OtelSink<T> {
+    tracer: T,
+}
+
+impl<T: Tracer> OtelSink<T> where T::Span: Send + Sync + 'static {
+    /// Export frames with `tracer`.
+    pub fn new(tracer: T) -> OtelSink<T> {
+        OtelSink { tracer }
+    }
+
+    fn export(&self, node: &ProfileNode, start: SystemTime, parent: &Context, mut attributes: Vec<KeyValue>) {
+        attributes.extend(vec![
+            KeyValue::new("hprof.calls", node.calls.get() as i64),
+            KeyValue::new("hprof.self_ns", node.self_time().as_nanos() as i64),
+        ]);
+        if let Some(category) = node.effective_category() {
+            attributes.push(KeyValue::new("hprof.category", category.name));
+        }
+        let builder = self.tracer.span_builder(node.display_name())
+                                 .with_start_time(start)
+                                 .with_attributes(attributes);
+        let cx = parent.with_span(self.tracer.build_with_context(builder, parent));
+        let mut child_start = start;
+        for child in &node.all_children() {
+            self.export(child, child_start, &cx, Vec::new());
+            child_start += Duration::from_nanos(child.total_time.get());
+        }
+        cx.span().end_with_timestamp(start + Duration::from_nanos(node.total_time.get()));
+    }
+}
+
+impl<T: Tracer> Sink for OtelSink<T> where T::Span: Send + Sync + 'static {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let tags = info.tags.iter().map(|(k, v)| KeyValue::new(format!("hprof.tag.{}", k), v.clone()));
+        self.export(root, info.start, &Context::new(), tags.collect());
+        Ok(())
+    }
+}
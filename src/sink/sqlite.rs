@@ -0,0 +1,126 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! SQLite databases.
+
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use rusqlite::{self, Connection, Transaction};
+
+use super::{FrameInfo, Sink};
+use ProfileNode;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS frames (
+        id INTEGER PRIMARY KEY,
+        frame_index INTEGER NOT NULL,
+        timestamp REAL NOT NULL,
+        total_ns INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS node_stats (
+        frame_id INTEGER NOT NULL REFERENCES frames(id),
+        node_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        name TEXT NOT NULL,
+        depth INTEGER NOT NULL,
+        calls INTEGER NOT NULL,
+        total_ns INTEGER NOT NULL,
+        self_ns INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS node_stats_path ON node_stats (path, total_ns);
+    CREATE TABLE IF NOT EXISTS frame_tags (
+        frame_id INTEGER NOT NULL REFERENCES frames(id),
+        key TEXT NOT NULL,
+        value TEXT NOT NULL
+    );
+";
+
+/// A sink storing frames in a SQLite database, so captures can be queried with SQL.
+///
+/// Each frame is a row of `frames (id, frame_index, timestamp, total_ns)`, where `timestamp` is
+/// the end of the frame in seconds since the Unix epoch. Each node other than the root is a row
+/// of `node_stats (frame_id, node_id, path, name, depth, calls, total_ns, self_ns)`, and each
+/// tag of a frame a row of `frame_tags (frame_id, key, value)`. For example, to find every frame
+/// where `Render/GPU wait` took more than 5ms:
+///
+/// ```sql
+/// SELECT f.frame_index, n.total_ns FROM node_stats n JOIN frames f ON f.id = n.frame_id
+///     WHERE n.path = 'Render/GPU wait' AND n.total_ns > 5000000;
+/// ```
+///
+/// or the average physics time in the forest level:
+///
+/// ```sql
+/// SELECT avg(n.total_ns) FROM node_stats n JOIN frame_tags t ON t.frame_id = n.frame_id
+///     WHERE n.path = 'Physics' AND t.key = 'level' AND t.value = 'forest_03';
+/// ```
+///
+/// `node_id` is the node's `ProfileNode::id`, reinterpreted as a signed integer.
+pub struct SqliteSink {
+    conn: Connection,
+}
+
+fn to_io(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl SqliteSink {
+    /// Store frames in the database at `path`, creating it if needed.
+    ///
+    /// Frames already in the database are kept.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SqliteSink> {
+        SqliteSink::with_connection(Connection::open(path).map_err(to_io)?)
+    }
+
+    /// Store frames using an open connection.
+    pub fn with_connection(conn: Connection) -> io::Result<SqliteSink> {
+        conn.execute_batch(SCHEMA).map_err(to_io)?;
+        Ok(SqliteSink { conn })
+    }
+
+    /// Stop storing frames, returning the connection.
+    pub fn into_inner(self) -> Connection {
+        self.conn
+    }
+}
+
+fn insert_nodes(tx: &Transaction, frame_id: i64, node: &ProfileNode, path: &mut String,
+                depth: u32) -> rusqlite::Result<()> {
+    for child in &node.all_children() {
+        let len = path.len();
+        if depth > 0 {
+            path.push('/');
+        }
+        path.push_str(child.name);
+        tx.prepare_cached("INSERT INTO node_stats VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?
+          .execute(rusqlite::params![frame_id, child.id as i64, &*path, child.name, depth + 1,
+                                     child.calls.get(), child.total_time.get() as i64,
+                                     child.self_time().as_nanos() as i64])?;
+        insert_nodes(tx, frame_id, child, path, depth + 1)?;
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+impl Sink for SqliteSink {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        let timestamp = info.timestamp.duration_since(UNIX_EPOCH)
+                                      .map(|d| d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9)
+                                      .unwrap_or(0.0);
+        let tx = self.conn.transaction().map_err(to_io)?;
+        tx.execute("INSERT INTO frames (frame_index, timestamp, total_ns) VALUES (?1, ?2, ?3)",
+                   rusqlite::params![info.index as i64, timestamp, root.total_time.get() as i64])
+          .map_err(to_io)?;
+        let frame_id = tx.last_insert_rowid();
+        for (key, value) in info.tags {
+            tx.execute("INSERT INTO frame_tags VALUES (?1, ?2, ?3)", rusqlite::params![frame_id, key, value])
+              .map_err(to_io)?;
+        }
+        insert_nodes(&tx, frame_id, root, &mut String::new(), 0).map_err(to_io)?;
+        tx.commit().map_err(to_io)
+    }
+}
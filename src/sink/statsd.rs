@@ -0,0 +1,99 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! StatsD timers over UDP.
+
+use std::io::{self, Write};
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use super::{FrameInfo, Sink};
+use ProfileNode;
+
+// Keep datagrams small enough not to be fragmented on typical networks.
+const MAX_DATAGRAM: usize = 1432;
+
+/// A sink sending the total time of every node as a StatsD timer, in ms.
+///
+/// Metric names are the node's path with `/` replaced by `.`, after a prefix, so with prefix
+/// `game` the node at `render/gpu wait` becomes `game.render.gpu_wait`. Characters StatsD
+/// doesn't allow in names are replaced by `_`. The root is sent under the prefix alone, or its own
+/// name if the prefix is empty.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    interval: u64,
+    buf: Vec<u8>,
+}
+
+impl StatsdSink {
+    /// Send metrics to the StatsD server at `addr`, with names starting with `prefix`.
+    pub fn new<A: ToSocketAddrs>(addr: A, prefix: &str) -> io::Result<StatsdSink> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        let prefix = prefix.split('.').map(sanitize).collect::<Vec<_>>().join(".");
+        Ok(StatsdSink { socket, prefix, interval: 1, buf: Vec::new() })
+    }
+
+    /// Only send every `frames`th frame. Defaults to 1, sending every frame.
+    ///
+    /// StatsD aggregates timers itself, so sampling busy profilers keeps the traffic down without
+    /// losing much.
+    pub fn set_interval(&mut self, frames: u64) {
+        self.interval = frames.max(1);
+    }
+
+    fn send_node(&mut self, node: &ProfileNode, name: &mut String) -> io::Result<()> {
+        // a node not entered this frame, whose subtree wasn't either, would only pull the
+        // server's timer statistics towards zero
+        if node.calls.get() == 0 {
+            return Ok(())
+        }
+        let mut line = Vec::new();
+        writeln!(line, "{}:{:.3}|ms", name, node.total_time.get() as f64 / 1e6)?;
+        if self.buf.len() + line.len() > MAX_DATAGRAM {
+            self.flush()?;
+        }
+        self.buf.extend_from_slice(&line);
+        for child in &node.all_children() {
+            let len = name.len();
+            name.push('.');
+            name.push_str(&sanitize(child.name));
+            self.send_node(child, name)?;
+            name.truncate(len);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.socket.send(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+        _ => '_',
+    }).collect()
+}
+
+impl Sink for StatsdSink {
+    fn frame(&mut self, info: &FrameInfo, root: &ProfileNode) -> io::Result<()> {
+        if !info.index.is_multiple_of(self.interval) {
+            return Ok(())
+        }
+        let mut name = if self.prefix.is_empty() { sanitize(&root.display_name()) } else { self.prefix.clone() };
+        let result = self.send_node(root, &mut name);
+        // don't leave half a frame behind to be sent with the next one
+        if result.is_err() {
+            self.buf.clear();
+            return result
+        }
+        self.flush()
+    }
+}
@@ -0,0 +1,90 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! The slowest individual calls of a session, which averages and totals hide.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use {Nanoseconds, ProfileNode};
+
+/// A single call, as returned by `Profiler::slowest_calls`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowCall {
+    /// `/`-separated path of the node called.
+    pub path: String,
+    /// How long the call took, including its children.
+    pub duration: Duration,
+    /// When the call was made, as the time since the origin of the profiler's clock, which is
+    /// only meaningful compared with other calls of the same profiler.
+    pub time: Duration,
+    /// Index of the frame the call was made in, counting from 0, unless it was made outside of
+    /// frames.
+    pub frame: Option<u64>,
+    /// Time from the start of that frame to the call.
+    pub offset: Option<Duration>,
+}
+
+/// The `capacity` slowest calls seen so far, slowest first.
+pub struct SlowestCalls {
+    calls: Vec<SlowCall>,
+    capacity: usize,
+}
+
+impl SlowestCalls {
+    pub fn new(capacity: usize) -> SlowestCalls {
+        SlowestCalls { calls: Vec::with_capacity(capacity), capacity }
+    }
+
+    /// Consider a call to `node` that started at `start` and took `duration` ns, in the frame
+    /// with the given index and start, if any.
+    ///
+    /// Only calls that make the list have their path looked up.
+    pub fn record(&mut self, node: &ProfileNode, start: u64, duration: u64, frame: Option<(u64, u64)>) {
+        let duration = Duration::from_nanos(duration);
+        if self.calls.len() == self.capacity {
+            match self.calls.last() {
+                Some(last) if last.duration < duration => {}
+                _ => return,
+            }
+            self.calls.pop();
+        }
+        // after any earlier call that took as long, so ties keep the first
+        let idx = self.calls.iter().position(|c| c.duration < duration).unwrap_or(self.calls.len());
+        self.calls.insert(idx, SlowCall {
+            path: node.path(),
+            duration,
+            time: Duration::from_nanos(start),
+            frame: frame.map(|(index, _)| index),
+            offset: frame.map(|(_, frame_start)| Duration::from_nanos(start.saturating_sub(frame_start))),
+        });
+    }
+
+    pub fn calls(&self) -> &[SlowCall] {
+        &self.calls
+    }
+
+    pub fn clear(&mut self) {
+        self.calls.clear();
+    }
+}
+
+/// Write `calls` as a table at indentation `indent`, one call per line with its rank, duration,
+/// path, and when it was made.
+pub fn write_slowest_calls<W: Write>(w: &mut W, calls: &[SlowCall], indent: usize) -> io::Result<()> {
+    let width = calls.iter().map(|c| c.path.len()).max().unwrap_or(0);
+    for (rank, call) in calls.iter().enumerate() {
+        let duration = Nanoseconds(call.duration.as_nanos() as u64).to_string();
+        write!(w, "{:indent$}{:>3}. {:>8}  {:width$}", "", rank + 1, duration, call.path,
+               indent = indent, width = width)?;
+        match (call.frame, call.offset) {
+            (Some(frame), Some(offset)) => {
+                writeln!(w, "  frame {} at +{}", frame, Nanoseconds(offset.as_nanos() as u64))?
+            }
+            _ => writeln!(w, "  outside of frames")?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Owned copies of profile trees.
+
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use timeline::TimelineEvent;
+use {Color, Location, ProfileNode};
+
+/// An owned, flattened copy of a profile tree at one point in time.
+///
+/// Unlike `ProfileNode`s, snapshots are plain data: they can be stored, sent to other threads,
+/// written to disk and read back. They are always `Clone + Send + Sync + 'static`, so heavy
+/// analysis and exports can be done on a worker thread. To hand the same snapshot to several
+/// threads without copying it, wrap it in an `Arc`, as `FrameReader::latest_shared` does.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileSnapshot {
+    /// Timestamp in ns of the profiler's clock when the root of the tree was entered. Only
+    /// differences between timestamps mean anything; see `offset`.
+    pub start_time: u64,
+    /// Number of frames the profiler completed before this one, counting from 0 for the first
+    /// frame of the session. 0 for trees that aren't frames.
+    pub index: u64,
+    /// Wall-clock time at which the frame started, for lining profiles up with logs and video.
+    /// `None` for trees that aren't frames.
+    pub wall_time: Option<SystemTime>,
+    /// Wall-clock time at which the frame ended. `None` for trees that aren't completed frames,
+    /// and for frames of recordings made before it was kept.
+    pub end_wall_time: Option<SystemTime>,
+    /// Nodes in depth-first order. The root, if any, is first.
+    pub nodes: Vec<SnapshotNode>,
+    /// Timeline events, in the order they happened. Empty unless timeline recording is enabled.
+    pub events: Vec<TimelineEvent>,
+    /// Tags describing the state the frame was recorded in, as `(key, value)` pairs. See
+    /// `Profiler::tag_frame`.
+    pub tags: Vec<(String, String)>,
+}
+
+/// A single node of a `ProfileSnapshot`.
+///
+/// Times are kept in ns, like the other fields of snapshots, since they are plain data mirrored
+/// field for field by the recording formats and exports, which all store integer ns. Read them
+/// through the `Duration` getters instead: `total`, `cpu` and `blocked`, and
+/// `ProfileSnapshot::self_time` for the self time, which depends on the node's children.
+#[derive(Clone, Debug)]
+pub struct SnapshotNode {
+    /// Stable ID of the node's path. See `ProfileNode::id`.
+    pub id: u64,
+    pub name: Cow<'static, str>,
+    /// Distance from the root, which has depth 0.
+    pub depth: u32,
+    /// Index of the parent in `ProfileSnapshot::nodes`.
+    pub parent: Option<usize>,
+    /// Number of calls made to this node.
+    pub calls: u32,
+    /// Total time in ns used by this node and all of its children.
+    pub total_time: u64,
+    /// Name of the node's effective category, if any.
+    pub category: Option<Cow<'static, str>>,
+    /// Color to draw the node in, if any. See `ProfileNode::effective_color`.
+    pub color: Option<Color>,
+    /// Notes attached to the node with `Profiler::annotate`.
+    pub annotations: Vec<String>,
+    /// Where the node was entered from, if it was entered through `scope!`.
+    ///
+    /// Not kept in recordings.
+    pub location: Option<Location>,
+    /// CPU time in ns used during calls to the node, if measured.
+    pub cpu_time: Option<u64>,
+    /// Time in ns the node spent waiting rather than running, if known. See
+    /// `ProfileNode::blocked_time`.
+    pub blocked_time: Option<u64>,
+    /// Bytes read or written during calls to the node. See `Profiler::add_bytes`. Not kept in
+    /// recordings.
+    pub bytes: u64,
+}
+
+impl SnapshotNode {
+    /// Total time used by this node and all of its children.
+    pub fn total(&self) -> Duration {
+        Duration::from_nanos(self.total_time)
+    }
+
+    /// CPU time used during calls to this node, if measured.
+    pub fn cpu(&self) -> Option<Duration> {
+        self.cpu_time.map(Duration::from_nanos)
+    }
+
+    /// Time this node spent waiting rather than running, if known. See
+    /// `ProfileNode::blocked_time`.
+    pub fn blocked(&self) -> Option<Duration> {
+        self.blocked_time.map(Duration::from_nanos)
+    }
+
+    /// Whether the node was blocked for more than half of its total, and counts as waiting.
+    pub fn waiting(&self) -> bool {
+        self.blocked_time.map(|b| b > self.total_time / 2).unwrap_or(false)
+    }
+}
+
+// snapshots must stay usable from worker threads
+const _: fn() = || {
+    fn assert_thread_safe<T: Clone + Send + Sync + 'static>() {}
+    assert_thread_safe::<ProfileSnapshot>();
+};
+
+impl ProfileSnapshot {
+    /// Snapshot the tree rooted at `root`.
+    pub fn of(root: &ProfileNode) -> ProfileSnapshot {
+        let mut snapshot = ProfileSnapshot::default();
+        snapshot.fill(root);
+        snapshot
+    }
+
+    /// The wall-clock time the frame started, in ns since the Unix epoch, if known.
+    pub fn unix_time(&self) -> Option<u64> {
+        self.wall_time.map(|t| t.duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0))
+    }
+
+    /// The wall-clock time the frame ended, in ns since the Unix epoch, if known.
+    pub fn unix_end_time(&self) -> Option<u64> {
+        self.end_wall_time.map(|t| t.duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0))
+    }
+
+    /// Whether the frame was in progress at the wall-clock time `time`.
+    ///
+    /// Frames whose end wasn't recorded are taken to end their total time after they started.
+    /// Always false for trees that aren't frames.
+    pub fn spans(&self, time: SystemTime) -> bool {
+        match self.wall_time {
+            Some(start) => {
+                let end = self.end_wall_time.unwrap_or(start + Duration::from_nanos(self.total_time()));
+                start <= time && time <= end
+            }
+            None => false,
+        }
+    }
+
+    /// Overwrite this snapshot with the tree rooted at `root`.
+    ///
+    /// Timeline events and tags are cleared, as are the frame index and wall-clock time. Reuses
+    /// the existing storage where possible.
+    pub fn fill(&mut self, root: &ProfileNode) {
+        self.start_time = root.start_time.get();
+        self.index = 0;
+        self.wall_time = None;
+        self.end_wall_time = None;
+        self.nodes.clear();
+        self.events.clear();
+        self.tags.clear();
+        self.push(root, 0, None);
+    }
+
+    // Returns the blocked time of `node`.
+    fn push(&mut self, node: &ProfileNode, depth: u32, parent: Option<usize>) -> Option<u64> {
+        let category = node.effective_category();
+        let idx = self.nodes.len();
+        self.nodes.push(SnapshotNode {
+            id: node.id,
+            name: node.display_name(),
+            depth,
+            parent,
+            calls: node.calls.get(),
+            total_time: node.total_time.get(),
+            category: category.map(|c| Cow::Borrowed(c.name)),
+            color: node.effective_color(),
+            annotations: node.annotations.borrow().clone(),
+            location: node.location.get(),
+            cpu_time: node.cpu_time.get(),
+            blocked_time: None,
+            bytes: node.bytes.get(),
+        });
+        // as in `ProfileNode::blocked_time`, without walking each subtree again
+        let mut children_blocked = None;
+        for child in &node.all_children() {
+            if let Some(blocked) = self.push(child, depth + 1, Some(idx)) {
+                children_blocked = Some(children_blocked.unwrap_or(0) + blocked);
+            }
+        }
+        let blocked = if node.blocking.get() {
+            Some(node.total_time.get())
+        } else if let Some(cpu) = node.cpu_time.get() {
+            Some(node.total_time.get().saturating_sub(cpu))
+        } else {
+            children_blocked
+        };
+        self.nodes[idx].blocked_time = blocked;
+        blocked
+    }
+
+    /// The root node, if the snapshot isn't empty.
+    pub fn root(&self) -> Option<&SnapshotNode> {
+        self.nodes.first()
+    }
+
+    /// Total time in ns of the root node, or 0 if the snapshot is empty.
+    pub fn total_time(&self) -> u64 {
+        self.root().map(|r| r.total_time).unwrap_or(0)
+    }
+
+    /// Total time of the root node, or zero if the snapshot is empty.
+    pub fn total(&self) -> Duration {
+        Duration::from_nanos(self.total_time())
+    }
+
+    /// Time used by the node at `idx` itself, rather than by its children.
+    pub fn self_time(&self, idx: usize) -> Duration {
+        let children: u64 = self.children(idx).map(|(_, c)| c.total_time).sum();
+        Duration::from_nanos(self.nodes[idx].total_time.saturating_sub(children))
+    }
+
+    /// Time from when the root was entered to `event`, such as one of `events`.
+    pub fn offset(&self, event: &TimelineEvent) -> Duration {
+        Duration::from_nanos(event.time.saturating_sub(self.start_time))
+    }
+
+    /// Iterate over the direct children of the node at `idx`.
+    pub fn children<'a>(&'a self, idx: usize) -> Box<dyn Iterator<Item=(usize, &'a SnapshotNode)> + 'a> {
+        let depth = self.nodes[idx].depth;
+        Box::new(self.nodes.iter().enumerate().skip(idx + 1)
+                     .take_while(move |&(_, n)| n.depth > depth)
+                     .filter(move |&(_, n)| n.parent == Some(idx)))
+    }
+
+    /// The value of the frame's tag `key`, if it has one.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.iter().find(|&(k, _)| k == key).map(|(_, v)| &**v)
+    }
+
+    /// The index of the node with the given `id`, if there is one.
+    pub fn find(&self, id: u64) -> Option<usize> {
+        self.nodes.iter().position(|n| n.id == id)
+    }
+
+    /// The `/`-separated path of the node at `idx`, not including the root.
+    pub fn path(&self, idx: usize) -> String {
+        let mut names = Vec::new();
+        let mut cur = idx;
+        while let Some(parent) = self.nodes[cur].parent {
+            names.push(&*self.nodes[cur].name);
+            cur = parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+}
@@ -0,0 +1,72 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Profiled async streams, with the `futures-core` feature.
+//!
+//! A scope can't be held across an `.await`: the task may be suspended and others polled on the
+//! same thread in the meantime. What can be measured is the time spent inside each poll, which
+//! is where a stream does its work. The `ProfiledStream` extension trait adds `profiled`, which
+//! records every call to `poll_next` under a child of the node current in the thread-local
+//! profiler when the stream is polled:
+//!
+//! ```ignore
+//! use hprof::stream::ProfiledStream;
+//!
+//! let mut messages = decode(socket).profiled("decode message");
+//! while let Some(message) = messages.next().await {
+//!     handle(message);
+//! }
+//! ```
+//!
+//! Every poll counts as a call, including those that returned `Pending` because no item was
+//! ready yet, so the calls of the node can be more than the items produced.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+/// Adds `profiled` to every stream.
+pub trait ProfiledStream: Stream + Sized {
+    /// Record the time spent in each call to `poll_next` under `name`.
+    fn profiled(self, name: &'static str) -> Profiled<Self> {
+        Profiled { inner: self, name }
+    }
+}
+
+impl<S: Stream> ProfiledStream for S {}
+
+/// A stream whose polls are profiled, as returned by `ProfiledStream::profiled`.
+#[derive(Clone, Debug)]
+pub struct Profiled<S> {
+    inner: S,
+    name: &'static str,
+}
+
+impl<S> Profiled<S> {
+    /// The name polls are recorded under.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Stop profiling, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Stream> Stream for Profiled<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<S::Item>> {
+        let _g = ::enter(self.name);
+        // `inner` is pinned along with `self`: it is never moved out of a pinned `Profiled`
+        unsafe { self.map_unchecked_mut(|s| &mut s.inner) }.poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,161 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Quick summaries of completed frames.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use ProfileNode;
+
+/// A summary of a single completed frame, as returned by `Profiler::end_frame_summary`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSummary {
+    /// Total time taken by the frame.
+    pub total: Duration,
+    /// Whether `total` exceeded the profiler's budget. Always false if no budget is set.
+    pub over_budget: bool,
+    /// Part of `total` spent waiting rather than running, if known. See
+    /// `ProfileNode::blocked_time`.
+    pub blocked: Option<Duration>,
+    /// Number of nodes in the tree, including the root.
+    pub node_count: usize,
+    /// Path of the node with the most self time (time not spent in its children).
+    ///
+    /// Empty if that is the root itself.
+    pub slowest_path: String,
+    /// Signs that the frame's timing data can't be trusted.
+    pub anomalies: Vec<Anomaly>,
+    /// Nodes that went over their own budget. See `Profiler::set_node_budget`.
+    pub violations: Vec<BudgetViolation>,
+}
+
+/// A node that went over the budget given to its path with `Profiler::set_node_budget`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BudgetViolation {
+    /// `/`-separated path of the node.
+    pub path: String,
+    /// Total time of the node in the frame.
+    pub total: Duration,
+    /// The node's budget.
+    pub budget: Duration,
+}
+
+/// Something impossible in a frame's timing data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    /// A node took longer than its parent, which usually means a `leave` was missed.
+    LongerThanParent {
+        path: String,
+        /// Total time of the node.
+        total: Duration,
+        /// Total time of its parent.
+        parent_total: Duration,
+    },
+    /// The clock went backwards during calls to a node, and those calls were ignored.
+    ClockWentBackwards {
+        path: String,
+        /// Number of calls affected.
+        count: u32,
+    },
+    /// The profiler was disabled or enabled during the frame, so `enter` or `leave` calls may
+    /// have been skipped.
+    ToggledMidFrame,
+}
+
+/// Statistics of a single node, as returned by `Profiler::stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeStats {
+    /// Number of calls in the last frame.
+    pub calls: u32,
+    /// Total time in the last frame.
+    pub total: Duration,
+    /// Time not spent in any children in the last frame.
+    pub self_time: Duration,
+    /// Exponential moving average of the total, if smoothing is enabled.
+    pub smoothed: Option<Duration>,
+    /// CPU time used by the thread in the last frame, if measured. See `Profiler::set_cpu_time`.
+    pub cpu: Option<Duration>,
+    /// Average total over every frame in which the node was called.
+    pub mean: Duration,
+    /// Average number of calls over every frame in which the node was called.
+    pub mean_calls: f64,
+    /// Most calls made in a single frame.
+    pub max_calls: u32,
+    /// Number of frames in which the node was called.
+    pub frames: u64,
+    /// Deepest the node was nested in itself in the last frame, where 1 means it wasn't
+    /// recursive.
+    pub max_recursion: u32,
+    /// Calls in the last frame at each recursion depth. See `ProfileNode::calls_by_depth`.
+    pub calls_by_depth: Vec<u32>,
+}
+
+impl NodeStats {
+    /// Gather the statistics of `node`.
+    pub fn of(node: &ProfileNode) -> NodeStats {
+        let frames = node.lifetime_frames();
+        let per_frame = |n: u64| if frames == 0 { 0.0 } else { n as f64 / frames as f64 };
+        NodeStats {
+            calls: node.calls.get(),
+            total: node.total(),
+            self_time: node.self_time(),
+            smoothed: node.smoothed(),
+            cpu: node.cpu(),
+            mean: Duration::from_nanos(per_frame(node.lifetime_time().as_nanos() as u64) as u64),
+            mean_calls: per_frame(node.lifetime_calls()),
+            max_calls: node.max_calls_per_frame(),
+            frames,
+            max_recursion: node.max_recursion.get(),
+            calls_by_depth: node.calls_by_depth(),
+        }
+    }
+}
+
+impl FrameSummary {
+    /// Summarize the tree rooted at `root`.
+    ///
+    /// `toggled` is whether the profiler was disabled or enabled during the frame.
+    pub fn of(root: &Rc<ProfileNode>, budget: Option<Duration>, toggled: bool) -> FrameSummary {
+        let total = root.total();
+        let mut summary = FrameSummary {
+            total,
+            over_budget: budget.map(|b| total > b).unwrap_or(false),
+            blocked: root.blocked_time(),
+            node_count: 0,
+            slowest_path: String::new(),
+            anomalies: Vec::new(),
+            violations: root.budget_violations(),
+        };
+        if toggled {
+            summary.anomalies.push(Anomaly::ToggledMidFrame);
+        }
+        let mut slowest = (root.self_time(), root.clone());
+        summary.visit(root, &mut slowest);
+        summary.slowest_path = slowest.1.path();
+        summary
+    }
+
+    fn visit(&mut self, node: &Rc<ProfileNode>, slowest: &mut (Duration, Rc<ProfileNode>)) {
+        self.node_count += 1;
+        let self_time = node.self_time();
+        if self_time > slowest.0 {
+            *slowest = (self_time, node.clone());
+        }
+        if node.clock_errors.get() > 0 {
+            self.anomalies.push(Anomaly::ClockWentBackwards { path: node.path(), count: node.clock_errors.get() });
+        }
+        for child in &node.all_children() {
+            if child.total_time.get() > node.total_time.get() {
+                self.anomalies.push(Anomaly::LongerThanParent {
+                    path: child.path(),
+                    total: child.total(),
+                    parent_total: node.total(),
+                });
+            }
+            self.visit(child, slowest);
+        }
+    }
+}
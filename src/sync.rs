@@ -0,0 +1,138 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Instrumented locks.
+//!
+//! `Mutex` and `RwLock` here wrap their `std::sync` counterparts. The time spent waiting to
+//! acquire the lock is recorded in the thread-local profiler as a child of the current node,
+//! named `"lock wait"` by default, so lock contention shows up directly in the profile tree. It
+//! is entered with `hprof::enter_blocking`, so reports and exports count it as blocked time.
+//! Time spent *holding* the lock is not recorded; wrap the critical section in `hprof::enter`
+//! if you want that too.
+
+use std::sync::{self, LockResult, TryLockResult, MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+
+/// Default name of the node that lock acquisition time is recorded under.
+pub const LOCK_WAIT: &str = "lock wait";
+
+/// A `std::sync::Mutex` whose lock acquisition time is profiled.
+pub struct Mutex<T: ?Sized> {
+    name: &'static str,
+    inner: sync::Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Create a new mutex, recording waits under `"lock wait"`.
+    pub fn new(t: T) -> Mutex<T> {
+        Mutex::named(LOCK_WAIT, t)
+    }
+
+    /// Create a new mutex, recording waits under `name`.
+    ///
+    /// Giving each interesting lock its own name makes it possible to tell which one is
+    /// contended.
+    pub fn named(name: &'static str, t: T) -> Mutex<T> {
+        Mutex { name, inner: sync::Mutex::new(t) }
+    }
+
+    /// Consume the mutex, returning the underlying data.
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquire the mutex, recording the time spent blocked.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        let _g = ::enter_blocking(self.name);
+        self.inner.lock()
+    }
+
+    /// Attempt to acquire the mutex without blocking.
+    ///
+    /// Nothing is recorded, since there is no wait.
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.inner.try_lock()
+    }
+
+    /// Get a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    /// The name waits on this mutex are recorded under.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Mutex<T> {
+        Mutex::new(T::default())
+    }
+}
+
+/// A `std::sync::RwLock` whose lock acquisition time is profiled.
+pub struct RwLock<T: ?Sized> {
+    name: &'static str,
+    inner: sync::RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Create a new lock, recording waits under `"lock wait"`.
+    pub fn new(t: T) -> RwLock<T> {
+        RwLock::named(LOCK_WAIT, t)
+    }
+
+    /// Create a new lock, recording waits under `name`.
+    pub fn named(name: &'static str, t: T) -> RwLock<T> {
+        RwLock { name, inner: sync::RwLock::new(t) }
+    }
+
+    /// Consume the lock, returning the underlying data.
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Acquire shared read access, recording the time spent blocked.
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let _g = ::enter_blocking(self.name);
+        self.inner.read()
+    }
+
+    /// Acquire exclusive write access, recording the time spent blocked.
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let _g = ::enter_blocking(self.name);
+        self.inner.write()
+    }
+
+    /// Attempt to acquire shared read access without blocking.
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        self.inner.try_read()
+    }
+
+    /// Attempt to acquire exclusive write access without blocking.
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        self.inner.try_write()
+    }
+
+    /// Get a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    /// The name waits on this lock are recorded under.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(T::default())
+    }
+}
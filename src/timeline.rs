@@ -0,0 +1,35 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Per-invocation timeline events.
+//!
+//! Normally every call to a node is folded into its totals. With timeline recording enabled
+//! (`Profiler::set_timeline`), each `enter` and `leave` is additionally recorded as an individual
+//! timestamped event, so it's possible to see *when* within a frame each scope ran.
+
+/// Whether a `TimelineEvent` marks entering or leaving a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Enter,
+    Leave,
+}
+
+/// A single `enter` or `leave` of a node.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimelineEvent {
+    pub kind: EventKind,
+    /// ID of the node entered or left. See `ProfileNode::id`.
+    pub id: u64,
+    /// Timestamp in ns of the profiler's clock. See `ProfileSnapshot::offset`.
+    pub time: u64,
+    /// Details of this particular call, given to `Profiler::enter_with`. Only ever set on enter
+    /// events.
+    pub payload: Option<String>,
+    /// CPU core the thread was running on, if recorded. See `Profiler::set_cpu_ids`.
+    ///
+    /// An enter and leave of the same call on different cores means the thread migrated in
+    /// between, which can cost it its caches.
+    pub cpu: Option<u32>,
+}
@@ -0,0 +1,66 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! A tab-separated format for grepping and scripts.
+//!
+//! Unlike the text report, which may change to suit people, this format is stable. It starts with
+//! a header line, shown here with each tab written as `\t`:
+//!
+//! ```text
+//! #path\tdepth\tcalls\ttotal_ns\tself_ns
+//! ```
+//!
+//! followed by one line per node, parents before their children:
+//!
+//! ```text
+//! game\t0\t1\t16510042\t10310
+//! game/Physics\t1\t1\t4203118\t2020
+//! game/Physics/Broad phase\t2\t1\t4201098\t4201098
+//! ```
+//!
+//! - `path` is the full `/`-separated path, starting with the name of the root so that paths
+//!   from different roots can't be confused. Backslashes, tabs and newlines in names are
+//!   escaped as `\\`, `\t` and `\n`.
+//! - `depth` is the distance from the root, which has depth 0.
+//! - `calls` is the number of calls made to the node.
+//! - `total_ns` and `self_ns` are the total and self time in ns.
+//!
+//! Later versions may add columns at the end of each line, but won't remove, reorder or change
+//! the meaning of the existing ones.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use ProfileNode;
+
+/// Write the trees below each of `roots` in the tab-separated format.
+pub fn write_tsv<W: Write>(w: &mut W, roots: &[&ProfileNode]) -> io::Result<()> {
+    writeln!(w, "#path\tdepth\tcalls\ttotal_ns\tself_ns")?;
+    for root in roots {
+        let mut path = escape(&root.display_name()).into_owned();
+        write_node(w, root, &mut path, 0)?;
+    }
+    Ok(())
+}
+
+fn write_node<W: Write>(w: &mut W, node: &ProfileNode, path: &mut String, depth: u32) -> io::Result<()> {
+    writeln!(w, "{}\t{}\t{}\t{}\t{}", path, depth, node.calls.get(), node.total_time.get(), node.self_time().as_nanos())?;
+    for child in &node.all_children() {
+        let len = path.len();
+        path.push('/');
+        path.push_str(&escape(child.name));
+        write_node(w, child, path, depth + 1)?;
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+// The same escaping as in recordings.
+fn escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['\\', '\t', '\n']) {
+        return Cow::Borrowed(s)
+    }
+    Cow::Owned(s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n"))
+}
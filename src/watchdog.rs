@@ -0,0 +1,19 @@
+//          Copyright Corey Richardson 2015
+// Distributed under the Boost Software License, Version 1.0.
+//    (See accompanying file LICENSE_1_0.txt or copy at
+//          http://www.boost.org/LICENSE_1_0.txt)
+
+//! Detection of frames going over budget while they are still running.
+
+use std::time::Duration;
+
+/// A frame that went over budget, as passed to the callback of `Profiler::set_watchdog`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Overrun {
+    /// Time since the frame started.
+    pub elapsed: Duration,
+    /// The profiler's budget.
+    pub budget: Duration,
+    /// Path of the scope being entered or left when the budget was found to be exceeded.
+    pub path: String,
+}
@@ -0,0 +1,53 @@
+//! `enter` and `leave` must not allocate once every scope has been seen.
+
+extern crate hprof;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn frame(p: &hprof::Profiler) {
+    p.start_frame();
+    {
+        let _g = p.enter("physics");
+        for _ in 0..10 {
+            let _g = p.enter("collision");
+        }
+        let _g = p.enter("update positions");
+    }
+    {
+        let _g = p.enter("render");
+        let _g = p.enter("cull");
+    }
+    p.end_frame();
+}
+
+#[test]
+fn steady_state_does_not_allocate() {
+    let p = hprof::Profiler::new("main loop");
+    p.reserve_nodes(8);
+    frame(&p);
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..100 {
+        frame(&p);
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+}
@@ -0,0 +1,114 @@
+//! Time spent paused must be cut out of the scopes open across the pause and the timeline, and
+//! scopes entered while paused must be ignored along with their leaves.
+
+extern crate hprof;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use hprof::{Clock, EventKind, ProfileSnapshot, Profiler};
+
+// A clock that only moves when told to.
+struct ManualClock(Rc<Cell<u64>>);
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+fn profiler() -> (Profiler, Rc<Cell<u64>>) {
+    let time = Rc::new(Cell::new(0));
+    let mut p = Profiler::new("main loop");
+    p.set_clock(ManualClock(time.clone()));
+    (p, time)
+}
+
+// The total time in ns of the node at the `/`-separated `path` of `frame`, if it's there.
+fn total(frame: &ProfileSnapshot, path: &str) -> Option<u64> {
+    (1..frame.nodes.len()).find(|&i| frame.path(i) == path).map(|i| frame.nodes[i].total_time)
+}
+
+#[test]
+fn pause_is_cut_from_open_scopes_and_timeline() {
+    let (p, time) = profiler();
+    p.set_timeline(true);
+    p.start_frame();
+    let work = p.enter("work");
+    time.set(10);
+    let pause = p.pause();
+    time.set(40);
+    drop(pause);
+    time.set(50);
+    drop(work);
+    time.set(60);
+    p.end_frame();
+
+    let frame = p.snapshot();
+    assert_eq!(frame.total_time(), 30);
+    assert_eq!(total(&frame, "work"), Some(20));
+    let events: Vec<(EventKind, u64)> = frame.events.iter().map(|e| (e.kind, e.time - frame.start_time)).collect();
+    // the root's, around those of `work`
+    assert_eq!(events, vec![
+        (EventKind::Enter, 0),
+        (EventKind::Enter, 0),
+        (EventKind::Leave, 20),
+        (EventKind::Leave, 30),
+    ]);
+}
+
+#[test]
+fn nested_pauses_and_leaving_while_paused() {
+    let (p, time) = profiler();
+    p.start_frame();
+    let outer = p.enter("outer");
+    time.set(5);
+    let inner = p.enter("inner");
+    time.set(10);
+    let first = p.pause();
+    time.set(15);
+    let second = p.pause();
+    time.set(20);
+    // left while paused, so it ends when the pause started
+    drop(inner);
+    time.set(25);
+    let ignored = p.enter("ignored");
+    time.set(30);
+    drop(second);
+    assert!(p.is_paused());
+    time.set(35);
+    drop(first);
+    assert!(!p.is_paused());
+    time.set(40);
+    // entered while paused, so its leave is ignored even after resuming
+    drop(ignored);
+    time.set(45);
+    drop(outer);
+    time.set(50);
+    p.end_frame();
+
+    let frame = p.snapshot();
+    assert_eq!(frame.total_time(), 25);
+    assert_eq!(total(&frame, "outer"), Some(20));
+    assert_eq!(total(&frame, "outer/inner"), Some(5));
+    assert_eq!(total(&frame, "outer/inner/ignored"), None);
+    assert_eq!(total(&frame, "outer/ignored"), None);
+}
+
+#[test]
+fn scopes_entered_after_a_pause_are_timed_normally() {
+    let (p, time) = profiler();
+    p.start_frame();
+    time.set(10);
+    let pause = p.pause();
+    time.set(20);
+    drop(pause);
+    let after = p.enter("after");
+    time.set(40);
+    drop(after);
+    p.end_frame();
+
+    let frame = p.snapshot();
+    assert_eq!(frame.total_time(), 30);
+    assert_eq!(total(&frame, "after"), Some(20));
+}
@@ -0,0 +1,182 @@
+//! Perfetto traces must be well-formed protobuf, with the fields the module docs list.
+
+extern crate hprof;
+
+use std::borrow::Cow;
+
+use hprof::export::perfetto;
+use hprof::snapshot::{ProfileSnapshot, SnapshotNode};
+use hprof::timeline::{EventKind, TimelineEvent};
+use hprof::Color;
+
+#[derive(Debug)]
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = buf[*pos];
+        *pos += 1;
+        n |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return n
+        }
+    }
+    panic!("varint longer than 10 bytes");
+}
+
+// Decode every field of the message `buf`, which must end exactly after the last one.
+fn fields(buf: &[u8]) -> Vec<(u64, Value<'_>)> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = read_varint(buf, &mut pos);
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(buf, &mut pos)),
+            2 => {
+                let len = read_varint(buf, &mut pos) as usize;
+                assert!(pos + len <= buf.len(), "field {} overruns its message", key >> 3);
+                pos += len;
+                Value::Bytes(&buf[pos - len..pos])
+            }
+            wire => panic!("unexpected wire type {}", wire),
+        };
+        fields.push((key >> 3, value));
+    }
+    assert_eq!(pos, buf.len());
+    fields
+}
+
+fn uint(fields: &[(u64, Value)], field: u64) -> Option<u64> {
+    fields.iter().find_map(|f| match *f {
+        (n, Value::Varint(v)) if n == field => Some(v),
+        _ => None,
+    })
+}
+
+fn bytes<'a>(fields: &[(u64, Value<'a>)], field: u64) -> Option<&'a [u8]> {
+    fields.iter().find_map(|f| match *f {
+        (n, Value::Bytes(b)) if n == field => Some(b),
+        _ => None,
+    })
+}
+
+fn string(fields: &[(u64, Value)], field: u64) -> Option<String> {
+    bytes(fields, field).map(|b| String::from_utf8(b.to_vec()).unwrap())
+}
+
+// The debug annotations of a track event, as (name, value) pairs with values written out.
+fn annotations(event: &[(u64, Value)]) -> Vec<(String, String)> {
+    event.iter().filter_map(|f| match *f {
+        (4, Value::Bytes(b)) => {
+            let annotation = fields(b);
+            let value = match (uint(&annotation, 3), string(&annotation, 6)) {
+                (Some(n), _) => n.to_string(),
+                (_, Some(s)) => s,
+                _ => panic!("annotation without a value"),
+            };
+            Some((string(&annotation, 10).unwrap(), value))
+        }
+        _ => None,
+    }).collect()
+}
+
+fn node(depth: u32, parent: Option<usize>, id: u64, name: &'static str, total_time: u64) -> SnapshotNode {
+    SnapshotNode {
+        id,
+        name: Cow::Borrowed(name),
+        depth,
+        parent,
+        calls: 1,
+        total_time,
+        category: None,
+        color: None,
+        annotations: Vec::new(),
+        location: None,
+        cpu_time: None,
+        blocked_time: None,
+        bytes: 0,
+    }
+}
+
+fn frame() -> ProfileSnapshot {
+    let mut frame = ProfileSnapshot { start_time: 1_000, index: 7, ..ProfileSnapshot::default() };
+    frame.nodes.push(node(0, None, 0, "main loop", 500));
+    let mut render = node(1, Some(0), 1, "render", 300);
+    render.color = Some(Color::rgb(0x12, 0xab, 0xff));
+    render.category = Some(Cow::Borrowed("gpu"));
+    frame.nodes.push(render);
+    frame
+}
+
+// The track events of the trace `buf`, as (timestamp, event fields), after checking the framing
+// of every packet and the descriptors that come first.
+fn track_events(buf: &[u8]) -> Vec<(u64, Vec<(u64, Value<'_>)>)> {
+    let packets: Vec<&[u8]> = fields(buf).into_iter().map(|f| match f {
+        (1, Value::Bytes(packet)) => packet,
+        other => panic!("unexpected field of Trace: {:?}", other),
+    }).collect();
+    let process = fields(bytes(&fields(packets[0]), 60).unwrap());
+    assert_eq!(uint(&process, 1), Some(1));
+    assert_eq!(string(&fields(bytes(&process, 3).unwrap()), 6).as_deref(), Some("hprof"));
+    let thread = fields(bytes(&fields(packets[1]), 60).unwrap());
+    let track = uint(&thread, 1).unwrap();
+    assert_eq!(string(&thread, 2).as_deref(), Some("main"));
+    assert_eq!(uint(&thread, 5), Some(1));
+    assert_eq!(string(&fields(bytes(&thread, 4).unwrap()), 5).as_deref(), Some("main"));
+
+    packets[2..].iter().enumerate().map(|(i, packet)| {
+        let packet = fields(packet);
+        assert_eq!(uint(&packet, 10), Some(1));
+        assert_eq!(uint(&packet, 13), if i == 0 { Some(1) } else { None });
+        let event = fields(bytes(&packet, 11).unwrap());
+        assert_eq!(uint(&event, 11), Some(track));
+        (uint(&packet, 8).unwrap(), event)
+    }).collect()
+}
+
+#[test]
+fn aggregate_frame() {
+    let mut buf = Vec::new();
+    perfetto::write_perfetto_trace(&mut buf, "main", &[frame()]).unwrap();
+    let events = track_events(&buf);
+    let summary: Vec<(u64, u64, Option<String>)> =
+        events.iter().map(|(time, e)| (*time, uint(e, 9).unwrap(), string(e, 23))).collect();
+    assert_eq!(summary, vec![
+        (1_000, 1, Some("main loop".to_string())),
+        (1_000, 1, Some("render".to_string())),
+        (1_300, 2, None),
+        (1_500, 2, None),
+    ]);
+    assert_eq!(annotations(&events[0].1), vec![("frame".to_string(), "7".to_string())]);
+    assert_eq!(string(&events[1].1, 22).as_deref(), Some("gpu"));
+    assert_eq!(annotations(&events[1].1), vec![("color".to_string(), "#12abff".to_string())]);
+}
+
+#[test]
+fn timeline_frame() {
+    let mut frame = frame();
+    let event = |kind, id, time, payload: Option<&str>, cpu| {
+        TimelineEvent { kind, id, time, payload: payload.map(String::from), cpu }
+    };
+    frame.events = vec![
+        event(EventKind::Enter, 0, 1_000, None, None),
+        event(EventKind::Enter, 1, 1_100, Some("draw calls: 300"), Some(2)),
+        event(EventKind::Leave, 1, 1_400, None, Some(3)),
+        event(EventKind::Leave, 0, 1_500, None, None),
+    ];
+    let mut buf = Vec::new();
+    perfetto::write_perfetto_trace(&mut buf, "main", &[frame]).unwrap();
+    let events = track_events(&buf);
+    let times: Vec<(u64, u64)> = events.iter().map(|(time, e)| (*time, uint(e, 9).unwrap())).collect();
+    assert_eq!(times, vec![(1_000, 1), (1_100, 1), (1_400, 2), (1_500, 2)]);
+    assert_eq!(annotations(&events[1].1), vec![
+        ("color".to_string(), "#12abff".to_string()),
+        ("payload".to_string(), "draw calls: 300".to_string()),
+        ("cpu".to_string(), "2".to_string()),
+    ]);
+    assert_eq!(annotations(&events[2].1), vec![("end_cpu".to_string(), "3".to_string())]);
+}
@@ -0,0 +1,171 @@
+//! Recordings must read back exactly as they were written, in both the text and the
+//! delta-encoded formats.
+
+extern crate hprof;
+
+use std::borrow::Cow;
+use std::time::{Duration, UNIX_EPOCH};
+
+use hprof::delta::{self, DeltaWriter};
+use hprof::recording::Recording;
+use hprof::snapshot::{ProfileSnapshot, SnapshotNode};
+use hprof::timeline::{EventKind, TimelineEvent};
+use hprof::Color;
+
+fn node(depth: u32, parent: Option<usize>, id: u64, name: &'static str) -> SnapshotNode {
+    SnapshotNode {
+        id,
+        name: Cow::Borrowed(name),
+        depth,
+        parent,
+        calls: 1,
+        total_time: 0,
+        category: None,
+        color: None,
+        annotations: Vec::new(),
+        location: None,
+        cpu_time: None,
+        blocked_time: None,
+        bytes: 0,
+    }
+}
+
+fn event(kind: EventKind, id: u64, time: u64, payload: Option<&str>, cpu: Option<u32>) -> TimelineEvent {
+    TimelineEvent { kind, id, time, payload: payload.map(String::from), cpu }
+}
+
+// Frame `i` of a session with a physics and a render scope, and a debug overlay drawn in
+// frames 3 and 4 only, so the set of nodes changes twice.
+fn frame(i: u64) -> ProfileSnapshot {
+    let start = 1_000_000 + i * 16_000_000;
+    let mut frame = ProfileSnapshot {
+        start_time: start,
+        index: i,
+        wall_time: Some(UNIX_EPOCH + Duration::from_nanos(1_500_000_000_123_456_789 + i * 16_000_000)),
+        end_wall_time: Some(UNIX_EPOCH + Duration::from_nanos(1_500_000_000_139_000_000 + i * 16_000_000)),
+        ..ProfileSnapshot::default()
+    };
+
+    let mut root = node(0, None, 0, "main loop");
+    root.total_time = 15_000_000 + i * 1_000;
+    root.cpu_time = Some(12_000_000 + i * 500);
+    root.blocked_time = Some(3_000_000 - i * 500);
+    frame.nodes.push(root);
+
+    let mut physics = node(1, Some(0), 1, "physics");
+    physics.calls = 1 + i as u32 % 3;
+    physics.total_time = 5_000_000 + i * 700;
+    physics.category = Some(Cow::Borrowed("simulation"));
+    physics.cpu_time = if i.is_multiple_of(2) { Some(4_900_000 + i) } else { None };
+    frame.nodes.push(physics);
+
+    let mut collision = node(2, Some(1), 2, "collision\twith\ttabs");
+    collision.calls = 10;
+    collision.total_time = 2_000_000 - i * 300;
+    frame.nodes.push(collision);
+
+    let mut render = node(1, Some(0), 3, "render");
+    render.total_time = 9_000_000;
+    render.color = Some(Color::rgb(0x12, 0xab, 0xff));
+    render.blocked_time = Some(8_000_000 + i);
+    if i == 1 {
+        render.annotations.push("shader recompile".to_string());
+        render.annotations.push("line one\nline two \\ with a backslash".to_string());
+    }
+    frame.nodes.push(render);
+
+    if i == 3 || i == 4 {
+        let mut debug = node(1, Some(0), 4, "debug overlay");
+        debug.total_time = 500_000;
+        frame.nodes.push(debug);
+    }
+
+    if i == 1 || i == 3 {
+        frame.tags.push(("level".to_string(), format!("level {}", i)));
+        frame.tags.push(("weather\tkind".to_string(), "rain\nand fog".to_string()));
+    }
+
+    frame.events.push(event(EventKind::Enter, 1, start + 10, Some("entities: 42"), Some(3)));
+    frame.events.push(event(EventKind::Leave, 1, start + 5_000_010, None, Some(5)));
+    frame.events.push(event(EventKind::Enter, 3, start + 5_000_020, None, None));
+    // a scope that isn't in the tree, such as one collapsed away
+    frame.events.push(event(EventKind::Enter, 99, start + 5_000_030, Some("tab\there"), None));
+    frame.events.push(event(EventKind::Leave, 99, start + 5_000_040, None, Some(0)));
+    frame.events.push(event(EventKind::Leave, 3, start + 14_000_020, None, None));
+    frame
+}
+
+fn recording() -> Recording {
+    let mut recording = Recording::new();
+    recording.metadata.insert("build".to_string(), "1.2.3\twith a tab".to_string());
+    recording.metadata.insert("gpu".to_string(), "none".to_string());
+    recording.frames = (0..7).map(frame).collect();
+    recording
+}
+
+fn assert_same(a: &Recording, b: &Recording) {
+    assert_eq!(a.metadata, b.metadata);
+    assert_eq!(a.frames.len(), b.frames.len());
+    for (a, b) in a.frames.iter().zip(&b.frames) {
+        assert_eq!(a.start_time, b.start_time);
+        assert_eq!(a.index, b.index);
+        assert_eq!(a.wall_time, b.wall_time);
+        assert_eq!(a.end_wall_time, b.end_wall_time);
+        assert_eq!(a.tags, b.tags);
+        assert_eq!(a.events, b.events);
+        assert_eq!(a.nodes.len(), b.nodes.len());
+        for (a, b) in a.nodes.iter().zip(&b.nodes) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.depth, b.depth);
+            assert_eq!(a.parent, b.parent);
+            assert_eq!(a.calls, b.calls);
+            assert_eq!(a.total_time, b.total_time);
+            assert_eq!(a.category, b.category);
+            assert_eq!(a.color, b.color);
+            assert_eq!(a.annotations, b.annotations);
+            assert_eq!(a.cpu_time, b.cpu_time);
+            assert_eq!(a.blocked_time, b.blocked_time);
+        }
+    }
+}
+
+#[test]
+fn text_round_trip() {
+    let recording = recording();
+    let mut buf = Vec::new();
+    recording.write_to(&mut buf).unwrap();
+    assert!(buf.starts_with(b"hprof-recording 2\n"));
+    assert_same(&recording, &Recording::read_from(&buf[..]).unwrap());
+}
+
+#[test]
+fn delta_round_trip() {
+    let recording = recording();
+    let mut buf = Vec::new();
+    recording.write_delta_to(&mut buf).unwrap();
+    assert!(buf.starts_with(delta::MAGIC));
+    assert_same(&recording, &Recording::read_from(&buf[..]).unwrap());
+}
+
+#[test]
+fn delta_round_trip_with_frequent_keyframes() {
+    let recording = recording();
+    let mut writer = DeltaWriter::new(Vec::new(), &recording.metadata).unwrap();
+    writer.set_keyframe_interval(2);
+    for frame in &recording.frames {
+        writer.write_frame(frame).unwrap();
+    }
+    let buf = writer.into_inner();
+    assert_same(&recording, &Recording::read_from(&buf[..]).unwrap());
+}
+
+#[test]
+fn delta_is_smaller() {
+    let recording = recording();
+    let mut text = Vec::new();
+    recording.write_to(&mut text).unwrap();
+    let mut delta = Vec::new();
+    recording.write_delta_to(&mut delta).unwrap();
+    assert!(delta.len() < text.len());
+}